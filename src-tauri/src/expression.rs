@@ -0,0 +1,768 @@
+// Expression Module - Typed expression evaluator for workflow conditions
+// Dùng cho `condition`, `while` và `waitCondition`: so sánh, logic, số học, chuỗi, regex
+
+use serde_json::Value;
+
+/// Error produced while parsing or evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub message: String,
+    /// 1-based character column in the source expression (0 = runtime error)
+    pub column: usize,
+}
+
+impl std::fmt::Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.column > 0 {
+            write!(f, "{} at column {}", self.message, self.column)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+fn parse_error(message: impl Into<String>, column: usize) -> ExprError {
+    ExprError { message: message.into(), column }
+}
+
+fn eval_error(message: impl Into<String>) -> ExprError {
+    ExprError { message: message.into(), column: 0 }
+}
+
+// ============================================
+// Tokenizer
+// ============================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+/// Operators ordered so that longer symbols are matched first
+const OPERATORS: &[&str] = &[
+    "{{", "}}", "==", "!=", "<=", ">=", "&&", "||",
+    "<", ">", "!", "+", "-", "*", "/", "%", "(", ")", "[", "]", ".", ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExprError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text.parse::<f64>()
+                .map_err(|_| parse_error(format!("Invalid number '{}'", text), column))?;
+            tokens.push((Token::Number(number), column));
+            continue;
+        }
+
+        if c == '"' || c == '\'' {
+            let quote = c;
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(parse_error("Unterminated string literal", column)),
+                    Some(&ch) if ch == quote => {
+                        i += 1;
+                        break;
+                    }
+                    Some('\\') => {
+                        let escaped = chars.get(i + 1)
+                            .ok_or_else(|| parse_error("Unterminated string literal", column))?;
+                        text.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => *other,
+                        });
+                        i += 2;
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Token::Str(text), column));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), column));
+            continue;
+        }
+
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push((Token::Op(op), column));
+            i += op.chars().count();
+            continue;
+        }
+
+        if c == '=' {
+            return Err(parse_error("Unexpected '=' (use '==' for comparison)", column));
+        }
+        return Err(parse_error(format!("Unexpected character '{}'", c), column));
+    }
+
+    Ok(tokens)
+}
+
+// ============================================
+// Parser
+// ============================================
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Var(String),
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(String, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// Keyword operators usable between two operands (e.g. `name contains "abc"`)
+const WORD_OPERATORS: &[&str] = &["contains", "matches", "startsWith", "endsWith"];
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end_column: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map(|(_, c)| *c).unwrap_or(self.end_column)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(w)) if w == word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), ExprError> {
+        if self.eat_op(op) {
+            Ok(())
+        } else {
+            Err(parse_error(format!("Expected '{}'", op), self.column()))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_and()?;
+        while self.eat_op("||") || self.eat_word("or") {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_not()?;
+        while self.eat_op("&&") || self.eat_word("and") {
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, ExprError> {
+        if self.eat_word("not") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ExprError> {
+        let left = self.parse_additive()?;
+        let op = match self.peek() {
+            Some(Token::Op(op)) if ["==", "!=", "<", "<=", ">", ">="].contains(op) => op.to_string(),
+            Some(Token::Ident(word)) if WORD_OPERATORS.contains(&word.as_str()) => word.clone(),
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_additive()?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op)) if *op == "+" || *op == "-" => op.to_string(),
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_multiplicative()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op)) if *op == "*" || *op == "/" || *op == "%" => op.to_string(),
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat_op("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat_op("-") {
+            return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            if self.eat_op(".") {
+                let column = self.column();
+                match self.next() {
+                    Some(Token::Ident(name)) => expr = Expr::Member(Box::new(expr), name),
+                    Some(Token::Number(n)) if n.fract() == 0.0 => {
+                        expr = Expr::Index(Box::new(expr), Box::new(Expr::Literal(number_value(n))))
+                    }
+                    _ => return Err(parse_error("Expected property name after '.'", column)),
+                }
+            } else if self.eat_op("[") {
+                let index = self.parse_or()?;
+                self.expect_op("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ExprError> {
+        let column = self.column();
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(number_value(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Op("(")) => {
+                let expr = self.parse_or()?;
+                self.expect_op(")")?;
+                Ok(expr)
+            }
+            // `{{path}}` placeholders are variable references, not text substitution
+            Some(Token::Op("{{")) => {
+                let expr = self.parse_or()?;
+                self.expect_op("}}")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if self.eat_op("(") => {
+                    let mut args = Vec::new();
+                    if !self.eat_op(")") {
+                        loop {
+                            args.push(self.parse_or()?);
+                            if self.eat_op(")") {
+                                break;
+                            }
+                            self.expect_op(",")?;
+                        }
+                    }
+                    Ok(Expr::Call(name, args))
+                }
+                _ => Ok(Expr::Var(name)),
+            },
+            Some(Token::Op(op)) => Err(parse_error(format!("Unexpected '{}'", op), column)),
+            None => Err(parse_error("Unexpected end of expression", column)),
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<Expr, ExprError> {
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        return Err(parse_error("Empty expression", 1));
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end_column: source.chars().count() + 1,
+    };
+    let expr = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parse_error("Unexpected token", parser.column()));
+    }
+    Ok(expr)
+}
+
+// ============================================
+// Evaluation
+// ============================================
+
+/// Evaluate an expression. `lookup` resolves top-level variable names.
+pub fn evaluate(source: &str, lookup: &dyn Fn(&str) -> Option<Value>) -> Result<Value, ExprError> {
+    let expr = parse(source)?;
+    eval(&expr, lookup)
+}
+
+/// Parse an expression without evaluating it and return the variable names it reads
+pub fn referenced_variables(source: &str) -> Result<Vec<String>, ExprError> {
+    fn collect(expr: &Expr, names: &mut Vec<String>) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Var(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            Expr::Member(inner, _) | Expr::Not(inner) | Expr::Neg(inner) => collect(inner, names),
+            Expr::Index(a, b) | Expr::And(a, b) | Expr::Or(a, b) | Expr::Binary(_, a, b) => {
+                collect(a, names);
+                collect(b, names);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| collect(arg, names)),
+        }
+    }
+    
+    let mut names = Vec::new();
+    collect(&parse(source)?, &mut names);
+    Ok(names)
+}
+
+/// Truthiness used by conditions: keeps the legacy "false"/"0"/"no" strings falsy
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|f| f != 0.0).unwrap_or(false),
+        Value::String(s) => !matches!(
+            s.trim().to_lowercase().as_str(),
+            "" | "false" | "0" | "no" | "null"
+        ),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Render a value the way it appears inside text
+pub fn to_display_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+        Value::from(n as i64)
+    } else {
+        serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+fn to_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn loose_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(_), Value::String(_)) | (Value::String(_), Value::Number(_)) => {
+            match (to_number(a), to_number(b)) {
+                (Some(x), Some(y)) => x == y,
+                _ => false,
+            }
+        }
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Bool(x), Value::String(s)) | (Value::String(s), Value::Bool(x)) => {
+            s.trim().eq_ignore_ascii_case(&x.to_string())
+        }
+        _ => a == b,
+    }
+}
+
+fn compare(op: &str, a: &Value, b: &Value) -> Result<bool, ExprError> {
+    let ordering = match (a, b) {
+        (Value::String(x), Value::String(y)) => match (x.trim().parse::<f64>(), y.trim().parse::<f64>()) {
+            (Ok(nx), Ok(ny)) => nx.partial_cmp(&ny),
+            _ => Some(x.cmp(y)),
+        },
+        _ => match (to_number(a), to_number(b)) {
+            (Some(x), Some(y)) if !a.is_null() && !b.is_null() => x.partial_cmp(&y),
+            _ => {
+                return Err(eval_error(format!(
+                    "Cannot compare {} with {} using '{}'",
+                    type_name(a), type_name(b), op
+                )))
+            }
+        },
+    };
+    let ordering = ordering.ok_or_else(|| eval_error("Cannot compare NaN"))?;
+    Ok(match op {
+        "<" => ordering.is_lt(),
+        "<=" => ordering.is_le(),
+        ">" => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    })
+}
+
+fn contains(haystack: &Value, needle: &Value) -> bool {
+    match haystack {
+        Value::String(s) => s.contains(&to_display_string(needle)),
+        Value::Array(items) => items.iter().any(|item| loose_eq(item, needle)),
+        Value::Object(map) => map.contains_key(&to_display_string(needle)),
+        _ => false,
+    }
+}
+
+fn regex_matches(text: &Value, pattern: &Value) -> Result<bool, ExprError> {
+    let pattern = to_display_string(pattern);
+    let re = regex::Regex::new(&pattern)
+        .map_err(|e| eval_error(format!("Invalid regex '{}': {}", pattern, e)))?;
+    Ok(re.is_match(&to_display_string(text)))
+}
+
+fn arithmetic(op: &str, a: &Value, b: &Value) -> Result<Value, ExprError> {
+    // String concatenation when either side is a non-numeric string
+    if op == "+" && (a.is_string() || b.is_string()) && (to_number(a).is_none() || to_number(b).is_none()) {
+        return Ok(Value::String(format!("{}{}", to_display_string(a), to_display_string(b))));
+    }
+    let (x, y) = match (to_number(a), to_number(b)) {
+        (Some(x), Some(y)) if !a.is_null() && !b.is_null() => (x, y),
+        _ => {
+            return Err(eval_error(format!(
+                "Cannot apply '{}' to {} and {}",
+                op, type_name(a), type_name(b)
+            )))
+        }
+    };
+    let result = match op {
+        "+" => x + y,
+        "-" => x - y,
+        "*" => x * y,
+        "/" | "%" if y == 0.0 => return Err(eval_error("Division by zero")),
+        "/" => x / y,
+        _ => x % y,
+    };
+    Ok(number_value(result))
+}
+
+fn length(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.chars().count(),
+        Value::Array(a) => a.len(),
+        Value::Object(o) => o.len(),
+        _ => 0,
+    }
+}
+
+fn call(name: &str, args: &[Value]) -> Result<Value, ExprError> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Null);
+    let expect = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(eval_error(format!("{}() expects {} argument(s), got {}", name, n, args.len())))
+        }
+    };
+    let number_arg = |i: usize| {
+        to_number(&arg(i)).ok_or_else(|| eval_error(format!("{}() expects a number", name)))
+    };
+
+    match name {
+        "len" | "length" => { expect(1)?; Ok(Value::from(length(&arg(0)))) }
+        "isNull" => { expect(1)?; Ok(Value::Bool(arg(0).is_null())) }
+        "exists" => { expect(1)?; Ok(Value::Bool(!arg(0).is_null())) }
+        "isEmpty" => {
+            expect(1)?;
+            let value = arg(0);
+            Ok(Value::Bool(value.is_null() || (!value.is_number() && !value.is_boolean() && length(&value) == 0)))
+        }
+        "contains" => { expect(2)?; Ok(Value::Bool(contains(&arg(0), &arg(1)))) }
+        "matches" => { expect(2)?; Ok(Value::Bool(regex_matches(&arg(0), &arg(1))?)) }
+        "startsWith" => { expect(2)?; Ok(Value::Bool(to_display_string(&arg(0)).starts_with(&to_display_string(&arg(1))))) }
+        "endsWith" => { expect(2)?; Ok(Value::Bool(to_display_string(&arg(0)).ends_with(&to_display_string(&arg(1))))) }
+        "lower" => { expect(1)?; Ok(Value::String(to_display_string(&arg(0)).to_lowercase())) }
+        "upper" => { expect(1)?; Ok(Value::String(to_display_string(&arg(0)).to_uppercase())) }
+        "trim" => { expect(1)?; Ok(Value::String(to_display_string(&arg(0)).trim().to_string())) }
+        "string" => { expect(1)?; Ok(Value::String(to_display_string(&arg(0)))) }
+        "number" => { expect(1)?; Ok(number_value(number_arg(0)?)) }
+        "abs" => { expect(1)?; Ok(number_value(number_arg(0)?.abs())) }
+        "round" => { expect(1)?; Ok(number_value(number_arg(0)?.round())) }
+        "floor" => { expect(1)?; Ok(number_value(number_arg(0)?.floor())) }
+        "ceil" => { expect(1)?; Ok(number_value(number_arg(0)?.ceil())) }
+        "min" | "max" => {
+            if args.is_empty() {
+                return Err(eval_error(format!("{}() expects at least 1 argument", name)));
+            }
+            let mut best = number_arg(0)?;
+            for i in 1..args.len() {
+                let n = number_arg(i)?;
+                best = if name == "min" { best.min(n) } else { best.max(n) };
+            }
+            Ok(number_value(best))
+        }
+        "random" => {
+            use rand::Rng;
+            let mut rng = rand::thread_rng();
+            match args.len() {
+                // random() -> float in [0, 1), random(min, max) -> integer in [min, max]
+                0 => Ok(number_value(rng.gen::<f64>())),
+                2 => {
+                    let (low, high) = (number_arg(0)?.round() as i64, number_arg(1)?.round() as i64);
+                    Ok(Value::from(rng.gen_range(low.min(high)..=low.max(high))))
+                }
+                n => Err(eval_error(format!("random() expects 0 or 2 arguments, got {}", n))),
+            }
+        }
+        "randomChoice" => {
+            use rand::seq::SliceRandom;
+            expect(1)?;
+            match arg(0) {
+                Value::Array(items) => Ok(items.choose(&mut rand::thread_rng()).cloned().unwrap_or(Value::Null)),
+                other => Err(eval_error(format!("randomChoice() expects an array, got {}", type_name(&other)))),
+            }
+        }
+        "now" => { expect(0)?; Ok(Value::String(chrono::Local::now().to_rfc3339())) }
+        _ => Err(eval_error(format!("Unknown function '{}'", name))),
+    }
+}
+
+fn eval(expr: &Expr, lookup: &dyn Fn(&str) -> Option<Value>) -> Result<Value, ExprError> {
+    match expr {
+        Expr::Literal(v) => Ok(v.clone()),
+        Expr::Var(name) => Ok(lookup(name).unwrap_or(Value::Null)),
+        Expr::Member(target, key) => {
+            let target = eval(target, lookup)?;
+            Ok(target.get(key).cloned().unwrap_or(Value::Null))
+        }
+        Expr::Index(target, index) => {
+            let target = eval(target, lookup)?;
+            let index = eval(index, lookup)?;
+            let found = match (&target, &index) {
+                (Value::Array(items), _) => to_number(&index)
+                    .filter(|n| *n >= 0.0)
+                    .and_then(|n| items.get(n as usize)),
+                (Value::Object(map), _) => map.get(&to_display_string(&index)),
+                _ => None,
+            };
+            Ok(found.cloned().unwrap_or(Value::Null))
+        }
+        Expr::Not(inner) => Ok(Value::Bool(!is_truthy(&eval(inner, lookup)?))),
+        Expr::Neg(inner) => {
+            let value = eval(inner, lookup)?;
+            let n = to_number(&value)
+                .filter(|_| !value.is_null())
+                .ok_or_else(|| eval_error(format!("Cannot negate {}", type_name(&value))))?;
+            Ok(number_value(-n))
+        }
+        Expr::And(left, right) => Ok(Value::Bool(
+            is_truthy(&eval(left, lookup)?) && is_truthy(&eval(right, lookup)?),
+        )),
+        Expr::Or(left, right) => Ok(Value::Bool(
+            is_truthy(&eval(left, lookup)?) || is_truthy(&eval(right, lookup)?),
+        )),
+        Expr::Binary(op, left, right) => {
+            let a = eval(left, lookup)?;
+            let b = eval(right, lookup)?;
+            match op.as_str() {
+                "==" => Ok(Value::Bool(loose_eq(&a, &b))),
+                "!=" => Ok(Value::Bool(!loose_eq(&a, &b))),
+                "<" | "<=" | ">" | ">=" => Ok(Value::Bool(compare(op, &a, &b)?)),
+                "contains" => Ok(Value::Bool(contains(&a, &b))),
+                "matches" => Ok(Value::Bool(regex_matches(&a, &b)?)),
+                "startsWith" => Ok(Value::Bool(to_display_string(&a).starts_with(&to_display_string(&b)))),
+                "endsWith" => Ok(Value::Bool(to_display_string(&a).ends_with(&to_display_string(&b)))),
+                _ => arithmetic(op, &a, &b),
+            }
+        }
+        Expr::Call(name, args) => {
+            let values = args.iter()
+                .map(|a| eval(a, lookup))
+                .collect::<Result<Vec<_>, _>>()?;
+            call(name, &values)
+        }
+    }
+}
+
+// ============================================
+// Python Export
+// ============================================
+
+/// Translate an expression to Python for exported workflow scripts. The code calls the
+/// script runtime (`V`, `_member`, `_binary`, `_call`...) which mirrors `eval` above.
+pub fn to_python(source: &str) -> Result<String, ExprError> {
+    fn emit(expr: &Expr) -> String {
+        match expr {
+            Expr::Literal(value) => python_literal(value),
+            Expr::Var(name) => format!("V({})", python_str(name)),
+            Expr::Member(target, key) => format!("_member({}, {})", emit(target), python_str(key)),
+            Expr::Index(target, index) => format!("_index({}, {})", emit(target), emit(index)),
+            Expr::Not(inner) => format!("(not _truthy({}))", emit(inner)),
+            Expr::Neg(inner) => format!("_neg({})", emit(inner)),
+            Expr::And(left, right) => format!("(_truthy({}) and _truthy({}))", emit(left), emit(right)),
+            Expr::Or(left, right) => format!("(_truthy({}) or _truthy({}))", emit(left), emit(right)),
+            Expr::Binary(op, left, right) => format!("_binary({}, {}, {})", python_str(op), emit(left), emit(right)),
+            Expr::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(emit).collect();
+                format!("_call({}, [{}])", python_str(name), args.join(", "))
+            }
+        }
+    }
+
+    Ok(emit(&parse(source)?))
+}
+
+/// Python source for a JSON value (JSON string escapes are valid in Python literals)
+pub fn python_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "None".to_string(),
+        Value::Bool(b) => if *b { "True" } else { "False" }.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => python_str(s),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(python_literal).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<String> = map.iter()
+                .map(|(key, value)| format!("{}: {}", python_str(key), python_literal(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+    }
+}
+
+pub fn python_str(text: &str) -> String {
+    Value::String(text.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scope(name: &str) -> Option<Value> {
+        match name {
+            "count" => Some(json!(7)),
+            "limit" => Some(json!("5")),
+            "result" => Some(json!({"status": "ok", "items": [1, 2, 3]})),
+            "user" => Some(json!("Alice Smith")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_comparisons_and_logic() {
+        assert_eq!(evaluate("{{count}} > 5", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("{{result.status}} == \"ok\"", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("count > limit && !(count >= 10)", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("len(result.items) == 3 or missing", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("missing == null", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("result.items[1] * 2 + count % 4", &scope).unwrap(), json!(7));
+    }
+
+    #[test]
+    fn test_string_operators() {
+        assert_eq!(evaluate("user contains 'Smith'", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("user matches '^A\\\\w+ S'", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("lower(user) startsWith 'alice'", &scope).unwrap(), json!(true));
+        assert_eq!(evaluate("'n=' + count", &scope).unwrap(), json!("n=7"));
+    }
+
+    #[test]
+    fn test_errors_and_truthiness() {
+        let err = evaluate("count >", &scope).unwrap_err();
+        assert_eq!(err.column, 8);
+        assert!(evaluate("count = 5", &scope).unwrap_err().message.contains("=="));
+        assert!(evaluate("result > 1", &scope).is_err());
+        assert!(!is_truthy(&json!("false")));
+        assert!(is_truthy(&json!("yes")));
+    }
+
+    #[test]
+    fn test_to_python() {
+        assert_eq!(
+            to_python("{{count}} > 5 && !done").unwrap(),
+            "(_truthy(_binary(\">\", V(\"count\"), 5)) and _truthy((not _truthy(V(\"done\")))))"
+        );
+        assert_eq!(
+            to_python("len(result.items[0]) == 'a\"b'").unwrap(),
+            "_binary(\"==\", _call(\"len\", [_index(_member(V(\"result\"), \"items\"), 0)]), \"a\\\"b\")"
+        );
+        assert_eq!(to_python("count >").unwrap_err().column, 8);
+    }
+}
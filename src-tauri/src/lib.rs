@@ -11,15 +11,26 @@ mod config;
 // mod custom_tools;
 // mod device_tools;
 mod emulator;
+mod expression;
 mod license;
 mod macro_cmd;
+mod macro_workflow;
 mod portal_client;
+mod run_history;
 // mod prompt_templates;
+mod selector;
+mod skill;
+mod template;
 mod task;
 // mod telemetry;
 // mod trajectory;
 mod utils;
 mod workflow;
+mod workflow_validator;
+mod workflow_schema;
+mod workflow_inputs;
+mod workflow_library;
+mod workflow_export;
 
 pub use adb::*;
 // pub use agents::*;
@@ -31,12 +42,20 @@ pub use config::*;
 pub use emulator::*;
 pub use license::*;
 pub use macro_cmd::*;
+pub use macro_workflow::*;
+pub use run_history::*;
+pub use skill::*;
 // pub use prompt_templates::*;
 pub use task::*;
 // pub use telemetry::*;
 // pub use trajectory::*;
 pub use utils::*;
 pub use workflow::*;
+pub use workflow_validator::*;
+pub use workflow_schema::*;
+pub use workflow_inputs::*;
+pub use workflow_library::*;
+pub use workflow_export::*;
 
 use tauri::Manager;
 use tauri::Emitter;
@@ -719,6 +738,7 @@ pub fn run() {
             macro_cmd::list_macros,
             macro_cmd::record_macro,
             macro_cmd::replay_macro,
+            macro_workflow::convert_macro_to_workflow,
             macro_cmd::delete_macro,
             macro_cmd::export_macro,
             macro_cmd::import_macro,
@@ -752,6 +772,43 @@ pub fn run() {
             workflow::run_python_script,
             workflow::run_workflow,
             workflow::run_workflow_python,
+            workflow::list_workflow_runs,
+            workflow::cancel_workflow,
+            workflow::pause_workflow,
+            workflow::continue_workflow,
+            workflow::step_workflow,
+            workflow::skip_workflow_step,
+            workflow::set_workflow_variable,
+            workflow::set_workflow_breakpoints,
+            workflow::get_workflow_debug_state,
+            workflow::resume_workflow,
+            workflow::list_workflow_checkpoints,
+            workflow::delete_workflow_checkpoint,
+            workflow_validator::validate_workflow,
+            workflow_schema::get_workflow_schema,
+            workflow_schema::check_workflow_schema,
+            workflow_inputs::validate_workflow_inputs,
+            workflow_library::list_library_workflows,
+            workflow_library::load_library_workflow,
+            workflow_library::save_library_workflow,
+            workflow_library::duplicate_library_workflow,
+            workflow_library::delete_library_workflow,
+            workflow_library::list_workflow_versions,
+            workflow_library::get_workflow_version,
+            workflow_library::diff_workflow_versions,
+            workflow_library::rollback_workflow,
+            workflow_export::export_workflow_python,
+            skill::list_skills,
+            skill::get_skill,
+            skill::save_skill,
+            skill::delete_skill,
+            run_history::list_run_history,
+            run_history::get_run_record,
+            run_history::delete_run_record,
+            run_history::clear_run_history,
+            run_history::get_history_retention,
+            run_history::set_history_retention,
+            run_history::rerun_from_history,
             workflow::calibrate_workflow,
             workflow::record_workflow,
             workflow::execute_actions,
@@ -610,6 +610,8 @@ pub async fn replay_macro(
     dry_run: Option<bool>,
 ) -> Result<MacroResult, String> {
     use tauri::Emitter;
+    let started = std::time::Instant::now();
+    let started_at = chrono::Utc::now().to_rfc3339();
     
     // Build droidrun macro replay command
     let mut args = vec![
@@ -662,34 +664,40 @@ pub async fn replay_macro(
     let stdout = child.stdout.take().unwrap();
     let stderr = child.stderr.take().unwrap();
     
-    // Stream output
+    // Stream output (and keep it for the run history)
     let window_clone = window.clone();
     let stdout_handle = tokio::spawn(async move {
+        let mut lines = Vec::new();
         let mut reader = BufReader::new(stdout).lines();
         while let Ok(Some(line)) = reader.next_line().await {
             let _ = window_clone.emit("macro-output", &line);
+            lines.push(line);
         }
+        lines
     });
     
     let window_clone2 = window.clone();
     let stderr_handle = tokio::spawn(async move {
+        let mut lines = Vec::new();
         let mut reader = BufReader::new(stderr).lines();
         while let Ok(Some(line)) = reader.next_line().await {
             let _ = window_clone2.emit("macro-output", &line);
+            lines.push(line);
         }
+        lines
     });
     
-    let _ = stdout_handle.await;
-    let _ = stderr_handle.await;
+    let mut logs = stdout_handle.await.unwrap_or_default();
+    logs.extend(stderr_handle.await.unwrap_or_default());
     
     let status = child.wait().await
         .map_err(|e| format!("Error waiting for process: {}", e))?;
     
-    if status.success() {
+    let result = if status.success() {
         let _ = window.emit("macro-output", "[SUCCESS] Macro replay completed!");
         Ok(MacroResult {
             success: true,
-            macro_path,
+            macro_path: macro_path.clone(),
             message: "Macro replayed successfully".to_string(),
             ai_remaining: None,
             ai_used: None,
@@ -697,7 +705,40 @@ pub async fn replay_macro(
     } else {
         let _ = window.emit("macro-output", "[ERROR] Failed to replay macro");
         Err("Failed to replay macro".to_string())
-    }
+    };
+    
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let screenshots = if result.is_err() {
+        crate::run_history::capture_failure_screenshot(&device_id, &run_id).await
+    } else {
+        vec![]
+    };
+    crate::run_history::record_run(crate::run_history::RunRecord {
+        run_id,
+        kind: "macro".to_string(),
+        target_id: Some(macro_path.clone()),
+        name: PathBuf::from(&macro_path).file_name().map(|n| n.to_string_lossy().to_string()),
+        device_id,
+        status: if result.is_ok() { "completed" } else { "failed" }.to_string(),
+        success: result.is_ok(),
+        inputs: std::collections::HashMap::from([
+            ("delay".to_string(), serde_json::json!(delay_val)),
+            ("startFrom".to_string(), serde_json::json!(start_from)),
+            ("maxSteps".to_string(), serde_json::json!(max_steps)),
+            ("dryRun".to_string(), serde_json::json!(dry_run)),
+        ]),
+        outputs: std::collections::HashMap::new(),
+        logs: crate::run_history::tail_lines(&logs),
+        duration_ms: started.elapsed().as_millis() as i64,
+        error: result.as_ref().err().cloned(),
+        screenshots,
+        started_at,
+        finished_at: chrono::Utc::now().to_rfc3339(),
+        resumed_from: None,
+        workflow: None,
+    }).await;
+    
+    result
 }
 
 /// Delete a macro
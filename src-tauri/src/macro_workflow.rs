@@ -0,0 +1,324 @@
+// Macro Workflow Module - Convert a recorded macro directory into an editable WorkflowDefinition
+// Tap theo element_text (giữ toạ độ làm fallback), text đã gõ thành input, chờ thêm ở cuối mỗi bước của agent trong trajectory
+
+use crate::workflow::{WorkflowDefinition, WorkflowInput, WorkflowStep};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+/// Extra wait where the agent ended a step. Recordings carry no timestamps, but the agent
+/// only acted again after a fresh screenshot, so the screen had time to settle there.
+const STEP_SETTLE_MS: i64 = 1500;
+
+/// Agent calls in trajectory code and the macro.json action types they record, in order.
+/// `type(text, index)` taps the field first, so its tap is optional.
+const AGENT_CALLS: &[(&str, &[&str])] = &[
+    ("click", &["tap"]),
+    ("tap", &["tap"]),
+    ("type", &["tap?", "input_text"]),
+    ("input_text", &["tap?", "input_text"]),
+    ("system_button", &["key_press"]),
+    ("press_key", &["key_press"]),
+    ("swipe", &["swipe"]),
+    ("wait", &["wait"]),
+    ("open_app", &["start_app"]),
+    ("start_app", &["start_app"]),
+];
+
+// ============================================
+// Commands
+// ============================================
+
+/// Turn a macro directory (macro.json + trajectory.json) into a workflow definition
+#[command]
+pub async fn convert_macro_to_workflow(macro_path: String, name: Option<String>) -> Result<WorkflowDefinition, String> {
+    let workflow = convert_macro_dir(&PathBuf::from(&macro_path), name)?;
+    println!("[MACRO] Converted {} into workflow '{}' ({} steps, {} inputs)",
+        macro_path, workflow.id, workflow.steps.len(), workflow.inputs.len());
+    Ok(workflow)
+}
+
+// ============================================
+// Conversion
+// ============================================
+
+fn convert_macro_dir(dir: &Path, name: Option<String>) -> Result<WorkflowDefinition, String> {
+    let read_json = |file: &str| -> Option<Result<Value, String>> {
+        let path = dir.join(file);
+        let content = std::fs::read_to_string(&path).ok()?;
+        Some(serde_json::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e)))
+    };
+
+    let macro_json = read_json("macro.json").ok_or_else(|| format!("No macro.json in {}", dir.display()))??;
+    let trajectory = read_json("trajectory.json").and_then(|r| r.ok());
+    let dir_name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "macro".to_string());
+    macro_to_workflow(&macro_json, trajectory.as_ref(), &dir_name, name)
+}
+
+pub fn macro_to_workflow(
+    macro_json: &Value,
+    trajectory: Option<&Value>,
+    dir_name: &str,
+    name: Option<String>,
+) -> Result<WorkflowDefinition, String> {
+    let actions = macro_json.get("actions").and_then(|a| a.as_array())
+        .ok_or("macro.json has no 'actions' list")?;
+    let step_ends = agent_step_ends(actions, trajectory);
+
+    let mut steps: Vec<WorkflowStep> = Vec::new();
+    let mut inputs: Vec<WorkflowInput> = Vec::new();
+    // Typed text -> input name, so the same text typed twice uses one input
+    let mut text_inputs: HashMap<String, String> = HashMap::new();
+    let mut last_tapped_text: Option<String> = None;
+
+    for (i, action) in actions.iter().enumerate() {
+        let action_type = action.get("action_type").and_then(|v| v.as_str()).unwrap_or_default();
+        let description = action.get("description").and_then(|v| v.as_str()).map(|d| d.chars().take(80).collect());
+        let number = |key: &str| action.get(key).and_then(|v| v.as_f64()).map(|n| n.round() as i64);
+
+        let step = match action_type {
+            "tap" => {
+                let element_text = action.get("element_text").and_then(|v| v.as_str())
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty());
+                last_tapped_text = element_text.clone();
+                let (x, y) = (number("x"), number("y"));
+                match (element_text, x, y) {
+                    // Coordinates stay as fallback when the text is not on screen
+                    (Some(text), Some(x), Some(y)) => Some(action_step("tap_element", json!({"text": text, "x": x, "y": y}))),
+                    (Some(text), _, _) => Some(action_step("tap_element", json!({"text": text}))),
+                    (None, Some(x), Some(y)) => Some(action_step("tap", json!({"x": x, "y": y}))),
+                    _ => None,
+                }
+            }
+            "input_text" => {
+                let text = action.get("text").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                let input_name = match text_inputs.get(&text) {
+                    Some(existing) => existing.clone(),
+                    None => {
+                        let input_name = unique_input_name(last_tapped_text.as_deref(), &inputs);
+                        inputs.push(WorkflowInput {
+                            name: input_name.clone(),
+                            label: Some(last_tapped_text.clone().unwrap_or_else(|| format!("Text {}", inputs.len() + 1))),
+                            input_type: "string".to_string(),
+                            default: Some(json!(text)),
+                            options: None,
+                            min: None,
+                            max: None,
+                            placeholder: None,
+                            required: None,
+                        });
+                        text_inputs.insert(text, input_name.clone());
+                        input_name
+                    }
+                };
+                Some(action_step("input_text", json!({"text": format!("{{{{{}}}}}", input_name)})))
+            }
+            "key_press" => number("keycode").map(|keycode| action_step("press_key", json!({"keycode": keycode}))),
+            "swipe" => match (number("start_x"), number("start_y"), number("end_x"), number("end_y")) {
+                (Some(x1), Some(y1), Some(x2), Some(y2)) => Some(action_step("swipe", json!({
+                    "x1": x1, "y1": y1, "x2": x2, "y2": y2,
+                    "duration": number("duration_ms").unwrap_or(300),
+                }))),
+                _ => None,
+            },
+            "start_app" => action.get("package").and_then(|v| v.as_str()).map(|package| {
+                let mut params = json!({"package": package});
+                if let Some(activity) = action.get("activity").and_then(|v| v.as_str()) {
+                    params["activity"] = json!(activity);
+                }
+                action_step("open_app", params)
+            }),
+            "wait" => action.get("duration").and_then(|v| v.as_f64()).map(|secs| WorkflowStep {
+                step_type: "wait".to_string(),
+                duration: Some(((secs * 1000.0).round() as i64).to_string()),
+                ..Default::default()
+            }),
+            _ => None,
+        };
+
+        let Some(mut step) = step else {
+            println!("[MACRO] Skipping unsupported action {}: {}", i + 1, action);
+            continue;
+        };
+        step.id = format!("step-{}", steps.len() + 1);
+        step.name = description;
+        steps.push(step);
+
+        // Let the screen settle where the agent looked again, unless the recording already waits
+        let next_type = actions.get(i + 1).and_then(|a| a.get("action_type")).and_then(|v| v.as_str());
+        if step_ends.contains(&i) && next_type.is_some_and(|t| t != "wait") && action_type != "wait" {
+            steps.push(WorkflowStep {
+                id: format!("step-{}", steps.len() + 1),
+                step_type: "wait".to_string(),
+                name: Some("Agent step pause".to_string()),
+                duration: Some(STEP_SETTLE_MS.to_string()),
+                ..Default::default()
+            });
+        }
+    }
+
+    if steps.is_empty() {
+        return Err("Macro has no actions that can be converted".to_string());
+    }
+
+    let description = macro_json.get("description").and_then(|v| v.as_str())
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty());
+    Ok(WorkflowDefinition {
+        id: format!("macro-{}", crate::workflow_library::slugify(dir_name)),
+        name: name.or(description.clone()).unwrap_or_else(|| dir_name.to_string()),
+        description: Some(format!("Converted from macro {}{}", dir_name,
+            description.map(|d| format!(": {}", d)).unwrap_or_default())),
+        icon: None,
+        color: None,
+        category: Some("macro".to_string()),
+        inputs,
+        steps,
+        outputs: vec![],
+        timeout: None,
+        step_delay: None,
+        created_at: Some(chrono::Utc::now().to_rfc3339()),
+        is_builtin: None,
+    })
+}
+
+fn action_step(action: &str, params: Value) -> WorkflowStep {
+    WorkflowStep {
+        step_type: "action".to_string(),
+        action: Some(action.to_string()),
+        params: serde_json::from_value(params).ok(),
+        ..Default::default()
+    }
+}
+
+/// "Enter game or app name" -> "enter_game_or_app_name", unique among existing inputs
+fn unique_input_name(field_text: Option<&str>, inputs: &[WorkflowInput]) -> String {
+    // A URL bar shows its current URL, which makes a poor input name
+    let base = field_text.filter(|t| !t.contains("://"))
+        .map(|t| crate::workflow_library::slugify(t).replace('-', "_"))
+        .filter(|s| !s.is_empty() && s.len() <= 32)
+        .unwrap_or_else(|| "text".to_string());
+    let taken = |name: &str| inputs.iter().any(|input| input.name == name);
+    if !taken(&base) {
+        return base;
+    }
+    (2..).map(|n| format!("{}_{}", base, n)).find(|name| !taken(name)).unwrap_or(base)
+}
+
+/// Index of the last macro action of each agent step, found by matching the calls in each
+/// CodeActCodeEvent against the recorded actions. Stops at the first step that does not
+/// line up (loops, branches or a step that failed part-way).
+fn agent_step_ends(actions: &[Value], trajectory: Option<&Value>) -> Vec<usize> {
+    let action_types: Vec<&str> = actions.iter()
+        .map(|a| a.get("action_type").and_then(|v| v.as_str()).unwrap_or_default())
+        .collect();
+    let codes = trajectory.and_then(|t| t.as_array()).into_iter().flatten()
+        .filter(|e| e.get("type").and_then(|t| t.as_str()) == Some("CodeActCodeEvent"))
+        .filter_map(|e| e.get("code").and_then(|c| c.as_str()));
+
+    let mut ends = vec![];
+    let mut next = 0;
+    for code in codes {
+        let Some(calls) = agent_calls(code) else { return ends };
+        let start = next;
+        for expected in calls.iter().flat_map(|call| *call) {
+            match (expected.strip_suffix('?'), action_types.get(next)) {
+                (Some(optional), Some(&actual)) if actual == optional => next += 1,
+                (Some(_), _) => {}
+                (None, Some(&actual)) if actual == *expected => next += 1,
+                _ => return ends,
+            }
+        }
+        if next > start {
+            ends.push(next - 1);
+        }
+    }
+    ends
+}
+
+/// Recorded action types of the agent calls in one code block, in call order.
+/// None when the block loops or branches, its actions cannot be counted from the source.
+fn agent_calls(code: &str) -> Option<Vec<&'static [&'static str]>> {
+    let mut calls = vec![];
+    for line in code.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if ["for ", "while ", "if ", "def "].iter().any(|keyword| line.starts_with(keyword)) {
+            return None;
+        }
+        let mut found: Vec<(usize, &'static [&'static str])> = AGENT_CALLS.iter()
+            .flat_map(|(name, recorded)| {
+                line.match_indices(&format!("{}(", name)).map(move |(pos, _)| (pos, *recorded)).collect::<Vec<_>>()
+            })
+            // click( but not double_click( or device.click(
+            .filter(|(pos, _)| line[..*pos].chars().last().is_none_or(|c| !c.is_alphanumeric() && c != '_' && c != '.'))
+            .collect();
+        found.sort_by_key(|(pos, _)| *pos);
+        calls.extend(found.into_iter().map(|(_, recorded)| recorded));
+    }
+    Some(calls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(workflow: &WorkflowDefinition) -> Vec<String> {
+        workflow.steps.iter()
+            .map(|s| s.action.clone().unwrap_or_else(|| format!("wait {}", s.duration.clone().unwrap_or_default())))
+            .collect()
+    }
+
+    fn recording(name: &str) -> WorkflowDefinition {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("trajectories").join(name);
+        convert_macro_dir(&dir, None).unwrap()
+    }
+
+    #[test]
+    fn test_macro_to_workflow() {
+        let macro_json = json!({
+            "description": "open dtdp.bio",
+            "actions": [
+                {"action_type": "start_app", "package": "com.android.browser"},
+                {"action_type": "tap", "x": 267, "y": 75, "element_text": "Search or type URL"},
+                {"action_type": "input_text", "text": "dtdp.bio"},
+                {"action_type": "key_press", "keycode": 66},
+                {"action_type": "tap", "x": 10, "y": 20, "element_text": ""},
+                {"action_type": "input_text", "text": "dtdp.bio"}
+            ]
+        });
+        let workflow = macro_to_workflow(&macro_json, None, "20260110_162107_0c7e75f0", None).unwrap();
+        assert_eq!(summary(&workflow), vec!["open_app", "tap_element", "input_text", "press_key", "tap", "input_text"]);
+        assert_eq!(workflow.name, "open dtdp.bio");
+        assert_eq!(workflow.id, "macro-20260110-162107-0c7e75f0");
+
+        let tap = workflow.steps[1].params.as_ref().unwrap();
+        assert_eq!((tap["text"].clone(), tap["x"].clone()), (json!("Search or type URL"), json!(267)));
+        assert_eq!(workflow.inputs.len(), 1);
+        assert_eq!(workflow.inputs[0].name, "search_or_type_url");
+        assert_eq!(workflow.steps[5].params.as_ref().unwrap()["text"], json!("{{search_or_type_url}}"));
+    }
+
+    #[test]
+    fn test_recorded_agent_steps() {
+        // click(9) | type(..., 9) | click(14) | click(4) | type(..., 3) | system_button("enter")
+        let browser = recording("20260110_162107_0c7e75f0");
+        assert_eq!(summary(&browser), vec![
+            "tap_element", "wait 1500", "tap_element", "input_text", "wait 1500", "tap_element", "wait 1500",
+            "tap_element", "wait 1500", "tap_element", "input_text", "wait 1500", "press_key",
+        ]);
+        assert_eq!(browser.inputs[0].name, "enter_game_or_app_name");
+
+        // Recorded wait() calls already pause after each swipe
+        let kuaishou = recording("20260109_175932_f7d5f366");
+        assert_eq!(summary(&kuaishou), vec![
+            "tap_element", "wait 1500", "swipe", "wait 3000", "swipe", "wait 3000", "swipe", "wait 3000", "swipe", "wait 3000",
+        ]);
+
+        // Six single-click steps, then a loop whose actions cannot be matched to agent steps
+        let looped = recording("20260109_203532_4623f2b8");
+        let settles = looped.steps.iter().filter(|s| s.name.as_deref() == Some("Agent step pause")).count();
+        assert_eq!(settles, 5);
+    }
+}
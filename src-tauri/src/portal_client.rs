@@ -45,6 +45,40 @@ pub struct UIElement {
     pub content_desc: Option<String>,
 }
 
+impl UIElement {
+    /// Parse bounds "left,top,right,bottom" thành (left, top, right, bottom)
+    pub fn bounds_rect(&self) -> Option<(i32, i32, i32, i32)> {
+        let parts: Vec<i32> = self.bounds.as_ref()?
+            .split(',')
+            .filter_map(|s| s.trim().parse().ok())
+            .collect();
+        if parts.len() == 4 {
+            Some((parts[0], parts[1], parts[2], parts[3]))
+        } else {
+            None
+        }
+    }
+    
+    /// Tọa độ tâm của element
+    pub fn center(&self) -> Option<(i32, i32)> {
+        self.bounds_rect().map(|(l, t, r, b)| ((l + r) / 2, (t + b) / 2))
+    }
+}
+
+/// Duyệt toàn bộ cây, trả về các element thỏa điều kiện (theo thứ tự duyệt)
+pub fn find_all_in_tree<'a>(elements: &'a [UIElement], predicate: &dyn Fn(&UIElement) -> bool) -> Vec<&'a UIElement> {
+    let mut found = Vec::new();
+    for elem in elements {
+        if predicate(elem) {
+            found.push(elem);
+        }
+        if let Some(children) = &elem.children {
+            found.extend(find_all_in_tree(children, predicate));
+        }
+    }
+    found
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PhoneState {
     pub current_activity: Option<String>,
@@ -387,17 +421,8 @@ impl PortalClient {
     /// Tap element theo text
     pub async fn tap_element_by_text(&self, text: &str) -> Result<(), String> {
         if let Some(element) = self.find_element_by_text(text).await? {
-            if let Some(bounds) = &element.bounds {
-                // Parse bounds "left,top,right,bottom"
-                let parts: Vec<i32> = bounds.split(',')
-                    .filter_map(|s| s.parse().ok())
-                    .collect();
-                
-                if parts.len() == 4 {
-                    let center_x = (parts[0] + parts[2]) / 2;
-                    let center_y = (parts[1] + parts[3]) / 2;
-                    return self.tap(center_x, center_y).await;
-                }
+            if let Some((center_x, center_y)) = element.center() {
+                return self.tap(center_x, center_y).await;
             }
             if let Some(index) = element.index {
                 return self.tap_by_index(index).await.map(|_| ());
@@ -454,4 +479,22 @@ mod tests {
         let port = find_available_port(18080);
         assert!(port >= 18080);
     }
+    
+    #[test]
+    fn test_find_all_in_tree() {
+        let tree: Vec<UIElement> = serde_json::from_value(serde_json::json!([{
+            "className": "android.widget.FrameLayout",
+            "children": [
+                {"index": 1, "text": "Balance: 120", "bounds": "0,0,100,50"},
+                {"index": 2, "contentDesc": "Balance icon"},
+            ],
+        }])).unwrap();
+        
+        let found = find_all_in_tree(&tree, &|e| {
+            e.text.as_deref().unwrap_or("").contains("Balance")
+                || e.content_desc.as_deref().unwrap_or("").contains("Balance")
+        });
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].center(), Some((50, 25)));
+    }
 }
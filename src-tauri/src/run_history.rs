@@ -0,0 +1,522 @@
+// Run History Module - Persistent history of workflow, task and macro runs
+// Lưu mỗi lần chạy vào ~/.mun-sdk-ai-v2/history để tra cứu "máy X lỗi gì tối qua" và chạy lại với cùng input
+
+use crate::workflow::{WorkflowDefinition, WorkflowResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tauri::command;
+
+/// Keep only the tail of long outputs so history files stay small
+const MAX_LOG_LINES: usize = 500;
+
+/// Retention runs on the first record after startup, then every PRUNE_EVERY records
+const PRUNE_EVERY: usize = 20;
+
+static RECORDS_SINCE_PRUNE: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes writes, deletes and pruning of history files
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+// ============================================
+// Data Models
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunRecord {
+    pub run_id: String,
+    /// "workflow", "task" or "macro"
+    pub kind: String,
+    /// Workflow id or macro path
+    pub target_id: Option<String>,
+    pub name: Option<String>,
+    pub device_id: String,
+    /// "completed", "failed", "timeout", "cancelled", "invalid_inputs"
+    pub status: String,
+    pub success: bool,
+    /// Workflow inputs, or the task/macro parameters needed to run it again
+    #[serde(default)]
+    pub inputs: HashMap<String, Value>,
+    #[serde(default)]
+    pub outputs: HashMap<String, Value>,
+    #[serde(default)]
+    pub logs: Vec<String>,
+    pub duration_ms: i64,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub screenshots: Vec<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    /// Run id of the run this attempt resumed - each resume is recorded under its own id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed_from: Option<String>,
+    /// Definition that ran, so a re-run does not depend on the library copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow: Option<WorkflowDefinition>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+    pub device_id: Option<String>,
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    /// Matches the workflow id (or macro path)
+    pub workflow_id: Option<String>,
+    /// RFC3339 bounds on started_at
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRetention {
+    pub max_runs: usize,
+    pub max_age_days: i64,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_runs: 500,
+            max_age_days: 30,
+        }
+    }
+}
+
+// ============================================
+// Storage
+// ============================================
+
+fn get_history_dir() -> PathBuf {
+    crate::utils::app_data_dir().join("history")
+}
+
+fn runs_dir(dir: &Path) -> PathBuf {
+    dir.join("runs")
+}
+
+fn run_path(dir: &Path, run_id: &str) -> Result<PathBuf, String> {
+    crate::utils::check_file_id("run", run_id)?;
+    Ok(runs_dir(dir).join(format!("{}.json", run_id)))
+}
+
+fn lock_history() -> std::sync::MutexGuard<'static, ()> {
+    HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Run history file IO off the async runtime
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| format!("History task failed: {}", e))
+}
+
+fn load_retention(dir: &Path) -> HistoryRetention {
+    std::fs::read_to_string(dir.join("retention.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Every parseable run; files that no longer parse are removed
+fn load_records(dir: &Path) -> Vec<RunRecord> {
+    let Ok(entries) = std::fs::read_dir(runs_dir(dir)) else { return vec![] };
+    let mut records = vec![];
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else { continue };
+        match serde_json::from_str(&content) {
+            Ok(record) => records.push(record),
+            Err(e) => {
+                println!("[HISTORY] Removing unreadable record {}: {}", path.display(), e);
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+    records
+}
+
+/// Write a record through a temp file so readers never see a partial run
+fn save_record(dir: &Path, record: &RunRecord) -> Result<(), String> {
+    let path = run_path(dir, &record.run_id)?;
+    std::fs::create_dir_all(runs_dir(dir)).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(record).map_err(|e| e.to_string())?;
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&temp, &path).map_err(|e| e.to_string())
+}
+
+/// Save a run (errors are logged, history never fails a run) and apply retention now and then
+pub async fn record_run(record: RunRecord) {
+    let prune = RECORDS_SINCE_PRUNE.fetch_add(1, Ordering::Relaxed).is_multiple_of(PRUNE_EVERY);
+    let saved = blocking(move || {
+        let dir = get_history_dir();
+        let _lock = lock_history();
+        let result = save_record(&dir, &record);
+        if prune {
+            prune_runs(&dir, &load_retention(&dir));
+        }
+        (record, result)
+    }).await;
+    match saved {
+        Ok((record, Ok(()))) => println!("[HISTORY] Recorded {} run {} ({})", record.kind, record.run_id, record.status),
+        Ok((record, Err(e))) => println!("[HISTORY] Cannot record run {}: {}", record.run_id, e),
+        Err(e) => println!("[HISTORY] Cannot record run: {}", e),
+    }
+}
+
+/// Drop runs older than max_age_days, then all but the newest max_runs.
+/// Goes by file mtime (written when the run finished) so no record is parsed.
+fn prune_runs(dir: &Path, retention: &HistoryRetention) -> usize {
+    let Ok(entries) = std::fs::read_dir(runs_dir(dir)) else { return 0 };
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries.flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+            (modified, entry.path())
+        })
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    let max_age = std::time::Duration::from_secs(retention.max_age_days.max(0) as u64 * 24 * 3600);
+    let mut removed = 0;
+    for (index, (modified, path)) in files.iter().enumerate() {
+        let expired = modified.elapsed().map(|age| age > max_age).unwrap_or(false);
+        if expired || index >= retention.max_runs {
+            if let Some(run_id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                delete_run_files(dir, run_id);
+                removed += 1;
+            }
+        }
+    }
+    if removed > 0 {
+        println!("[HISTORY] Pruned {} old runs", removed);
+    }
+    removed
+}
+
+/// Remove a run and its failure screenshot (the only screenshot kept under the history dir)
+fn delete_run_files(dir: &Path, run_id: &str) -> bool {
+    let _ = std::fs::remove_file(dir.join("screenshots").join(format!("{}.png", run_id)));
+    run_path(dir, run_id).is_ok_and(|path| std::fs::remove_file(path).is_ok())
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Screenshot of the device when a run fails, stored next to the history
+pub async fn capture_failure_screenshot(device_id: &str, run_id: &str) -> Vec<String> {
+    let dir = get_history_dir().join("screenshots");
+    if std::fs::create_dir_all(&dir).is_err() {
+        return vec![];
+    }
+    let path = dir.join(format!("{}.png", run_id)).to_string_lossy().to_string();
+    match crate::adb::take_screenshot(device_id.to_string(), path.clone()).await {
+        Ok(result) if result.success => vec![path],
+        _ => vec![],
+    }
+}
+
+/// Last MAX_LOG_LINES lines of a process output
+pub fn tail_lines<S: AsRef<str>>(lines: &[S]) -> Vec<String> {
+    let start = lines.len().saturating_sub(MAX_LOG_LINES);
+    lines[start..].iter().map(|line| line.as_ref().to_string()).collect()
+}
+
+/// Record a finished (or rejected) workflow run
+pub async fn record_workflow_run(
+    workflow: &WorkflowDefinition,
+    inputs: &HashMap<String, Value>,
+    device_id: &str,
+    started_at: &str,
+    result: &WorkflowResult,
+    screenshots: Vec<String>,
+    resumed_from: Option<&str>,
+) {
+    let logs: Vec<String> = result.logs.iter()
+        .map(|log| match &log.step_id {
+            Some(step_id) => format!("{} [{}] {}: {}", log.timestamp, log.level, step_id, log.message),
+            None => format!("{} [{}] {}", log.timestamp, log.level, log.message),
+        })
+        .collect();
+
+    // A resume keeps the run id of its checkpoint, the original attempt's record stays as it was
+    let run_id = match resumed_from {
+        Some(_) => uuid::Uuid::new_v4().to_string(),
+        None => result.run_id.clone(),
+    };
+    record_run(RunRecord {
+        run_id,
+        kind: "workflow".to_string(),
+        target_id: Some(workflow.id.clone()),
+        name: Some(workflow.name.clone()),
+        device_id: device_id.to_string(),
+        status: result.status.clone(),
+        success: result.success,
+        inputs: inputs.clone(),
+        outputs: result.outputs.clone(),
+        logs: tail_lines(&logs),
+        duration_ms: result.duration_ms,
+        error: result.error.clone(),
+        screenshots,
+        started_at: started_at.to_string(),
+        finished_at: Utc::now().to_rfc3339(),
+        resumed_from: resumed_from.map(|id| id.to_string()),
+        workflow: Some(workflow.clone()),
+    }).await;
+}
+
+fn matches_query(record: &RunRecord, query: &HistoryQuery) -> bool {
+    let started = parse_time(&record.started_at);
+    let within = |bound: &Option<String>, after: bool| match (bound.as_deref().and_then(parse_time), started) {
+        (Some(bound), Some(started)) => if after { started >= bound } else { started <= bound },
+        (Some(_), None) => false,
+        (None, _) => true,
+    };
+
+    query.device_id.as_ref().is_none_or(|d| &record.device_id == d)
+        && query.status.as_ref().is_none_or(|s| &record.status == s)
+        && query.kind.as_ref().is_none_or(|k| &record.kind == k)
+        && query.workflow_id.as_ref().is_none_or(|w| record.target_id.as_ref() == Some(w))
+        && within(&query.since, true)
+        && within(&query.until, false)
+}
+
+// ============================================
+// Commands
+// ============================================
+
+/// Query run history, newest first
+#[command]
+pub async fn list_run_history(query: Option<HistoryQuery>) -> Result<Vec<RunRecord>, String> {
+    let query = query.unwrap_or_default();
+    blocking(move || {
+        let mut records: Vec<RunRecord> = load_records(&get_history_dir()).into_iter()
+            .filter(|record| matches_query(record, &query))
+            .collect();
+        records.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        if let Some(limit) = query.limit {
+            records.truncate(limit);
+        }
+        records
+    }).await
+}
+
+fn read_record(dir: &Path, run_id: &str) -> Result<RunRecord, String> {
+    let path = run_path(dir, run_id)?;
+    let content = std::fs::read_to_string(&path).map_err(|_| format!("Run {} not found in history", run_id))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid history record {}: {}", path.display(), e))
+}
+
+#[command]
+pub async fn get_run_record(run_id: String) -> Result<RunRecord, String> {
+    blocking(move || read_record(&get_history_dir(), &run_id)).await?
+}
+
+#[command]
+pub async fn delete_run_record(run_id: String) -> Result<bool, String> {
+    blocking(move || {
+        let dir = get_history_dir();
+        run_path(&dir, &run_id)?;
+        let _lock = lock_history();
+        if delete_run_files(&dir, &run_id) {
+            Ok(true)
+        } else {
+            Err(format!("Run {} not found in history", run_id))
+        }
+    }).await?
+}
+
+/// Delete every run, returns how many were removed
+#[command]
+pub async fn clear_run_history() -> Result<usize, String> {
+    blocking(|| {
+        let dir = get_history_dir();
+        let _lock = lock_history();
+        prune_runs(&dir, &HistoryRetention { max_runs: 0, ..load_retention(&dir) })
+    }).await
+}
+
+#[command]
+pub async fn get_history_retention() -> Result<HistoryRetention, String> {
+    blocking(|| load_retention(&get_history_dir())).await
+}
+
+/// Save retention limits and prune right away
+#[command]
+pub async fn set_history_retention(retention: HistoryRetention) -> Result<HistoryRetention, String> {
+    blocking(move || {
+        let dir = get_history_dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create history directory: {}", e))?;
+        let json = serde_json::to_string_pretty(&retention).map_err(|e| e.to_string())?;
+        let _lock = lock_history();
+        std::fs::write(dir.join("retention.json"), json)
+            .map_err(|e| format!("Cannot save history retention: {}", e))?;
+        prune_runs(&dir, &retention);
+        Ok(retention)
+    }).await?
+}
+
+/// Definition recorded with the run, or the library copy for older records
+fn rerun_workflow(record: &RunRecord) -> Result<WorkflowDefinition, String> {
+    match record.workflow.clone() {
+        Some(workflow) => Ok(workflow),
+        None => crate::workflow::load_workflow_definition(record.target_id.as_deref().unwrap_or_default()),
+    }
+}
+
+/// Profile whose API key re-runs a task: the active one if it uses the provider, else the first that does
+fn rerun_profile<'a>(config: &'a crate::config::AppConfig, provider: &str) -> Result<&'a crate::config::Profile, String> {
+    config.profiles.iter()
+        .filter(|p| p.provider.name == provider)
+        .find(|p| config.active_profile_id.as_ref() == Some(&p.id))
+        .or_else(|| config.profiles.iter().find(|p| p.provider.name == provider))
+        .ok_or_else(|| format!("No profile with provider '{}' to re-run this task", provider))
+}
+
+/// Run a recorded workflow, task or macro again on the same device with the same inputs
+#[command]
+pub async fn rerun_from_history(
+    window: tauri::Window,
+    run_id: String,
+    device_id: Option<String>,
+) -> Result<Value, String> {
+    let record = get_run_record(run_id).await?;
+    let device_id = device_id.unwrap_or(record.device_id.clone());
+    let input = |name: &str| record.inputs.get(name).cloned().unwrap_or(Value::Null);
+    println!("[HISTORY] Re-running {} run {} on {}", record.kind, record.run_id, device_id);
+
+    match record.kind.as_str() {
+        "workflow" => {
+            let workflow = rerun_workflow(&record)?;
+            let result = crate::workflow::run_workflow(window, workflow, record.inputs.clone(), device_id, None, None).await?;
+            serde_json::to_value(result).map_err(|e| e.to_string())
+        }
+        "task" => {
+            // API keys are never written to history, use the matching profile's key
+            let provider = input("provider").as_str().unwrap_or_default().to_string();
+            let config = crate::config::load_config_sync()?;
+            let profile = rerun_profile(&config, &provider)?;
+
+            let result = crate::task::run_task(
+                window,
+                device_id,
+                provider,
+                profile.provider.api_key.clone(),
+                input("model").as_str().unwrap_or_default().to_string(),
+                input("prompt").as_str().unwrap_or_default().to_string(),
+                input("baseUrl").as_str().map(|s| s.to_string()),
+                input("vision").as_bool(),
+                input("reasoning").as_bool(),
+                None,
+            ).await?;
+            serde_json::to_value(result).map_err(|e| e.to_string())
+        }
+        "macro" => {
+            let result = crate::macro_cmd::replay_macro(
+                window,
+                device_id,
+                record.target_id.clone().ok_or("Macro run has no macro path")?,
+                input("delay").as_f64(),
+                input("startFrom").as_i64().map(|n| n as i32),
+                input("maxSteps").as_i64().map(|n| n as i32),
+                input("dryRun").as_bool(),
+            ).await?;
+            serde_json::to_value(result).map_err(|e| e.to_string())
+        }
+        other => Err(format!("Cannot re-run '{}' runs", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(run_id: &str, device_id: &str, status: &str, started_at: &str) -> RunRecord {
+        serde_json::from_value(json!({
+            "runId": run_id, "kind": "workflow", "targetId": "tiktok", "name": "TikTok",
+            "deviceId": device_id, "status": status, "success": status == "completed",
+            "durationMs": 10, "error": null, "startedAt": started_at, "finishedAt": started_at
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_matches_query() {
+        let failed = record("a", "emulator-5554", "failed", "2026-10-16T22:30:00Z");
+        let query = HistoryQuery {
+            device_id: Some("emulator-5554".to_string()),
+            status: Some("failed".to_string()),
+            since: Some("2026-10-16T18:00:00Z".to_string()),
+            until: Some("2026-10-17T06:00:00+00:00".to_string()),
+            ..Default::default()
+        };
+        assert!(matches_query(&failed, &query));
+        assert!(!matches_query(&record("b", "emulator-5556", "failed", "2026-10-16T22:30:00Z"), &query));
+        assert!(!matches_query(&record("c", "emulator-5554", "failed", "2026-10-15T22:30:00Z"), &query));
+        assert!(matches_query(&failed, &HistoryQuery { workflow_id: Some("tiktok".to_string()), ..Default::default() }));
+        assert!(run_path(Path::new("history"), "../config").is_err());
+        assert_eq!(tail_lines(&vec!["x"; MAX_LOG_LINES + 5]).len(), MAX_LOG_LINES);
+    }
+
+    #[test]
+    fn test_prune_runs() {
+        let dir = std::env::temp_dir().join(format!("mun-history-{}", std::process::id()));
+        let now = std::time::SystemTime::now();
+        let hours = |h: u64| now - std::time::Duration::from_secs(h * 3600);
+        for (run_id, modified) in [("old", hours(40 * 24)), ("b", hours(3)), ("c", hours(2)), ("d", hours(1))] {
+            save_record(&dir, &record(run_id, "emulator-5554", "failed", "2026-10-16T22:30:00Z")).unwrap();
+            let file = std::fs::File::options().write(true).open(run_path(&dir, run_id).unwrap()).unwrap();
+            file.set_modified(modified).unwrap();
+        }
+        std::fs::write(runs_dir(&dir).join("broken.json"), "{\"runId\":").unwrap();
+        std::fs::create_dir_all(dir.join("screenshots")).unwrap();
+        std::fs::write(dir.join("screenshots").join("old.png"), "png").unwrap();
+
+        // Newest first: broken, d, c - b is over max_runs and old is also expired
+        assert_eq!(prune_runs(&dir, &HistoryRetention { max_runs: 3, max_age_days: 30 }), 2);
+        assert!(!dir.join("screenshots").join("old.png").exists());
+
+        // Listing drops the record that does not parse
+        let mut ids: Vec<String> = load_records(&dir).into_iter().map(|r| r.run_id).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["c", "d"]);
+        assert!(!runs_dir(&dir).join("broken.json").exists());
+
+        assert_eq!(prune_runs(&dir, &HistoryRetention { max_runs: 0, max_age_days: 30 }), 2);
+        assert!(load_records(&dir).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rerun_targets() {
+        let mut run = record("a", "emulator-5554", "failed", "2026-10-16T22:30:00Z");
+        run.target_id = Some("missing-workflow".to_string());
+        assert!(rerun_workflow(&run).is_err());
+        run.workflow = Some(serde_json::from_value(json!({"id": "tiktok", "name": "TikTok", "inputs": [], "steps": [], "outputs": []})).unwrap());
+        assert_eq!(rerun_workflow(&run).unwrap().id, "tiktok");
+
+        let profile = |id: &str, provider: &str| serde_json::from_value(json!({
+            "id": id, "name": id, "task": "", "max_steps": 10, "device_ids": [],
+            "created_at": "", "updated_at": "",
+            "provider": {"name": provider, "api_key": format!("key-{}", id), "model": "m", "base_url": null},
+        })).unwrap();
+        let config = crate::config::AppConfig {
+            version: "2.0.0".to_string(),
+            active_profile_id: Some("p3".to_string()),
+            profiles: vec![profile("p1", "openai"), profile("p2", "gemini"), profile("p3", "openai")],
+            settings: Default::default(),
+        };
+        assert_eq!(rerun_profile(&config, "openai").unwrap().id, "p3");
+        assert_eq!(rerun_profile(&config, "gemini").unwrap().id, "p2");
+        assert!(rerun_profile(&config, "ollama").is_err());
+    }
+}
@@ -0,0 +1,671 @@
+// Selector Module - XPath-like queries over the Portal accessibility tree (UIElement)
+// Dùng chung cho tap_element, extract và wait_for thay vì tọa độ cứng
+//
+// Cú pháp:
+//   Balance                                      -> text hoặc content-desc chứa "Balance"
+//   //Button[@text='OK']                         -> class Button (tên đầy đủ hoặc tên ngắn), text đúng bằng "OK"
+//   //*[contains(@content-desc, 'Like')]          -> content-desc chứa "Like"
+//   //*[matches(@text, '^\d{6}$')]               -> text khớp regex
+//   //ImageView[@clickable='true'][2]            -> ImageView clickable thứ 2 (vị trí tính từ 1)
+//   //*[@text='Price']/following-sibling::*[1]   -> element liền sau "Price"
+//   //LinearLayout[.//*[@text='Price']]          -> LinearLayout có con cháu text "Price"
+//   //*[@text='Item']/ancestor::*[@clickable]    -> các tổ tiên clickable của "Item"
+//   //Button | //ImageButton                     -> hợp của hai truy vấn
+//   (//Button)[1]                               -> Button đầu tiên trong toàn bộ cây (vị trí trên cả tập kết quả)
+
+use crate::portal_client::UIElement;
+
+/// Select every element matching `selector`, in document (tree) order
+pub fn select<'a>(tree: &'a [UIElement], selector: &str) -> Result<Vec<&'a UIElement>, String> {
+    let selector = selector.trim();
+    if selector.is_empty() {
+        return Err("Empty selector".to_string());
+    }
+
+    // Plain text: same behaviour as the original text/content-desc search
+    if !selector.starts_with('/') && !selector.starts_with('(') {
+        return Ok(crate::portal_client::find_all_in_tree(tree, &|elem| {
+            elem.text.as_deref().map(|t| t.contains(selector)).unwrap_or(false)
+                || elem.content_desc.as_deref().map(|d| d.contains(selector)).unwrap_or(false)
+        }));
+    }
+
+    let queries = parse(selector).map_err(|e| format!("Invalid selector '{}': {}", selector, e))?;
+    let nodes = build_nodes(tree);
+
+    Ok(eval_union(&nodes, &queries).into_iter().filter_map(|id| nodes[id].elem).collect())
+}
+
+/// Select the first matching element, if any
+pub fn select_first<'a>(tree: &'a [UIElement], selector: &str) -> Result<Option<&'a UIElement>, String> {
+    Ok(select(tree, selector)?.into_iter().next())
+}
+
+// ============================================
+// Syntax tree
+// ============================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Parent,
+    Ancestor,
+    FollowingSibling,
+    PrecedingSibling,
+    SelfNode,
+}
+
+#[derive(Debug, Clone)]
+enum NodeTest {
+    /// node() - any node including the virtual root
+    Node,
+    /// * - any element
+    AnyElement,
+    /// Class name, matched in full or by its simple name ("Button")
+    Class(String),
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone)]
+struct Path {
+    absolute: bool,
+    steps: Vec<Step>,
+}
+
+/// One side of a `|` union
+#[derive(Debug, Clone)]
+enum Query {
+    Path(Path),
+    /// `(//Button | //ImageButton)[1]` - predicates apply to the whole node-set, in document order
+    Group(Vec<Query>, Vec<Predicate>),
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    /// [n] - 1-based position among the step's candidates
+    Position(usize),
+    Cond(Cond),
+}
+
+#[derive(Debug, Clone)]
+enum Cond {
+    Or(Box<Cond>, Box<Cond>),
+    And(Box<Cond>, Box<Cond>),
+    Not(Box<Cond>),
+    HasAttr(String),
+    Compare { attr: String, negate: bool, value: String },
+    Func { name: String, attr: String, value: String },
+    /// matches() keeps its regex compiled once per query
+    Matches { attr: String, regex: regex::Regex },
+    Exists(Path),
+}
+
+// ============================================
+// Tokenizer
+// ============================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    DoubleSlash,
+    Slash,
+    DotDot,
+    Dot,
+    Star,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Comma,
+    Pipe,
+    Eq,
+    Ne,
+    Axis(String),
+    Attr(String),
+    Name(String),
+    Str(String),
+    Number(usize),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '$';
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '/' if chars.get(i + 1) == Some(&'/') => { tokens.push(Tok::DoubleSlash); i += 2; }
+            '/' => { tokens.push(Tok::Slash); i += 1; }
+            '.' if chars.get(i + 1) == Some(&'.') => { tokens.push(Tok::DotDot); i += 2; }
+            '.' => { tokens.push(Tok::Dot); i += 1; }
+            '*' => { tokens.push(Tok::Star); i += 1; }
+            '[' => { tokens.push(Tok::LBracket); i += 1; }
+            ']' => { tokens.push(Tok::RBracket); i += 1; }
+            '(' => { tokens.push(Tok::LParen); i += 1; }
+            ')' => { tokens.push(Tok::RParen); i += 1; }
+            ',' => { tokens.push(Tok::Comma); i += 1; }
+            '|' => { tokens.push(Tok::Pipe); i += 1; }
+            '=' => { tokens.push(Tok::Eq); i += 1; }
+            '!' if chars.get(i + 1) == Some(&'=') => { tokens.push(Tok::Ne); i += 2; }
+            '"' | '\'' => {
+                let end = chars[i + 1..].iter().position(|&ch| ch == c)
+                    .ok_or_else(|| format!("unterminated string at column {}", i + 1))?;
+                tokens.push(Tok::Str(chars[i + 1..i + 1 + end].iter().collect()));
+                i += end + 2;
+            }
+            '@' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && is_name_char(chars[i]) {
+                    i += 1;
+                }
+                if i == start {
+                    return Err(format!("expected attribute name at column {}", start));
+                }
+                tokens.push(Tok::Attr(chars[start..i].iter().collect()));
+            }
+            _ if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push(Tok::Number(text.parse().map_err(|_| format!("invalid number '{}'", text))?));
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && is_name_char(chars[i]) {
+                    i += 1;
+                }
+                let name: String = chars[start..i].iter().collect();
+                if chars.get(i) == Some(&':') && chars.get(i + 1) == Some(&':') {
+                    tokens.push(Tok::Axis(name));
+                    i += 2;
+                } else {
+                    tokens.push(Tok::Name(name));
+                }
+            }
+            _ => return Err(format!("unexpected character '{}' at column {}", c, i + 1)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+// ============================================
+// Parser
+// ============================================
+
+struct Parser {
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Tok> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn eat(&mut self, tok: &Tok) -> bool {
+        if self.peek() == Some(tok) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, tok: Tok, what: &str) -> Result<(), String> {
+        if self.eat(&tok) {
+            Ok(())
+        } else {
+            Err(format!("expected {}", what))
+        }
+    }
+
+    fn parse_union(&mut self) -> Result<Vec<Query>, String> {
+        let mut queries = vec![self.parse_query()?];
+        while self.eat(&Tok::Pipe) {
+            queries.push(self.parse_query()?);
+        }
+        Ok(queries)
+    }
+
+    fn parse_query(&mut self) -> Result<Query, String> {
+        if !self.eat(&Tok::LParen) {
+            return Ok(Query::Path(self.parse_path()?));
+        }
+        let inner = self.parse_union()?;
+        self.expect(Tok::RParen, "')'")?;
+        let predicates = self.parse_predicates()?;
+        if matches!(self.peek(), Some(Tok::Slash) | Some(Tok::DoubleSlash)) {
+            return Err("steps after a grouped selector are not supported".to_string());
+        }
+        Ok(Query::Group(inner, predicates))
+    }
+
+    fn parse_path(&mut self) -> Result<Path, String> {
+        let mut steps = Vec::new();
+        let absolute = matches!(self.peek(), Some(Tok::Slash) | Some(Tok::DoubleSlash));
+
+        // Relative paths (inside predicates) start directly with a step
+        if !absolute {
+            steps.push(self.parse_step()?);
+        }
+
+        loop {
+            match self.peek() {
+                Some(Tok::Slash) => {
+                    self.pos += 1;
+                }
+                Some(Tok::DoubleSlash) => {
+                    self.pos += 1;
+                    steps.push(Step {
+                        axis: Axis::DescendantOrSelf,
+                        test: NodeTest::Node,
+                        predicates: vec![],
+                    });
+                }
+                _ => break,
+            }
+            steps.push(self.parse_step()?);
+        }
+
+        Ok(Path { absolute, steps })
+    }
+
+    fn parse_step(&mut self) -> Result<Step, String> {
+        if self.eat(&Tok::DotDot) {
+            return Ok(Step { axis: Axis::Parent, test: NodeTest::Node, predicates: vec![] });
+        }
+        if self.eat(&Tok::Dot) {
+            return Ok(Step { axis: Axis::SelfNode, test: NodeTest::Node, predicates: vec![] });
+        }
+
+        let axis = match self.peek() {
+            Some(Tok::Axis(name)) => {
+                let axis = match name.as_str() {
+                    "child" => Axis::Child,
+                    "descendant" => Axis::Descendant,
+                    "descendant-or-self" => Axis::DescendantOrSelf,
+                    "parent" => Axis::Parent,
+                    "ancestor" => Axis::Ancestor,
+                    "following-sibling" => Axis::FollowingSibling,
+                    "preceding-sibling" => Axis::PrecedingSibling,
+                    "self" => Axis::SelfNode,
+                    other => return Err(format!("unknown axis '{}'", other)),
+                };
+                self.pos += 1;
+                axis
+            }
+            _ => Axis::Child,
+        };
+
+        let test = match self.next() {
+            Some(Tok::Star) => NodeTest::AnyElement,
+            Some(Tok::Name(name)) if name == "node" && self.peek() == Some(&Tok::LParen) => {
+                self.pos += 1;
+                self.expect(Tok::RParen, "')' after node(")?;
+                NodeTest::Node
+            }
+            Some(Tok::Name(name)) => NodeTest::Class(name),
+            _ => return Err("expected element name or '*'".to_string()),
+        };
+
+        let predicates = self.parse_predicates()?;
+        Ok(Step { axis, test, predicates })
+    }
+
+    fn parse_predicates(&mut self) -> Result<Vec<Predicate>, String> {
+        let mut predicates = Vec::new();
+        while self.eat(&Tok::LBracket) {
+            let predicate = match self.peek() {
+                Some(Tok::Number(n)) if self.peek_at(1) == Some(&Tok::RBracket) => {
+                    let n = *n;
+                    self.pos += 1;
+                    if n == 0 {
+                        return Err("positions start at 1".to_string());
+                    }
+                    Predicate::Position(n)
+                }
+                _ => Predicate::Cond(self.parse_or()?),
+            };
+            self.expect(Tok::RBracket, "']'")?;
+            predicates.push(predicate);
+        }
+        Ok(predicates)
+    }
+
+    fn parse_or(&mut self) -> Result<Cond, String> {
+        let mut left = self.parse_and()?;
+        while self.eat(&Tok::Name("or".to_string())) {
+            let right = self.parse_and()?;
+            left = Cond::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Cond, String> {
+        let mut left = self.parse_cond()?;
+        while self.eat(&Tok::Name("and".to_string())) {
+            let right = self.parse_cond()?;
+            left = Cond::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_value(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Tok::Str(s)) => Ok(s),
+            Some(Tok::Number(n)) => Ok(n.to_string()),
+            Some(Tok::Name(n)) if n == "true" || n == "false" => Ok(n),
+            _ => Err("expected a quoted value".to_string()),
+        }
+    }
+
+    /// Attribute operand: `@text` or `text()`
+    fn parse_attr(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Tok::Attr(a)) => Ok(a),
+            Some(Tok::Name(n)) if n == "text" && self.peek() == Some(&Tok::LParen) => {
+                self.pos += 1;
+                self.expect(Tok::RParen, "')' after text(")?;
+                Ok("text".to_string())
+            }
+            _ => Err("expected an attribute like @text".to_string()),
+        }
+    }
+
+    fn parse_cond(&mut self) -> Result<Cond, String> {
+        if self.eat(&Tok::LParen) {
+            let cond = self.parse_or()?;
+            self.expect(Tok::RParen, "')'")?;
+            return Ok(cond);
+        }
+
+        let is_call = self.peek_at(1) == Some(&Tok::LParen);
+        match self.peek().cloned() {
+            Some(Tok::Name(name)) if is_call && name == "not" => {
+                self.pos += 2;
+                let inner = self.parse_or()?;
+                self.expect(Tok::RParen, "')' after not(")?;
+                Ok(Cond::Not(Box::new(inner)))
+            }
+            Some(Tok::Name(name)) if is_call && name != "text" && name != "node" => {
+                if !["contains", "starts-with", "ends-with", "matches"].contains(&name.as_str()) {
+                    return Err(format!("unknown function '{}'", name));
+                }
+                self.pos += 2;
+                let attr = self.parse_attr()?;
+                self.expect(Tok::Comma, "',' between arguments")?;
+                let value = self.parse_value()?;
+                self.expect(Tok::RParen, "')'")?;
+                if name == "matches" {
+                    let regex = regex::Regex::new(&value).map_err(|e| format!("invalid regex '{}': {}", value, e))?;
+                    return Ok(Cond::Matches { attr, regex });
+                }
+                Ok(Cond::Func { name, attr, value })
+            }
+            Some(Tok::Attr(_)) => self.parse_comparison(),
+            Some(Tok::Name(name)) if is_call && name == "text" => self.parse_comparison(),
+            _ => Ok(Cond::Exists(self.parse_path()?)),
+        }
+    }
+
+    /// `@attr`, `@attr = 'v'` or `@attr != 'v'`
+    fn parse_comparison(&mut self) -> Result<Cond, String> {
+        let attr = self.parse_attr()?;
+        let negate = match self.peek() {
+            Some(Tok::Eq) => false,
+            Some(Tok::Ne) => true,
+            _ => return Ok(Cond::HasAttr(attr)),
+        };
+        self.pos += 1;
+        let value = self.parse_value()?;
+        Ok(Cond::Compare { attr, negate, value })
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<Query>, String> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    let queries = parser.parse_union()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("unexpected token {:?}", parser.tokens[parser.pos]));
+    }
+    Ok(queries)
+}
+
+// ============================================
+// Evaluation
+// ============================================
+
+struct Node<'a> {
+    elem: Option<&'a UIElement>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// Flatten the tree in pre-order; node 0 is a virtual root above the top-level elements
+fn build_nodes(tree: &[UIElement]) -> Vec<Node<'_>> {
+    fn add<'a>(nodes: &mut Vec<Node<'a>>, elem: &'a UIElement, parent: usize) -> usize {
+        let id = nodes.len();
+        nodes.push(Node { elem: Some(elem), parent: Some(parent), children: vec![] });
+        let children: Vec<usize> = elem.children.iter().flatten()
+            .map(|child| add(nodes, child, id))
+            .collect();
+        nodes[id].children = children;
+        id
+    }
+
+    let mut nodes = vec![Node { elem: None, parent: None, children: vec![] }];
+    let roots: Vec<usize> = tree.iter().map(|elem| add(&mut nodes, elem, 0)).collect();
+    nodes[0].children = roots;
+    nodes
+}
+
+fn descendants(nodes: &[Node], id: usize, out: &mut Vec<usize>) {
+    for &child in &nodes[id].children {
+        out.push(child);
+        descendants(nodes, child, out);
+    }
+}
+
+/// Nodes along an axis, in proximity order (reverse axes list the nearest node first)
+fn axis_nodes(nodes: &[Node], id: usize, axis: Axis) -> Vec<usize> {
+    let siblings = || nodes[id].parent.map(|p| nodes[p].children.clone()).unwrap_or_default();
+    match axis {
+        Axis::Child => nodes[id].children.clone(),
+        Axis::Descendant => {
+            let mut out = Vec::new();
+            descendants(nodes, id, &mut out);
+            out
+        }
+        Axis::DescendantOrSelf => {
+            let mut out = vec![id];
+            descendants(nodes, id, &mut out);
+            out
+        }
+        Axis::Parent => nodes[id].parent.into_iter().collect(),
+        Axis::Ancestor => {
+            let mut out = Vec::new();
+            let mut current = nodes[id].parent;
+            while let Some(p) = current {
+                out.push(p);
+                current = nodes[p].parent;
+            }
+            out
+        }
+        Axis::FollowingSibling => siblings().into_iter().filter(|&s| s > id).collect(),
+        Axis::PrecedingSibling => siblings().into_iter().filter(|&s| s < id).rev().collect(),
+        Axis::SelfNode => vec![id],
+    }
+}
+
+fn matches_test(node: &Node, test: &NodeTest) -> bool {
+    match (test, node.elem) {
+        (NodeTest::Node, _) => true,
+        (NodeTest::AnyElement, elem) => elem.is_some(),
+        (NodeTest::Class(name), Some(elem)) => elem.class_name.as_deref()
+            .map(|class| class == name || class.rsplit('.').next() == Some(name.as_str()))
+            .unwrap_or(false),
+        (NodeTest::Class(_), None) => false,
+    }
+}
+
+fn attribute(elem: &UIElement, name: &str) -> Option<String> {
+    match name {
+        "text" => elem.text.clone(),
+        "content-desc" | "contentDesc" | "desc" => elem.content_desc.clone(),
+        "class" | "className" => elem.class_name.clone(),
+        "clickable" => Some(elem.clickable.unwrap_or(false).to_string()),
+        "index" => elem.index.map(|i| i.to_string()),
+        "bounds" => elem.bounds.clone(),
+        _ => None,
+    }
+}
+
+fn eval_cond(nodes: &[Node], id: usize, cond: &Cond) -> bool {
+    let attr = |name: &str| nodes[id].elem.and_then(|elem| attribute(elem, name));
+    match cond {
+        Cond::Or(a, b) => eval_cond(nodes, id, a) || eval_cond(nodes, id, b),
+        Cond::And(a, b) => eval_cond(nodes, id, a) && eval_cond(nodes, id, b),
+        Cond::Not(inner) => !eval_cond(nodes, id, inner),
+        Cond::HasAttr(name) => match attr(name) {
+            Some(v) => !v.is_empty() && v != "false",
+            None => false,
+        },
+        Cond::Compare { attr: name, negate, value } => match attr(name) {
+            Some(v) => (v == *value) != *negate,
+            None => false,
+        },
+        Cond::Func { name, attr: attr_name, value } => {
+            let Some(v) = attr(attr_name) else { return false };
+            match name.as_str() {
+                "contains" => v.contains(value.as_str()),
+                "starts-with" => v.starts_with(value.as_str()),
+                _ => v.ends_with(value.as_str()),
+            }
+        }
+        Cond::Matches { attr: attr_name, regex } => attr(attr_name).is_some_and(|v| regex.is_match(&v)),
+        Cond::Exists(path) => !eval_path(nodes, path, &[id]).is_empty(),
+    }
+}
+
+fn eval_path(nodes: &[Node], path: &Path, start: &[usize]) -> Vec<usize> {
+    let mut current: Vec<usize> = if path.absolute { vec![0] } else { start.to_vec() };
+
+    for step in &path.steps {
+        let mut next = Vec::new();
+        for &id in &current {
+            let candidates: Vec<usize> = axis_nodes(nodes, id, step.axis).into_iter()
+                .filter(|&n| matches_test(&nodes[n], &step.test))
+                .collect();
+            next.extend(apply_predicates(nodes, candidates, &step.predicates));
+        }
+        next.sort_unstable();
+        next.dedup();
+        current = next;
+    }
+
+    current
+}
+
+fn apply_predicates(nodes: &[Node], mut candidates: Vec<usize>, predicates: &[Predicate]) -> Vec<usize> {
+    for predicate in predicates {
+        candidates = match predicate {
+            Predicate::Position(n) => candidates.get(n - 1).copied().into_iter().collect(),
+            Predicate::Cond(cond) => candidates.into_iter()
+                .filter(|&c| eval_cond(nodes, c, cond))
+                .collect(),
+        };
+    }
+    candidates
+}
+
+/// Union of the queries in document order
+fn eval_union(nodes: &[Node], queries: &[Query]) -> Vec<usize> {
+    let mut result: Vec<usize> = Vec::new();
+    for query in queries {
+        match query {
+            Query::Path(path) => result.extend(eval_path(nodes, path, &[0])),
+            Query::Group(inner, predicates) => result.extend(apply_predicates(nodes, eval_union(nodes, inner), predicates)),
+        }
+    }
+    result.sort_unstable();
+    result.dedup();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_tree() -> Vec<UIElement> {
+        serde_json::from_value(serde_json::json!([{
+            "className": "android.widget.FrameLayout",
+            "children": [
+                {
+                    "className": "android.widget.LinearLayout",
+                    "clickable": true,
+                    "children": [
+                        {"className": "android.widget.TextView", "text": "Price", "index": 1},
+                        {"className": "android.widget.TextView", "text": "120.000đ", "index": 2},
+                    ],
+                },
+                {"className": "android.widget.Button", "text": "OK", "clickable": true, "index": 3},
+                {"className": "android.widget.ImageView", "contentDesc": "Like video", "clickable": true, "index": 4},
+            ],
+        }])).unwrap()
+    }
+
+    fn texts(found: Vec<&UIElement>) -> Vec<String> {
+        found.iter().map(|e| e.text.clone().or(e.content_desc.clone()).unwrap_or_default()).collect()
+    }
+
+    #[test]
+    fn test_attribute_selectors() {
+        let tree = sample_tree();
+        assert_eq!(texts(select(&tree, "//Button[@text='OK']").unwrap()), vec!["OK"]);
+        assert_eq!(texts(select(&tree, "//*[contains(@content-desc, 'Like')]").unwrap()), vec!["Like video"]);
+        assert_eq!(texts(select(&tree, "//TextView[matches(@text, '^\\d+')]").unwrap()), vec!["120.000đ"]);
+        assert_eq!(select(&tree, "//*[@clickable='true']").unwrap().len(), 3);
+        assert_eq!(texts(select(&tree, "//TextView[2]").unwrap()), vec!["120.000đ"]);
+        assert_eq!(texts(select(&tree, "Like").unwrap()), vec!["Like video"]);
+    }
+
+    #[test]
+    fn test_relation_selectors() {
+        let tree = sample_tree();
+        assert_eq!(texts(select(&tree, "//*[@text='Price']/following-sibling::*[1]").unwrap()), vec!["120.000đ"]);
+        let row = select(&tree, "//LinearLayout[.//*[@text='Price']]").unwrap();
+        assert_eq!(row.len(), 1);
+        let ancestors = select(&tree, "//*[@text='Price']/ancestor::*[@clickable]").unwrap();
+        assert_eq!(ancestors[0].class_name.as_deref(), Some("android.widget.LinearLayout"));
+        assert_eq!(select(&tree, "//Button | //ImageView").unwrap().len(), 2);
+        assert_eq!(texts(select(&tree, "(//*[@clickable='true'])[2]").unwrap()), vec!["OK"]);
+        assert_eq!(texts(select(&tree, "(//TextView | //ImageView)[3]").unwrap()), vec!["Like video"]);
+        assert!(select(&tree, "(//Button)[1]/..").unwrap_err().contains("grouped selector"));
+        assert!(select(&tree, "//Button[@text=").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+// Skill Module - Backend store for the JSON skills produced by task::generate_skill
+// Lưu skill trong ~/.mun-sdk-ai-v2/skills để bước `skill` của workflow chạy được cả khi không có frontend (scheduler, headless)
+
+use crate::workflow::WorkflowInput;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::command;
+use uuid::Uuid;
+
+// ============================================
+// Data Models
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Skill {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+    pub category: Option<String>,
+    /// Seconds before the agent run is stopped
+    pub timeout: Option<u64>,
+    /// "skip" keeps the workflow going when the skill fails, anything else fails the step
+    pub on_error: Option<String>,
+    #[serde(default)]
+    pub variables: Vec<WorkflowInput>,
+    /// Agent instructions, may reference variables with {{name}}
+    pub prompt: Option<String>,
+    /// Guidance-only skills (built-in UI skills) are run with their guidance as the prompt
+    pub guidance: Option<String>,
+    pub max_steps: Option<i32>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// UI fields (priority, isEnabled, examples...) kept as-is
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+// ============================================
+// Storage
+// ============================================
+
+fn get_skills_dir() -> PathBuf {
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home.join(".mun-sdk-ai-v2").join("skills")
+}
+
+fn skill_path(skill_id: &str) -> Result<PathBuf, String> {
+    crate::utils::check_file_id("skill", skill_id)?;
+    Ok(get_skills_dir().join(format!("{}.json", skill_id)))
+}
+
+/// Load a skill by id
+pub fn load_skill(skill_id: &str) -> Result<Skill, String> {
+    let path = skill_path(skill_id)?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|_| format!("Skill '{}' not found", skill_id))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid skill {}: {}", path.display(), e))
+}
+
+// ============================================
+// Commands
+// ============================================
+
+/// List saved skills, sorted by name
+#[command]
+pub async fn list_skills() -> Result<Vec<Skill>, String> {
+    let Ok(entries) = std::fs::read_dir(get_skills_dir()) else { return Ok(vec![]) };
+    let mut skills: Vec<Skill> = entries.flatten()
+        .filter(|entry| entry.path().extension().and_then(|e| e.to_str()) == Some("json"))
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    skills.sort_by_key(|s| s.name.to_lowercase());
+    Ok(skills)
+}
+
+#[command]
+pub async fn get_skill(skill_id: String) -> Result<Skill, String> {
+    load_skill(&skill_id)
+}
+
+/// Create (no id) or update a skill; accepts the raw JSON returned by generate_skill
+#[command]
+pub async fn save_skill(skill: Skill) -> Result<Skill, String> {
+    let mut skill = skill;
+    if skill.name.trim().is_empty() {
+        return Err("Skill name is required".to_string());
+    }
+    if skill.prompt.is_none() && skill.guidance.is_none() {
+        return Err("Skill requires 'prompt' or 'guidance'".to_string());
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    if skill.id.is_empty() {
+        skill.id = Uuid::new_v4().to_string();
+    }
+    let path = skill_path(&skill.id)?;
+    if skill.created_at.is_none() {
+        skill.created_at = Some(now.clone());
+    }
+    skill.updated_at = Some(now);
+
+    std::fs::create_dir_all(get_skills_dir()).map_err(|e| format!("Cannot create skills directory: {}", e))?;
+    let json = serde_json::to_string_pretty(&skill).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Cannot save skill: {}", e))?;
+
+    println!("[SKILL] Saved skill '{}' ({})", skill.name, skill.id);
+    Ok(skill)
+}
+
+#[command]
+pub async fn delete_skill(skill_id: String) -> Result<bool, String> {
+    let path = skill_path(&skill_id)?;
+    if !path.exists() {
+        return Err(format!("Skill '{}' not found", skill_id));
+    }
+    std::fs::remove_file(&path).map_err(|e| format!("Cannot delete skill: {}", e))?;
+    Ok(true)
+}
+
+// ============================================
+// Execution
+// ============================================
+
+/// Bind inputs to the skill variables and render the agent prompt
+pub fn compile_skill_prompt(skill: &Skill, inputs: HashMap<String, Value>) -> Result<String, String> {
+    let prepared = crate::workflow_inputs::prepare_inputs(&skill.variables, inputs);
+    if !prepared.valid {
+        return Err(format!("Invalid inputs for skill '{}': {}", skill.name, prepared.error_summary()));
+    }
+
+    let template = skill.prompt.as_ref().or(skill.guidance.as_ref())
+        .ok_or_else(|| format!("Skill '{}' has no prompt", skill.name))?;
+    let (prompt, warnings) = crate::template::render(template, &|name| prepared.inputs.get(name).cloned());
+    for warning in warnings {
+        println!("[SKILL] {}: {}", skill.name, warning);
+    }
+    Ok(prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compile_generated_skill() {
+        // Shape returned by task::generate_skill
+        let skill: Skill = serde_json::from_value(json!({
+            "name": "TikTok Engagement",
+            "icon": "heart",
+            "timeout": 300,
+            "onError": "skip",
+            "variables": [
+                {"name": "video_count", "label": "Video count", "type": "number", "default": 5},
+                {"name": "like_videos", "label": "Like videos", "type": "boolean", "default": true}
+            ],
+            "prompt": "Watch {{video_count}} videos. Like videos: {{like_videos}}.",
+            "priority": 5
+        })).unwrap();
+        assert_eq!(skill.extra["priority"], json!(5));
+
+        let prompt = compile_skill_prompt(&skill, HashMap::from([("video_count".to_string(), json!("3"))])).unwrap();
+        assert_eq!(prompt, "Watch 3 videos. Like videos: true.");
+
+        let invalid = compile_skill_prompt(&skill, HashMap::from([("video_count".to_string(), json!("many"))]));
+        assert!(invalid.unwrap_err().contains("Video count"));
+        assert!(skill_path("../config").is_err());
+    }
+}
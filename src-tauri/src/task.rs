@@ -106,6 +106,34 @@ pub struct TaskResult {
     pub output: String,
     pub screenshots: Vec<String>,
     pub duration_ms: i64,
+    /// Final answer reported by the agent's result event
+    #[serde(default)]
+    pub answer: Option<String>,
+}
+
+/// Failed agent run, with the reason the agent reported in its result event
+#[derive(Debug, Clone)]
+pub struct TaskError {
+    pub message: String,
+    pub answer: Option<String>,
+}
+
+impl From<String> for TaskError {
+    fn from(message: String) -> Self {
+        Self { message, answer: None }
+    }
+}
+
+impl From<&str> for TaskError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl std::fmt::Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
 /// Parameters for running a task on a device (Flet logic)
@@ -183,9 +211,9 @@ pub async fn create_task(
 }
 
 /// Internal function to run task on a single device
-/// Used by both run_task and run_parallel_tasks
-async fn run_task_internal(
-    window: &tauri::Window,
+/// Used by run_task, run_parallel_tasks and the workflow prompt step
+pub(crate) async fn run_task_internal<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     device_id: String,
     provider: String,
     api_key: String,
@@ -195,7 +223,8 @@ async fn run_task_internal(
     vision: Option<bool>,
     reasoning: Option<bool>,
     tracing: Option<TracingParams>,
-) -> Result<TaskResult, String> {
+    max_steps: Option<i32>,
+) -> Result<TaskResult, TaskError> {
     let start_time = std::time::Instant::now();
 
     // ============ BACKEND AI REQUEST LIMIT CHECK ============
@@ -216,7 +245,7 @@ async fn run_task_internal(
                 "line": format!("[AI] ✗ {}", e),
                 "stream": "ai_limit"
             }));
-            return Err(format!("AI request limit: {}", e));
+            return Err(format!("AI request limit: {}", e).into());
         }
     }
     // ========================================================
@@ -248,8 +277,8 @@ async fn run_task_internal(
     let vision_str = if vision.unwrap_or(true) { "true" } else { "false" };
     let reasoning_str = if reasoning.unwrap_or(false) { "true" } else { "false" };
 
-    // Build tracing JSON config
-    let tracing_json = if let Some(ref t) = tracing {
+    // Build tracing JSON config (the script also reads max_steps from it)
+    let mut tracing_json = if let Some(ref t) = tracing {
         serde_json::json!({
             "enabled": t.enabled,
             "provider": t.provider,
@@ -260,10 +289,14 @@ async fn run_task_internal(
             "langfuse_host": t.langfuse_host.clone().unwrap_or_default(),
             "langfuse_user_id": t.langfuse_user_id.clone().unwrap_or_default(),
             "save_trajectory": t.save_trajectory.clone(),
-        }).to_string()
+        })
     } else {
-        "{}".to_string()
+        serde_json::json!({})
     };
+    if let Some(max_steps) = max_steps {
+        tracing_json["max_steps"] = serde_json::json!(max_steps);
+    }
+    let tracing_json = tracing_json.to_string();
 
     // Build arguments for the helper script
     let args = vec![
@@ -306,16 +339,19 @@ async fn run_task_internal(
         cmd.env("OPENAI_API_BASE", &base_url_str);
     }
 
+    // Kill the agent if the caller stops waiting (workflow step timeout or cancel)
     let mut child = cmd
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .spawn()
         .map_err(|e| format!("Không thể chạy droidrun: {}\nCommand: {}", e, cmd_display))?;
 
-    // Register the process for cancellation support
-    if let Some(pid) = child.id() {
+    // Register the process for cancellation support, unregistered even if this future is dropped
+    let running = child.id().map(|pid| {
         register_running_task(&device_id, pid);
-    }
+        RunningTaskGuard { task_id: device_id.clone(), pid }
+    });
 
     // Take both pipes immediately
     let stdout = child.stdout.take().unwrap();
@@ -326,6 +362,7 @@ async fn run_task_internal(
     let device_id_stdout = device_id.clone();
     let stdout_handle = tokio::spawn(async move {
         let mut lines = Vec::new();
+        let mut answer = None;
         let mut reader = BufReader::new(stdout).lines();
 
         while let Ok(Some(line)) = reader.next_line().await {
@@ -359,6 +396,7 @@ async fn run_task_internal(
                             let _ = window_stdout.emit("agent-execution", &event_with_device);
                         }
                         "result" => {
+                            answer = event.get("reason").and_then(|v| v.as_str()).map(|s| s.to_string());
                             let _ = window_stdout.emit("agent-result", &event_with_device);
                         }
                         "executor_input" => {
@@ -384,7 +422,7 @@ async fn run_task_internal(
 
             lines.push(line);
         }
-        lines
+        (lines, answer)
     });
 
     let window_stderr = window.clone();
@@ -409,9 +447,9 @@ async fn run_task_internal(
     // Wait for I/O tasks to complete FIRST
     let (stdout_result, stderr_result) = tokio::join!(stdout_handle, stderr_handle);
 
-    let stdout_lines = stdout_result.unwrap_or_else(|e| {
+    let (stdout_lines, answer) = stdout_result.unwrap_or_else(|e| {
         eprintln!("[DroidRun] stdout task error: {}", e);
-        vec!["[ERROR] Failed to read stdout".to_string()]
+        (vec!["[ERROR] Failed to read stdout".to_string()], None)
     });
 
     let stderr_lines = stderr_result.unwrap_or_else(|e| {
@@ -436,7 +474,7 @@ async fn run_task_internal(
     let exit_code = status.code().unwrap_or(-1);
 
     // Unregister task when finished
-    unregister_running_task(&device_id);
+    drop(running);
 
     println!("[DroidRun][{}] Exit code: {}, Duration: {}ms", device_id, exit_code, duration);
 
@@ -448,9 +486,13 @@ async fn run_task_internal(
             output: stdout,
             screenshots: vec![],
             duration_ms: duration,
+            answer,
         })
     } else {
         let mut error_msg = format!("[{}] Task thất bại (exit code: {})", device_id, exit_code);
+        if let Some(reason) = answer.as_deref().filter(|r| !r.is_empty()) {
+            error_msg.push_str(&format!("\nAgent: {}", reason));
+        }
         if !stderr_content.is_empty() {
             error_msg.push_str(&format!("\n--- STDERR ---\n{}", stderr_content.trim()));
         }
@@ -497,7 +539,7 @@ async fn run_task_internal(
             ));
         }
         
-        Err(error_msg)
+        Err(TaskError { message: error_msg, answer })
     }
 }
 
@@ -686,7 +728,19 @@ pub async fn run_task(
     reasoning: Option<bool>,
     tracing: Option<TracingParams>,
 ) -> Result<TaskResult, String> {
-    run_task_internal(
+    let params = TaskParams {
+        device_id: device_id.clone(),
+        provider: provider.clone(),
+        api_key: String::new(),
+        model: model.clone(),
+        prompt: prompt.clone(),
+        base_url: base_url.clone(),
+        vision,
+        reasoning,
+        tracing: None,
+    };
+    let started_at = Utc::now().to_rfc3339();
+    let result = run_task_internal(
         &window,
         device_id,
         provider,
@@ -697,7 +751,57 @@ pub async fn run_task(
         vision,
         reasoning,
         tracing,
-    ).await
+        None,
+    ).await.map_err(|e| e.message);
+    record_task_run(&params, &started_at, &result).await;
+    result
+}
+
+/// Save a finished task to the run history (the API key is never recorded)
+async fn record_task_run(params: &TaskParams, started_at: &str, result: &Result<TaskResult, String>) {
+    let (run_id, success, output, screenshots, duration_ms, answer) = match result {
+        Ok(task) => (task.task_id.clone(), task.success, task.output.clone(), task.screenshots.clone(), task.duration_ms, task.answer.clone()),
+        Err(e) => (Uuid::new_v4().to_string(), false, e.clone(), vec![], 0, None),
+    };
+    let duration_ms = if duration_ms > 0 {
+        duration_ms
+    } else {
+        chrono::DateTime::parse_from_rfc3339(started_at)
+            .map(|t| (Utc::now() - t.with_timezone(&Utc)).num_milliseconds())
+            .unwrap_or(0)
+    };
+    let mut screenshots = screenshots;
+    if !success {
+        screenshots.extend(crate::run_history::capture_failure_screenshot(&params.device_id, &run_id).await);
+    }
+    let lines: Vec<&str> = output.lines().collect();
+
+    crate::run_history::record_run(crate::run_history::RunRecord {
+        run_id,
+        kind: "task".to_string(),
+        target_id: None,
+        name: Some(params.prompt.chars().take(60).collect()),
+        device_id: params.device_id.clone(),
+        status: if success { "completed" } else { "failed" }.to_string(),
+        success,
+        inputs: HashMap::from([
+            ("prompt".to_string(), serde_json::json!(params.prompt)),
+            ("provider".to_string(), serde_json::json!(params.provider)),
+            ("model".to_string(), serde_json::json!(params.model)),
+            ("baseUrl".to_string(), serde_json::json!(params.base_url)),
+            ("vision".to_string(), serde_json::json!(params.vision)),
+            ("reasoning".to_string(), serde_json::json!(params.reasoning)),
+        ]),
+        outputs: HashMap::from([("answer".to_string(), serde_json::json!(answer))]),
+        logs: crate::run_history::tail_lines(&lines),
+        duration_ms,
+        error: result.as_ref().err().cloned(),
+        screenshots,
+        started_at: started_at.to_string(),
+        finished_at: Utc::now().to_rfc3339(),
+        resumed_from: None,
+        workflow: None,
+    }).await;
 }
 
 /// Ping provider to test API key and connection - REAL API TEST with chat completion
@@ -944,6 +1048,8 @@ pub async fn run_parallel_tasks(
         let _ = window_clone.emit("task-output", &format!("[{}] >>> STARTING agent <<<", device_id));
 
         let handle = tokio::spawn(async move {
+            let params = task_params.clone();
+            let started_at = Utc::now().to_rfc3339();
             let result = run_task_internal(
                 &window_clone,
                 task_params.device_id.clone(),
@@ -955,7 +1061,9 @@ pub async fn run_parallel_tasks(
                 task_params.vision,
                 task_params.reasoning,
                 task_params.tracing,
-            ).await;
+                None,
+            ).await.map_err(|e| e.message);
+            record_task_run(&params, &started_at, &result).await;
 
             // Release semaphore permit
             drop(permit);
@@ -998,6 +1106,7 @@ pub async fn run_parallel_tasks(
                     output: e,
                     screenshots: vec![],
                     duration_ms: 0,
+                    answer: None,
                 });
             }
             Err(e) => {
@@ -1009,6 +1118,7 @@ pub async fn run_parallel_tasks(
                     output: format!("Task panicked: {}", e),
                     screenshots: vec![],
                     duration_ms: 0,
+                    answer: None,
                 });
             }
         }
@@ -1094,6 +1204,21 @@ pub fn unregister_running_task(task_id: &str) {
     }
 }
 
+/// Unregisters a task process on drop, unless a newer process took its place
+struct RunningTaskGuard {
+    task_id: String,
+    pid: u32,
+}
+
+impl Drop for RunningTaskGuard {
+    fn drop(&mut self) {
+        let registered = RUNNING_TASKS.lock().map(|tasks| tasks.get(&self.task_id) == Some(&self.pid)).unwrap_or(false);
+        if registered {
+            unregister_running_task(&self.task_id);
+        }
+    }
+}
+
 /// Input parameters for scheduling a task (từ frontend)
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -1378,6 +1503,7 @@ pub async fn run_scheduled_task(
                             output: result.message,
                             screenshots: vec![],
                             duration_ms: duration,
+                            answer: None,
                         })
                     }
                     Err(e) => {
@@ -1448,7 +1574,19 @@ pub async fn run_scheduled_task(
     }
     
     // Run the actual task
-    run_task_internal(
+    let params = TaskParams {
+        device_id: device_id.clone(),
+        provider: provider.clone(),
+        api_key: String::new(),
+        model: model.clone(),
+        prompt: task.prompt.clone(),
+        base_url: base_url.clone(),
+        vision: Some(vision),
+        reasoning: Some(reasoning),
+        tracing: None,
+    };
+    let started_at = Utc::now().to_rfc3339();
+    let result = run_task_internal(
         &window,
         device_id,
         provider,
@@ -1459,7 +1597,10 @@ pub async fn run_scheduled_task(
         Some(vision),    // vision from profile
         Some(reasoning), // reasoning from profile
         None,            // tracing
-    ).await
+        None,            // max_steps
+    ).await.map_err(|e| e.message);
+    record_task_run(&params, &started_at, &result).await;
+    result
 }
 
 /// Nâng cao lời nhắc bằng AI - biến prompt đơn giản thành prompt chi tiết từng bước
@@ -2271,6 +2412,30 @@ pub async fn analyze_screen_for_action(
     ))
 }
 
+/// Fill the fields the editor expects and check the AI output against the workflow schema
+fn finalize_generated_workflow(mut workflow: serde_json::Value) -> Result<serde_json::Value, String> {
+    let object = workflow.as_object_mut().ok_or("AI không trả về workflow object")?;
+    if !object.contains_key("id") {
+        let name = object.get("name").and_then(|n| n.as_str()).unwrap_or("workflow");
+        let slug = crate::workflow_library::slugify(name);
+        object.insert("id".to_string(), serde_json::json!(format!("{}-{}", slug, Utc::now().timestamp())));
+    }
+    for field in ["inputs", "outputs"] {
+        object.entry(field).or_insert_with(|| serde_json::json!([]));
+    }
+
+    let errors = crate::workflow_schema::validate_against_schema(&workflow);
+    if !errors.is_empty() {
+        println!("[GENERATE_WORKFLOW] Schema errors: {:?}", errors);
+        return Err(format!(
+            "Workflow do AI tạo không khớp schema v{}: {}",
+            crate::workflow_schema::WORKFLOW_SCHEMA_VERSION,
+            errors.iter().take(5).cloned().collect::<Vec<_>>().join("; ")
+        ));
+    }
+    Ok(workflow)
+}
+
 /// Tạo workflow tự động bằng AI từ mô tả của người dùng
 #[command]
 pub async fn generate_workflow(
@@ -2321,13 +2486,14 @@ STEP TYPES:
 
 1. ACTION - Interact with device:
 {"id": "step-1", "type": "action", "name": "Open TikTok", "action": "open_app", "params": {"package": "com.zhiliaoapp.musically"}}
-{"id": "step-2", "type": "action", "name": "Tap like", "action": "tap", "params": {"target": "heart button"}}
+{"id": "step-2", "type": "action", "name": "Tap like", "action": "tap_element", "params": {"text": "Like"}}
 
 2. WAIT - Delay execution:
 {"id": "step-3", "type": "wait", "name": "Wait for load", "duration": "3000"}
 
 3. PROMPT - AI agent instruction (most powerful):
-{"id": "step-4", "type": "prompt", "name": "Watch video", "prompt": "Watch the current video for 5-10 seconds, then swipe up to next video", "save_to": "watch_result"}
+{"id": "step-4", "type": "prompt", "name": "Watch video", "prompt": "Watch the current video for 5-10 seconds, then swipe up to next video", "params": {"maxSteps": 15}, "saveTo": "watch_result"}
+The agent runs to completion; saveTo receives {"success": bool, "answer": "final answer", "error": null}.
 
 4. LOOP - Repeat steps:
 {"id": "step-5", "type": "loop", "name": "Video loop", "count": "{{video_count}}", "variable": "i", "body": [...nested steps...]}
@@ -2336,10 +2502,11 @@ STEP TYPES:
 {"id": "step-6", "type": "condition", "name": "Check like", "condition": "{{like_enabled}}", "then": [...steps if true...], "else_branch": [...steps if false...]}
 
 6. PYTHON - Run Python code:
-{"id": "step-7", "type": "python", "name": "Random delay", "script": "import random; return {'delay': random.randint(3, 8)}", "save_to": "random_delay"}
+{"id": "step-7", "type": "python", "name": "Random delay", "script": "import random; return {'delay': random.randint(3, 8)}", "saveTo": "random_delay"}
 
 RULES:
 - Use {{input_name}} to reference inputs in step parameters
+- Placeholders support paths, filters and helpers: {{result.items[0].id}}, {{name | default:"x" | upper}}, {{random(5,15)}}
 - Each step needs unique id (step-1, step-2, step-3...)
 - For nested steps use step-X-Y format (step-3-1, step-3-2...)
 - Use PROMPT type for complex AI-driven actions
@@ -2361,7 +2528,7 @@ EXAMPLE - TikTok engagement workflow:
     {"id": "step-1", "type": "action", "name": "Open TikTok", "action": "open_app", "params": {"package": "com.zhiliaoapp.musically"}},
     {"id": "step-2", "type": "wait", "name": "Wait load", "duration": "4000"},
     {"id": "step-3", "type": "loop", "name": "Watch loop", "count": "{{video_count}}", "variable": "i", "body": [
-      {"id": "step-3-1", "type": "prompt", "name": "Watch video", "prompt": "Watch the current video for 5-10 seconds. If video is interesting based on content, double tap to like. Then swipe up to next video.", "save_to": "watch_result"},
+      {"id": "step-3-1", "type": "prompt", "name": "Watch video", "prompt": "Watch the current video for 5-10 seconds. If video is interesting based on content, double tap to like. Then swipe up to next video.", "saveTo": "watch_result"},
       {"id": "step-3-2", "type": "wait", "name": "Brief pause", "duration": "1000"}
     ]}
   ]
@@ -2418,7 +2585,7 @@ Now create a workflow based on this description:"##;
                         let workflow: serde_json::Value = serde_json::from_str(&json_str)
                             .map_err(|e| format!("AI trả về JSON không hợp lệ: {}. Raw: {}", e, json_str.chars().take(200).collect::<String>()))?;
                         
-                        Ok(workflow)
+                        finalize_generated_workflow(workflow)
                     } else {
                         let body = response.text().await.unwrap_or_default();
                         Err(format!("Lỗi API {}: {}", status.as_u16(), body.chars().take(200).collect::<String>()))
@@ -2463,7 +2630,7 @@ Now create a workflow based on this description:"##;
                         let workflow: serde_json::Value = serde_json::from_str(&json_str)
                             .map_err(|e| format!("AI trả về JSON không hợp lệ: {}", e))?;
                         
-                        Ok(workflow)
+                        finalize_generated_workflow(workflow)
                     } else {
                         Err(format!("Lỗi {}", status.as_u16()))
                     }
@@ -2509,7 +2676,7 @@ Now create a workflow based on this description:"##;
                         let workflow: serde_json::Value = serde_json::from_str(&json_str)
                             .map_err(|e| format!("AI trả về JSON không hợp lệ: {}", e))?;
                         
-                        Ok(workflow)
+                        finalize_generated_workflow(workflow)
                     } else {
                         Err(format!("Lỗi {}", status.as_u16()))
                     }
@@ -2543,4 +2710,19 @@ mod tests {
         };
         assert_eq!(actual, "OpenAILike");
     }
+
+    #[test]
+    fn test_running_task_guard() {
+        register_running_task("guard-device", 41);
+        let guard = RunningTaskGuard { task_id: "guard-device".to_string(), pid: 41 };
+        drop(guard);
+        assert!(!RUNNING_TASKS.lock().unwrap().contains_key("guard-device"));
+
+        // A newer process on the same device stays registered
+        let stale = RunningTaskGuard { task_id: "guard-device".to_string(), pid: 41 };
+        register_running_task("guard-device", 42);
+        drop(stale);
+        assert_eq!(RUNNING_TASKS.lock().unwrap().get("guard-device"), Some(&42));
+        unregister_running_task("guard-device");
+    }
 }
@@ -0,0 +1,404 @@
+// Template Module - Renders `{{...}}` placeholders in workflow step fields
+// Hỗ trợ path sâu `{{user.posts[0].id}}`, filter `{{name | default:"x" | upper}}` và hàm `{{random(5,15)}}`
+
+use crate::expression::{self, python_str, to_display_string};
+use serde_json::Value;
+
+/// A parsed `{{ expression | filter:arg | ... }}` placeholder
+struct Placeholder {
+    expression: String,
+    filters: Vec<(String, Vec<String>)>,
+}
+
+impl Placeholder {
+    fn has_default(&self) -> bool {
+        self.filters.iter().any(|(name, _)| name == "default")
+    }
+}
+
+// ============================================
+// Public API
+// ============================================
+
+/// Render every placeholder to text. Placeholders that cannot be resolved stay as literal
+/// text and are reported in the returned warnings.
+pub fn render(template: &str, lookup: &dyn Fn(&str) -> Option<Value>) -> (String, Vec<String>) {
+    let mut output = String::new();
+    let mut warnings = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else { break };
+        output.push_str(&rest[..start]);
+
+        let raw = &rest[start..start + end + 4];
+        match evaluate(&after[..end], lookup) {
+            Ok(value) => output.push_str(&to_display_string(&value)),
+            Err(warning) => {
+                warnings.push(format!("{}: {}", raw, warning));
+                output.push_str(raw);
+            }
+        }
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+
+    (output, warnings)
+}
+
+/// Like `render`, but a template that is exactly one placeholder keeps the JSON type of its value
+/// (`"{{user}}"` gives the object, `"{{count}}"` the number)
+pub fn resolve(template: &str, lookup: &dyn Fn(&str) -> Option<Value>) -> (Value, Vec<String>) {
+    let trimmed = template.trim();
+    if let Some(inner) = trimmed.strip_prefix("{{").and_then(|t| t.strip_suffix("}}")) {
+        if !inner.contains("{{") && !inner.contains("}}") {
+            return match evaluate(inner, lookup) {
+                Ok(value) => (value, vec![]),
+                Err(warning) => (Value::String(template.to_string()), vec![format!("{}: {}", trimmed, warning)]),
+            };
+        }
+    }
+    let (text, warnings) = render(template, lookup);
+    (Value::String(text), warnings)
+}
+
+/// Root variable names read by the placeholders in `text` ("user" for `{{user.posts[0].id}}`).
+/// Placeholders with a `default` filter are skipped since they never fail.
+pub fn template_variables(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for inner in placeholders(text) {
+        let placeholder = parse_placeholder(inner);
+        if placeholder.has_default() {
+            continue;
+        }
+        let found = expression::referenced_variables(&placeholder.expression)
+            .unwrap_or_else(|_| legacy_name(&placeholder.expression).into_iter().collect());
+        for name in found {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Translate a template to a Python expression for exported workflow scripts: `_render([...])`
+/// gives the text like `render`, `keep_type` keeps the value of a lone placeholder like `resolve`
+pub fn to_python(template: &str, keep_type: bool) -> String {
+    let trimmed = template.trim();
+    if let Some(inner) = trimmed.strip_prefix("{{").and_then(|t| t.strip_suffix("}}")) {
+        if keep_type && !inner.contains("{{") && !inner.contains("}}") {
+            return format!("_resolve({}, {})", python_str(template), placeholder_to_python(trimmed, inner));
+        }
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else { break };
+        if start > 0 {
+            parts.push(python_str(&rest[..start]));
+        }
+        parts.push(placeholder_to_python(&rest[start..start + end + 4], &after[..end]));
+        rest = &after[end + 2..];
+    }
+    if parts.is_empty() {
+        return python_str(template);
+    }
+    if !rest.is_empty() {
+        parts.push(python_str(rest));
+    }
+    format!("_render([{}])", parts.join(", "))
+}
+
+/// `_P(raw, source, names, has_default, value, filters)` - values and filter args are lambdas
+/// so they are evaluated lazily, in the same order as `evaluate`
+fn placeholder_to_python(raw: &str, inner: &str) -> String {
+    let thunk = |source: &str| match expression::to_python(source) {
+        Ok(code) => format!("lambda: {}", code),
+        Err(e) => format!("lambda: _invalid({})", python_str(&e.to_string())),
+    };
+
+    let placeholder = parse_placeholder(inner);
+    let source = placeholder.expression.trim();
+    let names: Vec<String> = expression::referenced_variables(source)
+        .unwrap_or_default()
+        .iter()
+        .map(|name| python_str(name))
+        .collect();
+    let filters: Vec<String> = placeholder.filters.iter()
+        .map(|(name, args)| {
+            let args: Vec<String> = args.iter().map(|arg| thunk(arg)).collect();
+            format!("({}, [{}])", python_str(name), args.join(", "))
+        })
+        .collect();
+
+    format!(
+        "_P({}, {}, [{}], {}, {}, [{}])",
+        python_str(raw),
+        python_str(source),
+        names.join(", "),
+        if placeholder.has_default() { "True" } else { "False" },
+        thunk(source),
+        filters.join(", "),
+    )
+}
+
+// ============================================
+// Evaluation
+// ============================================
+
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else { break };
+        found.push(&after[..end]);
+        rest = &after[end + 2..];
+    }
+    found
+}
+
+/// Leading identifier of a placeholder, used when it does not parse as an expression
+fn legacy_name(expression: &str) -> Option<String> {
+    let name: String = expression.trim()
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+fn evaluate(inner: &str, lookup: &dyn Fn(&str) -> Option<Value>) -> Result<Value, String> {
+    let placeholder = parse_placeholder(inner);
+    let source = placeholder.expression.trim();
+
+    // Names that are not valid expressions (e.g. "step-1") still resolve as plain variables
+    let value = match lookup(source) {
+        Some(value) => Ok(value),
+        None => {
+            let missing: Vec<String> = expression::referenced_variables(source)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .filter(|name| lookup(name).is_none())
+                .collect();
+            if missing.is_empty() {
+                expression::evaluate(source, lookup).map_err(|e| e.to_string())
+            } else {
+                Err(format!("unknown variable '{}'", missing.join("', '")))
+            }
+        }
+    };
+
+    let mut value = match value {
+        Ok(value) if !value.is_null() => value,
+        Ok(_) if !placeholder.has_default() => return Err(format!("'{}' has no value", source)),
+        Err(e) if !placeholder.has_default() => return Err(e),
+        _ => Value::Null,
+    };
+
+    for (name, args) in &placeholder.filters {
+        let args = args.iter()
+            .map(|arg| expression::evaluate(arg, lookup).map_err(|e| format!("filter '{}': {}", name, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        value = apply_filter(name, &args, value)?;
+    }
+    Ok(value)
+}
+
+/// Split `expr | name:arg1,arg2 | name` on single pipes outside quotes
+fn parse_placeholder(inner: &str) -> Placeholder {
+    let mut parts = split_outside_quotes(inner, '|').into_iter();
+    let expression = parts.next().unwrap_or_default().trim().to_string();
+    let filters = parts
+        .map(|part| {
+            let part = part.trim();
+            match part.split_once(':') {
+                Some((name, args)) => (
+                    name.trim().to_string(),
+                    split_outside_quotes(args, ',').into_iter().map(|a| a.trim().to_string()).collect(),
+                ),
+                None => (part.to_string(), vec![]),
+            }
+        })
+        .collect();
+    Placeholder { expression, filters }
+}
+
+/// Split on `separator`, ignoring it inside quotes and (for `|`) in the `||` operator
+fn split_outside_quotes(text: &str, separator: char) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut parts = vec![String::new()];
+    let mut quote: Option<char> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match quote {
+            Some(q) => {
+                if c == '\\' && i + 1 < chars.len() {
+                    parts.last_mut().unwrap().push(c);
+                    i += 1;
+                    parts.last_mut().unwrap().push(chars[i]);
+                } else {
+                    if c == q {
+                        quote = None;
+                    }
+                    parts.last_mut().unwrap().push(c);
+                }
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                parts.last_mut().unwrap().push(c);
+            }
+            None if c == separator && separator == '|' && chars.get(i + 1) == Some(&'|') => {
+                parts.last_mut().unwrap().push_str("||");
+                i += 1;
+            }
+            None if c == separator => parts.push(String::new()),
+            None => parts.last_mut().unwrap().push(c),
+        }
+        i += 1;
+    }
+    parts
+}
+
+// ============================================
+// Filters
+// ============================================
+
+fn apply_filter(name: &str, args: &[Value], value: Value) -> Result<Value, String> {
+    let text = || to_display_string(&value);
+    match name {
+        "default" => {
+            let empty = value.is_null() || value.as_str().map(|s| s.is_empty()).unwrap_or(false);
+            Ok(if empty { args.first().cloned().unwrap_or(Value::Null) } else { value })
+        }
+        "upper" => Ok(Value::String(text().to_uppercase())),
+        "lower" => Ok(Value::String(text().to_lowercase())),
+        "trim" => Ok(Value::String(text().trim().to_string())),
+        "json" => serde_json::to_string(&value).map(Value::String).map_err(|e| e.to_string()),
+        "urlencode" => Ok(Value::String(url_encode(&text()))),
+        "number" => format_number(&value, args).map(Value::String),
+        "date" => format_date(&value, args).map(Value::String),
+        other => Err(format!("unknown filter '{}'", other)),
+    }
+}
+
+fn url_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `number` rounds to an integer, `number:2` keeps two decimals, `number:0,","` adds a thousands separator
+fn format_number(value: &Value, args: &[Value]) -> Result<String, String> {
+    let n = match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("filter 'number': {} is not a number", value))?;
+
+    let decimals = args.first().and_then(|d| d.as_f64()).unwrap_or(0.0).max(0.0) as usize;
+    let formatted = format!("{:.*}", decimals, n);
+    let Some(separator) = args.get(1).map(to_display_string).filter(|s| !s.is_empty()) else {
+        return Ok(formatted);
+    };
+
+    let (sign, unsigned) = formatted.strip_prefix('-').map(|u| ("-", u)).unwrap_or(("", &formatted));
+    let (integer, fraction) = unsigned.split_once('.').map(|(i, f)| (i, Some(f))).unwrap_or((unsigned, None));
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push_str(&separator);
+        }
+        grouped.push(digit);
+    }
+    Ok(match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, grouped, fraction),
+        None => format!("{}{}", sign, grouped),
+    })
+}
+
+/// Format "now", an RFC 3339 string or a unix timestamp (seconds or milliseconds) with a
+/// strftime pattern, default "%Y-%m-%d %H:%M:%S" in local time
+fn format_date(value: &Value, args: &[Value]) -> Result<String, String> {
+    use chrono::{DateTime, Local, TimeZone};
+
+    let date: DateTime<Local> = match value {
+        Value::String(s) if s.trim().eq_ignore_ascii_case("now") => Local::now(),
+        Value::String(s) => DateTime::parse_from_rfc3339(s.trim())
+            .map(|d| d.with_timezone(&Local))
+            .or_else(|_| s.trim().parse::<i64>().map_err(|e| e.to_string()).and_then(timestamp_to_local))
+            .map_err(|_| format!("filter 'date': cannot parse \"{}\"", s))?,
+        Value::Number(n) => timestamp_to_local(n.as_f64().unwrap_or(0.0) as i64)?,
+        other => return Err(format!("filter 'date': {} is not a date", other)),
+    };
+
+    fn timestamp_to_local(ts: i64) -> Result<DateTime<Local>, String> {
+        // Values this large are milliseconds
+        let millis = if ts.abs() > 100_000_000_000 { ts } else { ts * 1000 };
+        Local.timestamp_millis_opt(millis).single().ok_or_else(|| format!("invalid timestamp {}", ts))
+    }
+
+    let pattern = args.first().map(to_display_string).unwrap_or_else(|| "%Y-%m-%d %H:%M:%S".to_string());
+    let mut formatted = String::new();
+    use std::fmt::Write;
+    write!(formatted, "{}", date.format(&pattern)).map_err(|_| format!("filter 'date': invalid format \"{}\"", pattern))?;
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scope(name: &str) -> Option<Value> {
+        match name {
+            "user" => Some(json!({"name": " Alice ", "posts": [{"id": 42}]})),
+            "count" => Some(json!(1234.5)),
+            "step-1" => Some(json!("legacy")),
+            "empty" => Some(json!("")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_render_paths_and_filters() {
+        let render_ok = |t: &str| {
+            let (text, warnings) = render(t, &scope);
+            assert!(warnings.is_empty(), "{:?}", warnings);
+            text
+        };
+        assert_eq!(render_ok("id={{user.posts[0].id}}"), "id=42");
+        assert_eq!(render_ok("{{ user.name | trim | upper }}"), "ALICE");
+        assert_eq!(render_ok("{{missing | default:\"x\"}}/{{empty | default:'y'}}"), "x/y");
+        assert_eq!(render_ok("{{count | number:2,\",\"}}"), "1,234.50");
+        assert_eq!(render_ok("{{'a b&c' | urlencode}}"), "a%20b%26c");
+        assert_eq!(render_ok("{{user.posts | json}}"), "[{\"id\":42}]");
+        assert_eq!(render_ok("{{0 | date:'%Y'}}").len(), 4);
+        assert_eq!(render_ok("{{step-1}}"), "legacy");
+
+        let n: i64 = render_ok("{{random(5, 15)}}").parse().unwrap();
+        assert!((5..=15).contains(&n));
+    }
+
+    #[test]
+    fn test_unknown_variables() {
+        let (text, warnings) = render("Hi {{nobody}} {{user.age}}", &scope);
+        assert_eq!(text, "Hi {{nobody}} {{user.age}}");
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("unknown variable 'nobody'"));
+
+        assert_eq!(resolve("{{user.posts[0]}}", &scope).0, json!({"id": 42}));
+        assert_eq!(template_variables("{{a | default:1}} {{random(1, n)}} {{user.name}}"), vec!["n", "user"]);
+    }
+}
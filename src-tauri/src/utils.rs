@@ -20,6 +20,23 @@ fn new_command(program: &str) -> Command {
     cmd
 }
 
+/// ~/.mun-sdk-ai-v2 - tests get a temp dir per process so they never touch real data
+pub(crate) fn app_data_dir() -> std::path::PathBuf {
+    if cfg!(test) {
+        return std::env::temp_dir().join(format!("mun-sdk-ai-v2-test-{}", std::process::id()));
+    }
+    let home = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("."));
+    home.join(".mun-sdk-ai-v2")
+}
+
+/// Ids used as file names (workflows, runs, skills) must stay inside their directory
+pub(crate) fn check_file_id(kind: &str, id: &str) -> Result<(), String> {
+    if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+        return Err(format!("Invalid {} id: {}", kind, id));
+    }
+    Ok(())
+}
+
 /// Find available Python executable (python3.11 > python3 > python)
 fn get_python_cmd() -> &'static str {
     if Command::new("python3.11").arg("--version").output().is_ok() {
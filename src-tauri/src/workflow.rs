@@ -1,9 +1,10 @@
 // Workflow Engine Module - Step-based Task Automation
-// Hỗ trợ các step types: action, condition, loop, while, parallel, python, prompt, wait, extract
+// Hỗ trợ các step types: action, condition, loop, while, parallel, python, prompt, wait, extract, call
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::process::Stdio;
+use std::sync::{Arc, Mutex};
 use tauri::{command, Emitter, Manager};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use uuid::Uuid;
@@ -15,6 +16,7 @@ use std::os::windows::process::CommandExt;
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 /// Helper to create a tokio command with hidden window on Windows
+/// Child processes are killed when their step future is dropped (e.g. a cancelled parallel branch)
 fn new_async_command(program: &str) -> tokio::process::Command {
     #[cfg(windows)]
     let mut std_cmd = std::process::Command::new(program);
@@ -23,7 +25,9 @@ fn new_async_command(program: &str) -> tokio::process::Command {
     
     #[cfg(windows)]
     std_cmd.creation_flags(CREATE_NO_WINDOW);
-    tokio::process::Command::from(std_cmd)
+    let mut cmd = tokio::process::Command::from(std_cmd);
+    cmd.kill_on_drop(true);
+    cmd
 }
 
 // ============================================
@@ -71,6 +75,8 @@ pub struct WorkflowInput {
     pub min: Option<f64>,
     pub max: Option<f64>,
     pub placeholder: Option<String>,
+    /// Inputs without a default are required unless this is false
+    pub required: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,12 +85,12 @@ pub struct SelectOption {
     pub label: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct WorkflowStep {
     pub id: String,
     
-    /// Step type: action, condition, loop, while, parallel, python, prompt, wait, extract, skill
+    /// Step type: action, condition, loop, foreach, while, break, continue, parallel, python, prompt, wait, wait_for, extract, skill, call, try, set
     #[serde(rename = "type")]
     pub step_type: String,
     
@@ -94,10 +100,10 @@ pub struct WorkflowStep {
     pub action: Option<String>,
     pub params: Option<HashMap<String, serde_json::Value>>,
     
-    // For CONDITION step
+    // For CONDITION step (also optional guard of BREAK / CONTINUE)
     pub condition: Option<String>,
     pub then: Option<Vec<WorkflowStep>>,
-    #[serde(rename = "else")]
+    #[serde(rename = "else", alias = "else_branch")]
     pub else_branch: Option<Vec<WorkflowStep>>,
     
     // For LOOP step
@@ -105,11 +111,32 @@ pub struct WorkflowStep {
     pub variable: Option<String>, // Loop variable name
     pub body: Option<Vec<WorkflowStep>>,
     
+    // For FOREACH step - item goes to `variable` (default "item")
+    /// Array to iterate: "{{usernames}}", "{{result.items}}" or a JSON array string
+    pub items: Option<String>,
+    /// Variable holding the 0-based index (default: "index")
+    pub index_variable: Option<String>,
+    /// Max iterations running at once, each on its own copy of the context (default: 1)
+    /// Above 1 progress is not checkpointed, so a resumed run starts again at the first item
+    pub concurrency: Option<usize>,
+    
     // For WHILE step
     pub max_iterations: Option<i32>,
     
+    // For TRY step - `body` runs first, the error is bound to `variable` (default "error")
+    /// Steps run when the body fails
+    pub catch: Option<Vec<WorkflowStep>>,
+    /// Steps that always run after body/catch
+    pub finally: Option<Vec<WorkflowStep>>,
+    
     // For PARALLEL step
     pub branches: Option<Vec<Vec<WorkflowStep>>>,
+    /// "all" (default) waits for every branch, "first_success" stops at the first branch that succeeds
+    pub join: Option<String>,
+    /// Stop the remaining branches as soon as one fails (default: true)
+    pub fail_fast: Option<bool>,
+    /// How branch variables merge back: "last_writer" (default) or "namespace" (per-branch list in save_to)
+    pub merge_strategy: Option<String>,
     
     // For PYTHON step
     pub script: Option<String>,
@@ -125,25 +152,76 @@ pub struct WorkflowStep {
     pub duration: Option<String>, // Can be number or variable
     pub wait_condition: Option<String>,
     
+    // For WAIT_FOR step (polls the device, element uses `selector`)
+    /// "appear" (default with selector), "disappear", "activity", "keyboard", "keyboard_hidden", "screen_change"
+    pub until: Option<String>,
+    /// Activity name (or part of it) to wait for
+    pub activity: Option<String>,
+    /// Poll interval in milliseconds (default: 500)
+    pub poll_interval: Option<u64>,
+    /// Give up after this many milliseconds (default: 30000)
+    pub wait_timeout: Option<u64>,
+    
     // Delay after this step completes (in milliseconds)
     pub delay_after: Option<u64>,
     
+    /// Max execution time for this step in seconds (child processes are killed on timeout)
+    pub timeout: Option<u64>,
+    
     // For EXTRACT step
     pub selector: Option<String>,
     
     // For SKILL step
     pub skill_id: Option<String>,
     
+    // For CALL step - child inputs come from `params`, outputs go to `save_to` (or same-named variables)
+    pub workflow_id: Option<String>,
+    
+    // For SET step - applied in order, later entries see earlier results
+    pub set: Option<Vec<SetOperation>>,
+    
     // Error handling
     pub on_error: Option<ErrorConfig>,
 }
 
+/// One assignment of a `set` step - exactly one of the value fields is used
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SetOperation {
+    /// Variable to write
+    pub name: String,
+    /// Literal value, strings are templates ("{{user}}" keeps its JSON type)
+    pub value: Option<serde_json::Value>,
+    /// Expression evaluated like a condition, e.g. "count * 2 + 1"
+    pub expression: Option<String>,
+    /// Add this amount to the current number (missing variable counts as 0)
+    pub increment: Option<serde_json::Value>,
+    /// Push this value to the current list (missing variable counts as [])
+    pub append: Option<serde_json::Value>,
+    /// Shallow-merge this object into the current object (missing variable counts as {})
+    pub merge: Option<serde_json::Value>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ErrorConfig {
     pub strategy: String, // "abort", "skip", "retry", "fallback"
     pub retries: Option<i32>,
     pub fallback: Option<Vec<WorkflowStep>>,
+    
+    // Retry policy - delay = retry_delay * backoff^(attempt-1), capped at max_delay, +/- jitter
+    /// Delay before the first retry in milliseconds (default: 1000)
+    pub retry_delay: Option<u64>,
+    /// Multiplier applied to the delay after each attempt (default: 1 = fixed delay)
+    pub backoff: Option<f64>,
+    /// Upper bound for the delay in milliseconds (default: 60000)
+    pub max_delay: Option<u64>,
+    /// Random spread as a fraction of the delay, e.g. 0.2 = +/-20% (default: 0)
+    pub jitter: Option<f64>,
+    /// Only retry when the error message matches this regex (default: any error)
+    pub retry_on: Option<String>,
+    /// Steps to run before each retry, e.g. press back and reopen the app
+    pub recovery: Option<Vec<WorkflowStep>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -176,6 +254,43 @@ pub struct WorkflowContext {
     /// Error context for self-healing
     #[serde(default)]
     pub last_error: Option<ErrorContext>,
+    
+    /// Workflow ids on the current call chain (sub-workflow cycle detection)
+    #[serde(default)]
+    pub call_stack: Vec<String>,
+    
+    /// Run id of the top-level workflow execution (registry key for cancel)
+    #[serde(default)]
+    pub run_id: String,
+    
+    /// Step whose deadline (step or workflow timeout) was hit
+    #[serde(default)]
+    pub timed_out_step: Option<String>,
+    
+    /// Completed iterations of loops still running, by step id (saved in checkpoints)
+    #[serde(default)]
+    pub loop_counters: HashMap<String, i32>,
+    
+    /// Innermost step whose error is propagating (read by try/catch)
+    #[serde(default)]
+    pub failed_step_id: Option<String>,
+    
+    /// Set while `finally` steps run - they still run after a graceful cancel
+    #[serde(skip)]
+    pub in_finally: bool,
+    
+    /// Last failure absorbed by an on_error skip or fallback
+    #[serde(skip)]
+    pub handled_error: Option<HandledError>,
+}
+
+/// A step error that did not stop the run because on_error skipped it or ran its fallback
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandledError {
+    pub step_id: String,
+    /// "skipped" or "recovered"
+    pub status: String,
+    pub error: String,
 }
 
 /// Record of an executed action for history tracking
@@ -199,6 +314,9 @@ pub struct ErrorContext {
     pub error_message: String,
     pub retry_count: i32,
     pub suggested_fix: Option<String>,
+    /// Last screen state seen before the error (activity, keyboard, visible texts)
+    #[serde(default)]
+    pub screen_state: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -214,11 +332,20 @@ pub struct WorkflowLog {
 #[serde(rename_all = "camelCase")]
 pub struct WorkflowResult {
     pub success: bool,
+    pub run_id: String,
+    /// "completed", "failed", "timeout", "cancelled" or "invalid_inputs"
+    pub status: String,
     pub workflow_id: String,
     pub outputs: HashMap<String, serde_json::Value>,
     pub logs: Vec<WorkflowLog>,
     pub duration_ms: i64,
     pub error: Option<String>,
+    /// Step that was running when a timeout aborted the workflow
+    #[serde(default)]
+    pub timed_out_step: Option<String>,
+    /// Per-input problems when the run was rejected before starting
+    #[serde(default)]
+    pub input_errors: Vec<crate::workflow_inputs::InputError>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -230,6 +357,397 @@ pub struct PythonScriptResult {
     pub error: Option<String>,
 }
 
+// ============================================
+// Run Registry - active runs and cancellation
+// ============================================
+
+/// An active workflow run, listed for the cancel UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowRunInfo {
+    pub run_id: String,
+    pub workflow_id: String,
+    pub workflow_name: String,
+    pub device_id: String,
+    pub started_at: String,
+    pub current_step_id: Option<String>,
+    /// Set once cancel is requested: "graceful" or "immediate"
+    pub cancel_requested: Option<String>,
+}
+
+struct ActiveRun {
+    info: WorkflowRunInfo,
+    cancel_now: Arc<tokio::sync::Notify>,
+    debug: Option<DebugSession>,
+}
+
+lazy_static::lazy_static! {
+    static ref ACTIVE_RUNS: Mutex<HashMap<String, ActiveRun>> = Mutex::new(HashMap::new());
+}
+
+/// Removes the run from the registry when run_workflow returns (or is dropped)
+struct RunGuard(String);
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        if let Ok(mut runs) = ACTIVE_RUNS.lock() {
+            runs.remove(&self.0);
+        }
+    }
+}
+
+/// Add a run to the registry - fails if a run with the same id is still active
+fn register_run(info: WorkflowRunInfo, debug: Option<WorkflowDebugOptions>) -> Result<(RunGuard, Arc<tokio::sync::Notify>), String> {
+    let cancel_now = Arc::new(tokio::sync::Notify::new());
+    let run_id = info.run_id.clone();
+    let debug = debug.map(|options| DebugSession {
+        breakpoints: options.breakpoints.into_iter().collect(),
+        pause_next: options.pause_on_start,
+        paused: HashMap::new(),
+        next_pause: 0,
+        paused_total: std::time::Duration::ZERO,
+        paused_since: None,
+    });
+    let mut runs = ACTIVE_RUNS.lock().map_err(|e| format!("Lock error: {}", e))?;
+    if runs.contains_key(&run_id) {
+        return Err(format!("Run {} is already active", run_id));
+    }
+    runs.insert(run_id.clone(), ActiveRun { info, cancel_now: cancel_now.clone(), debug });
+    Ok((RunGuard(run_id), cancel_now))
+}
+
+fn set_run_step(run_id: &str, step_id: &str) {
+    if let Ok(mut runs) = ACTIVE_RUNS.lock() {
+        if let Some(run) = runs.get_mut(run_id) {
+            run.info.current_step_id = Some(step_id.to_string());
+        }
+    }
+}
+
+/// True once cancel has been requested for this run
+fn is_run_cancelled(run_id: &str) -> bool {
+    ACTIVE_RUNS.lock()
+        .map(|runs| runs.get(run_id).map(|r| r.info.cancel_requested.is_some()).unwrap_or(false))
+        .unwrap_or(false)
+}
+
+/// List workflow runs that are still executing
+#[command]
+pub async fn list_workflow_runs() -> Result<Vec<WorkflowRunInfo>, String> {
+    let runs = ACTIVE_RUNS.lock().map_err(|e| format!("Lock error: {}", e))?;
+    let mut list: Vec<WorkflowRunInfo> = runs.values().map(|r| r.info.clone()).collect();
+    list.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    Ok(list)
+}
+
+/// Cancel a workflow run - at the next step boundary, or immediately (kills child processes)
+#[command]
+pub async fn cancel_workflow(run_id: String, immediate: Option<bool>) -> Result<bool, String> {
+    let immediate = immediate.unwrap_or(false);
+    println!("[WORKFLOW] Cancel requested for run {} (immediate: {})", run_id, immediate);
+    
+    let mut runs = ACTIVE_RUNS.lock().map_err(|e| format!("Lock error: {}", e))?;
+    let Some(run) = runs.get_mut(&run_id) else {
+        println!("[WORKFLOW] No active run: {}", run_id);
+        return Ok(false);
+    };
+    
+    run.info.cancel_requested = Some(if immediate { "immediate" } else { "graceful" }.to_string());
+    if immediate {
+        run.cancel_now.notify_one();
+    }
+    // Wake paused steps so the run can stop
+    for paused in run.debug.iter().flat_map(|debug| debug.paused.values()) {
+        paused.wake.notify_one();
+    }
+    Ok(true)
+}
+
+// ============================================
+// Debugger - breakpoints and step-through
+// ============================================
+
+/// Debug mode options for run_workflow
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowDebugOptions {
+    /// Step ids to pause before (nested steps included)
+    #[serde(default)]
+    pub breakpoints: Vec<String>,
+    /// Pause before the first step
+    #[serde(default)]
+    pub pause_on_start: bool,
+}
+
+/// Context snapshot reported while a run is paused
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowDebugState {
+    pub run_id: String,
+    /// Identifies this pause - concurrent foreach iterations can pause at the same step id
+    #[serde(default)]
+    pub pause_id: String,
+    pub paused_at: Option<String>,
+    pub breakpoints: Vec<String>,
+    pub inputs: HashMap<String, serde_json::Value>,
+    pub variables: HashMap<String, serde_json::Value>,
+    pub history: Vec<ActionRecord>,
+    pub last_error: Option<ErrorContext>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DebugAction {
+    /// Run until the next breakpoint
+    Continue,
+    /// Run the paused step, then pause again
+    Step,
+    /// Skip the paused step, then pause again
+    Skip,
+}
+
+/// A step waiting at a breakpoint - parallel branches can be paused at the same time
+struct PausedStep {
+    state: WorkflowDebugState,
+    action: Option<DebugAction>,
+    /// Variable edits made while paused, applied when the step wakes up
+    edits: Vec<(String, serde_json::Value)>,
+    wake: Arc<tokio::sync::Notify>,
+}
+
+struct DebugSession {
+    breakpoints: std::collections::HashSet<String>,
+    pause_next: bool,
+    /// Paused steps by pause id
+    paused: HashMap<String, PausedStep>,
+    next_pause: u64,
+    /// Time with at least one step paused, which does not count against workflow.timeout
+    paused_total: std::time::Duration,
+    paused_since: Option<std::time::Instant>,
+}
+
+impl DebugSession {
+    fn snapshot(&self, run_id: &str, step_id: &str, context: &WorkflowContext) -> WorkflowDebugState {
+        let mut breakpoints: Vec<String> = self.breakpoints.iter().cloned().collect();
+        breakpoints.sort();
+        WorkflowDebugState {
+            run_id: run_id.to_string(),
+            pause_id: String::new(),
+            paused_at: Some(step_id.to_string()),
+            breakpoints,
+            inputs: context.inputs.clone(),
+            variables: context.variables.clone(),
+            history: context.history.clone(),
+            last_error: context.last_error.clone(),
+        }
+    }
+
+    /// Register a paused step, returns its pause id ("step_id#n") and the notify that wakes it
+    fn pause(&mut self, mut state: WorkflowDebugState) -> (String, Arc<tokio::sync::Notify>) {
+        if self.paused.is_empty() {
+            self.paused_since = Some(std::time::Instant::now());
+        }
+        self.next_pause += 1;
+        let pause_id = format!("{}#{}", state.paused_at.as_deref().unwrap_or_default(), self.next_pause);
+        state.pause_id = pause_id.clone();
+        let wake = Arc::new(tokio::sync::Notify::new());
+        self.paused.insert(pause_id.clone(), PausedStep { state, action: None, edits: vec![], wake: wake.clone() });
+        (pause_id, wake)
+    }
+
+    fn unpause(&mut self, pause_id: &str) {
+        if self.paused.remove(pause_id).is_some() && self.paused.is_empty() {
+            if let Some(since) = self.paused_since.take() {
+                self.paused_total += since.elapsed();
+            }
+        }
+    }
+
+    fn paused_time(&self) -> std::time::Duration {
+        self.paused_total + self.paused_since.map(|since| since.elapsed()).unwrap_or_default()
+    }
+
+    /// Paused steps a command applies to: one pause id, every pause at a step id, or all of them
+    fn targets<'a>(&'a mut self, target: Option<&'a str>) -> impl Iterator<Item = &'a mut PausedStep> + 'a {
+        self.paused.iter_mut()
+            .filter(move |(pause_id, paused)| {
+                target.is_none_or(|t| t == pause_id.as_str() || paused.state.paused_at.as_deref() == Some(t))
+            })
+            .map(|(_, paused)| paused)
+    }
+}
+
+/// Removes a paused step from the session when its checkpoint returns or its branch is dropped
+struct PauseGuard<'a> {
+    run_id: &'a str,
+    pause_id: String,
+}
+
+impl Drop for PauseGuard<'_> {
+    fn drop(&mut self) {
+        let _ = with_debug_session(self.run_id, |debug| debug.unpause(&self.pause_id));
+    }
+}
+
+/// Run a closure on the debug session of a run (Ok(None) if the run is not active or not in debug mode)
+fn with_debug_session<T>(run_id: &str, f: impl FnOnce(&mut DebugSession) -> T) -> Result<Option<T>, String> {
+    let mut runs = ACTIVE_RUNS.lock().map_err(|e| format!("Lock error: {}", e))?;
+    Ok(runs.get_mut(run_id).and_then(|run| run.debug.as_mut()).map(f))
+}
+
+fn send_debug_action(run_id: &str, step_id: Option<&str>, action: DebugAction) -> Result<bool, String> {
+    let sent = with_debug_session(run_id, |debug| {
+        let mut sent = false;
+        for paused in debug.targets(step_id) {
+            paused.action = Some(action);
+            paused.wake.notify_one();
+            sent = true;
+        }
+        sent
+    })?;
+    Ok(sent.unwrap_or(false))
+}
+
+/// Resolves once the run has been busy for `secs` - time paused at a breakpoint does not count
+async fn workflow_deadline(run_id: &str, secs: u64) {
+    let started = std::time::Instant::now();
+    let limit = std::time::Duration::from_secs(secs);
+    loop {
+        let (paused_time, paused) = with_debug_session(run_id, |debug| (debug.paused_time(), !debug.paused.is_empty()))
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let busy = started.elapsed().saturating_sub(paused_time);
+        if busy >= limit && !paused {
+            return;
+        }
+        let wait = if paused { std::time::Duration::from_millis(250) } else { limit - busy };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Pause a debug run before its next step
+#[command]
+pub async fn pause_workflow(run_id: String) -> Result<bool, String> {
+    Ok(with_debug_session(&run_id, |debug| debug.pause_next = true)?.is_some())
+}
+
+/// Continue paused steps (a pause id, a step id, or all of them) until the next breakpoint
+#[command]
+pub async fn continue_workflow(run_id: String, step_id: Option<String>) -> Result<bool, String> {
+    send_debug_action(&run_id, step_id.as_deref(), DebugAction::Continue)
+}
+
+/// Execute the paused step and pause again before the next one
+#[command]
+pub async fn step_workflow(run_id: String, step_id: Option<String>) -> Result<bool, String> {
+    send_debug_action(&run_id, step_id.as_deref(), DebugAction::Step)
+}
+
+/// Skip the paused step and pause again before the next one
+#[command]
+pub async fn skip_workflow_step(run_id: String, step_id: Option<String>) -> Result<bool, String> {
+    send_debug_action(&run_id, step_id.as_deref(), DebugAction::Skip)
+}
+
+/// Set a variable on paused steps (applied before the step executes)
+#[command]
+pub async fn set_workflow_variable(
+    run_id: String,
+    name: String,
+    value: serde_json::Value,
+    step_id: Option<String>,
+) -> Result<bool, String> {
+    let queued = with_debug_session(&run_id, |debug| {
+        let mut queued = false;
+        for paused in debug.targets(step_id.as_deref()) {
+            paused.state.variables.insert(name.clone(), value.clone());
+            paused.edits.push((name.clone(), value.clone()));
+            paused.wake.notify_one();
+            queued = true;
+        }
+        queued
+    })?;
+    Ok(queued.unwrap_or(false))
+}
+
+/// Replace the breakpoint list of a debug run
+#[command]
+pub async fn set_workflow_breakpoints(run_id: String, breakpoints: Vec<String>) -> Result<bool, String> {
+    Ok(with_debug_session(&run_id, |debug| debug.breakpoints = breakpoints.into_iter().collect())?.is_some())
+}
+
+/// Inspect the paused steps of a run: variables, history and last error
+#[command]
+pub async fn get_workflow_debug_state(run_id: String) -> Result<Vec<WorkflowDebugState>, String> {
+    let mut states: Vec<WorkflowDebugState> = with_debug_session(&run_id, |debug| {
+        debug.paused.values().map(|paused| paused.state.clone()).collect()
+    })?.unwrap_or_default();
+    states.sort_by(|a, b| a.paused_at.cmp(&b.paused_at).then_with(|| a.pause_id.cmp(&b.pause_id)));
+    Ok(states)
+}
+
+/// Pause before a step if the run is in debug mode and a breakpoint (or step mode) hits
+/// Returns true when the user chose to skip the step
+async fn debug_checkpoint<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+) -> Result<bool, String> {
+    let run_id = context.run_id.clone();
+    let paused = with_debug_session(&run_id, |debug| {
+        if !debug.pause_next && !debug.breakpoints.contains(&step.id) {
+            return None;
+        }
+        debug.pause_next = false;
+        let (pause_id, wake) = debug.pause(debug.snapshot(&run_id, &step.id, context));
+        let state = debug.paused[&pause_id].state.clone();
+        Some((pause_id, wake, state))
+    })?.flatten();
+    let Some((pause_id, wake, mut state)) = paused else { return Ok(false) };
+    let _pause = PauseGuard { run_id: &run_id, pause_id: pause_id.clone() };
+    
+    println!("[WORKFLOW] Debugger paused before step {}", step.id);
+    loop {
+        let _ = window.emit("workflow-step", serde_json::json!({
+            "run_id": run_id,
+            "step_id": step.id,
+            "pause_id": pause_id,
+            "status": "paused",
+            "debug": state,
+        }));
+        
+        wake.notified().await;
+        
+        let (action, edits) = with_debug_session(&run_id, |debug| {
+            debug.paused.get_mut(&pause_id)
+                .map(|paused| (paused.action.take(), std::mem::take(&mut paused.edits)))
+                .unwrap_or_default()
+        })?.unwrap_or_default();
+        for (name, value) in edits {
+            context.variables.insert(name, value);
+        }
+        if is_run_cancelled(&run_id) {
+            return Err("Workflow cancelled".to_string());
+        }
+        
+        if let Some(action) = action {
+            with_debug_session(&run_id, |debug| debug.pause_next = action != DebugAction::Continue)?;
+            let _ = window.emit("workflow-step", serde_json::json!({
+                "run_id": run_id,
+                "step_id": step.id,
+                "pause_id": pause_id,
+                "status": if action == DebugAction::Skip { "skipped" } else { "resumed" },
+            }));
+            return Ok(action == DebugAction::Skip);
+        }
+        
+        // Woken by a variable edit - report the updated context and keep waiting
+        if let Some(updated) = with_debug_session(&run_id, |debug| debug.paused.get(&pause_id).map(|p| p.state.clone()))?.flatten() {
+            state = updated;
+        }
+    }
+}
+
 // ============================================
 // Calibration Mode - LLM Vision analysis
 // ============================================
@@ -498,24 +1016,7 @@ pub async fn record_workflow(
                         action_type.to_lowercase()
                     }),
                     params: Some(params.into_iter().collect()),
-                    duration: None,
-                    delay_after: None,
-                    prompt: None,
-                    script: None,
-                    save_to: None,
-                    count: None,
-                    variable: None,
-                    body: None,
-                    condition: None,
-                    then: None,
-                    else_branch: None,
-                    max_iterations: None,
-                    branches: None,
-                    wait_condition: None,
-                    selector: None,
-                    skill_id: None,
-                    on_error: None,
-                    ai_prompt: None,
+                    ..Default::default()
                 };
                 steps.push(step);
                 
@@ -525,25 +1026,7 @@ pub async fn record_workflow(
                     step_type: "wait".to_string(),
                     name: Some("Wait".to_string()),
                     duration: Some("500".to_string()),
-                    delay_after: None,
-                    action: None,
-                    params: None,
-                    prompt: None,
-                    script: None,
-                    save_to: None,
-                    count: None,
-                    variable: None,
-                    body: None,
-                    condition: None,
-                    then: None,
-                    else_branch: None,
-                    max_iterations: None,
-                    branches: None,
-                    wait_condition: None,
-                    selector: None,
-                    skill_id: None,
-                    on_error: None,
-                    ai_prompt: None,
+                    ..Default::default()
                 });
             }
             
@@ -1103,8 +1586,8 @@ pub async fn calibrate_workflow(
 /// Run a Python script with context/inputs
 /// The script can access: device, ai, context, inputs
 #[command]
-pub async fn run_python_script(
-    window: tauri::Window,
+pub async fn run_python_script<R: tauri::Runtime>(
+    window: tauri::Window<R>,
     script: String,
     inputs: HashMap<String, serde_json::Value>,
     context: HashMap<String, serde_json::Value>,
@@ -1459,6 +1942,126 @@ Be concise and actionable."#;
     Ok(analysis)
 }
 
+// ============================================
+// Checkpoints - resume failed runs
+// ============================================
+
+/// Progress of a run, saved after each top-level step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowCheckpoint {
+    pub run_id: String,
+    pub workflow: WorkflowDefinition,
+    pub device_id: String,
+    pub inputs: HashMap<String, serde_json::Value>,
+    /// Index of the next top-level step to run
+    pub step_index: usize,
+    pub step_id: Option<String>,
+    pub variables: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub loop_counters: HashMap<String, i32>,
+    #[serde(default)]
+    pub history: Vec<ActionRecord>,
+    /// "running", "failed", "timeout" or "cancelled"
+    pub status: String,
+    pub error: Option<String>,
+    pub updated_at: String,
+}
+
+/// Checkpoints of stopped runs are kept this long for resume_workflow
+const CHECKPOINT_MAX_AGE_DAYS: u64 = 7;
+const MAX_CHECKPOINTS: usize = 100;
+
+fn get_checkpoints_dir() -> std::path::PathBuf {
+    crate::utils::app_data_dir().join("checkpoints")
+}
+
+fn checkpoint_path(run_id: &str) -> Result<std::path::PathBuf, String> {
+    crate::utils::check_file_id("run", run_id)?;
+    Ok(get_checkpoints_dir().join(format!("{}.json", run_id)))
+}
+
+/// Persist run progress (errors are logged, a failed write never stops the run)
+fn save_checkpoint(workflow: &WorkflowDefinition, context: &WorkflowContext, step_index: usize, status: &str, error: Option<&str>) {
+    let checkpoint = WorkflowCheckpoint {
+        run_id: context.run_id.clone(),
+        workflow: workflow.clone(),
+        device_id: context.device_id.clone(),
+        inputs: context.inputs.clone(),
+        step_index,
+        step_id: workflow.steps.get(step_index).map(|s| s.id.clone()),
+        variables: context.variables.clone(),
+        loop_counters: context.loop_counters.clone(),
+        history: context.history.clone(),
+        status: status.to_string(),
+        error: error.map(|e| e.to_string()),
+        updated_at: chrono::Utc::now().to_rfc3339(),
+    };
+    
+    let result = checkpoint_path(&context.run_id).and_then(|path| {
+        std::fs::create_dir_all(get_checkpoints_dir()).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(&checkpoint).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        println!("[WORKFLOW] Cannot save checkpoint for run {}: {}", context.run_id, e);
+    }
+}
+
+fn load_checkpoint(run_id: &str) -> Result<WorkflowCheckpoint, String> {
+    let path = checkpoint_path(run_id)?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|_| format!("No checkpoint for run {}", run_id))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid checkpoint {}: {}", path.display(), e))
+}
+
+fn delete_checkpoint_file(run_id: &str) {
+    if let Ok(path) = checkpoint_path(run_id) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Drop checkpoints older than CHECKPOINT_MAX_AGE_DAYS, then all but the newest MAX_CHECKPOINTS
+fn prune_checkpoints(dir: &std::path::Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut files: Vec<(std::time::SystemTime, std::path::PathBuf)> = entries.flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+            (modified, entry.path())
+        })
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    
+    let max_age = std::time::Duration::from_secs(CHECKPOINT_MAX_AGE_DAYS * 24 * 3600);
+    for (index, (modified, path)) in files.iter().enumerate() {
+        let expired = modified.elapsed().map(|age| age > max_age).unwrap_or(false);
+        if expired || index >= MAX_CHECKPOINTS {
+            println!("[WORKFLOW] Removing old checkpoint {}", path.display());
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// List saved checkpoints, newest first
+#[command]
+pub async fn list_workflow_checkpoints() -> Result<Vec<WorkflowCheckpoint>, String> {
+    let Ok(entries) = std::fs::read_dir(get_checkpoints_dir()) else { return Ok(vec![]) };
+    let mut checkpoints: Vec<WorkflowCheckpoint> = entries.flatten()
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    checkpoints.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(checkpoints)
+}
+
+/// Delete a saved checkpoint
+#[command]
+pub async fn delete_workflow_checkpoint(run_id: String) -> Result<(), String> {
+    let path = checkpoint_path(&run_id)?;
+    std::fs::remove_file(&path).map_err(|e| format!("Cannot delete checkpoint: {}", e))
+}
+
 // ============================================
 // Workflow Execution
 // ============================================
@@ -1470,12 +2073,40 @@ pub async fn run_workflow(
     workflow: WorkflowDefinition,
     inputs: HashMap<String, serde_json::Value>,
     device_id: String,
+    run_id: Option<String>,
+    debug: Option<WorkflowDebugOptions>,
 ) -> Result<WorkflowResult, String> {
-    println!("[WORKFLOW] Starting workflow: {} on device: {}", workflow.name, device_id);
-    let start_time = std::time::Instant::now();
+    // Run ids name checkpoint and history files
+    if let Some(run_id) = &run_id {
+        crate::utils::check_file_id("run", run_id)?;
+    }
+    let run_id = run_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    println!("[WORKFLOW] Starting workflow: {} on device: {} (run {})", workflow.name, device_id, run_id);
+    
+    // Reject bad inputs before anything touches the device
+    let prepared = crate::workflow_inputs::prepare_inputs(&workflow.inputs, inputs.clone());
+    if !prepared.valid {
+        let error = prepared.error_summary();
+        println!("[WORKFLOW] Invalid inputs for {}: {}", workflow.name, error);
+        let result = WorkflowResult {
+            success: false,
+            run_id,
+            status: "invalid_inputs".to_string(),
+            workflow_id: workflow.id.clone(),
+            outputs: HashMap::new(),
+            logs: vec![],
+            duration_ms: 0,
+            error: Some(format!("Invalid inputs: {}", error)),
+            timed_out_step: None,
+            input_errors: prepared.errors,
+        };
+        let now = chrono::Utc::now().to_rfc3339();
+        crate::run_history::record_workflow_run(&workflow, &inputs, &device_id, &now, &result, vec![], None).await;
+        return Ok(result);
+    }
     
-    let mut context = WorkflowContext {
-        inputs: inputs.clone(),
+    let context = WorkflowContext {
+        inputs: prepared.inputs,
         variables: HashMap::new(),
         device_id: device_id.clone(),
         current_step_id: None,
@@ -1483,124 +2114,247 @@ pub async fn run_workflow(
         history: vec![],
         plan: workflow.description.clone(),
         last_error: None,
+        call_stack: vec![workflow.id.clone()],
+        timed_out_step: None,
+        run_id: run_id.clone(),
+        loop_counters: HashMap::new(),
+        failed_step_id: None,
+        in_finally: false,
+        handled_error: None,
     };
     
-    // Add log helper
-    fn add_log(context: &mut WorkflowContext, level: &str, step_id: Option<&str>, message: &str) {
-        context.logs.push(WorkflowLog {
-            timestamp: chrono::Utc::now().to_rfc3339(),
-            level: level.to_string(),
-            step_id: step_id.map(|s| s.to_string()),
-            message: message.to_string(),
-        });
-    }
+    execute_workflow_run(window, workflow, context, 0, false, debug).await
+}
+
+/// Resume a failed, cancelled or timed-out run from its checkpoint
+/// `from_step` (step id) defaults to the step that was running when the run stopped
+#[command]
+pub async fn resume_workflow(
+    window: tauri::Window,
+    run_id: String,
+    from_step: Option<String>,
+    debug: Option<WorkflowDebugOptions>,
+) -> Result<WorkflowResult, String> {
+    resume_run(window, run_id, from_step, debug).await
+}
+
+async fn resume_run<R: tauri::Runtime>(
+    window: tauri::Window<R>,
+    run_id: String,
+    from_step: Option<String>,
+    debug: Option<WorkflowDebugOptions>,
+) -> Result<WorkflowResult, String> {
+    let checkpoint = load_checkpoint(&run_id)?;
+    let start_index = match &from_step {
+        Some(step_id) => checkpoint.workflow.steps.iter().position(|s| &s.id == step_id)
+            .ok_or_else(|| format!("Step '{}' not found in workflow '{}'", step_id, checkpoint.workflow.id))?,
+        None => checkpoint.step_index,
+    };
+    println!("[WORKFLOW] Resuming run {} at step index {}", run_id, start_index);
+    
+    let (workflow, context) = resume_context(checkpoint, start_index);
+    execute_workflow_run(window, workflow, context, start_index, true, debug).await
+}
+
+/// Rebuild the run context saved in a checkpoint
+fn resume_context(checkpoint: WorkflowCheckpoint, start_index: usize) -> (WorkflowDefinition, WorkflowContext) {
+    let context = WorkflowContext {
+        inputs: checkpoint.inputs,
+        variables: checkpoint.variables,
+        device_id: checkpoint.device_id,
+        current_step_id: None,
+        logs: vec![],
+        history: checkpoint.history,
+        plan: checkpoint.workflow.description.clone(),
+        last_error: None,
+        call_stack: vec![checkpoint.workflow.id.clone()],
+        timed_out_step: None,
+        run_id: checkpoint.run_id,
+        // Loop progress only applies when resuming the step that was interrupted
+        loop_counters: if start_index == checkpoint.step_index { checkpoint.loop_counters } else { HashMap::new() },
+        failed_step_id: None,
+        in_finally: false,
+        handled_error: None,
+    };
+    (checkpoint.workflow, context)
+}
+
+/// Execute top-level steps from `start_index`, registering the run and saving checkpoints
+/// A `resumed` run is recorded in the history as a new attempt of the same run id
+pub(crate) async fn execute_workflow_run<R: tauri::Runtime>(
+    window: tauri::Window<R>,
+    workflow: WorkflowDefinition,
+    mut context: WorkflowContext,
+    start_index: usize,
+    resumed: bool,
+    debug: Option<WorkflowDebugOptions>,
+) -> Result<WorkflowResult, String> {
+    let run_id = context.run_id.clone();
+    let device_id = context.device_id.clone();
+    let start_time = std::time::Instant::now();
+    let started_at = chrono::Utc::now().to_rfc3339();
     
+    let (_run_guard, cancel_now) = register_run(WorkflowRunInfo {
+        run_id: run_id.clone(),
+        workflow_id: workflow.id.clone(),
+        workflow_name: workflow.name.clone(),
+        device_id: device_id.clone(),
+        started_at: started_at.clone(),
+        current_step_id: None,
+        cancel_requested: None,
+    }, debug)?;
+    
+    // Add log helper
     add_log(&mut context, "info", None, &format!("🚀 Starting workflow: {}", workflow.name));
+    if start_index > 0 {
+        let step_id = workflow.steps.get(start_index).map(|s| s.id.as_str()).unwrap_or("end");
+        add_log(&mut context, "info", None, &format!("⏩ Resuming from step {} ({})", start_index + 1, step_id));
+    }
     
     // Emit start event
     let _ = window.emit("workflow-start", serde_json::json!({
+        "run_id": run_id,
         "workflow_id": workflow.id,
         "workflow_name": workflow.name,
         "device_id": device_id,
     }));
     
-    // Execute steps
+    // Execute steps - the whole run is bounded by workflow.timeout (seconds)
     let mut error: Option<String> = None;
-    for step in &workflow.steps {
-        context.current_step_id = Some(step.id.clone());
-        
-        add_log(&mut context, "info", Some(&step.id), 
-            &format!("▶️ Step: {}", step.name.as_deref().unwrap_or(&step.step_type)));
-        
-        // Emit step start event
-        let _ = window.emit("workflow-step", serde_json::json!({
-            "step_id": step.id,
-            "step_type": step.step_type,
-            "step_name": step.name,
-            "status": "running",
-        }));
-        
-        match execute_step(&window, step, &mut context).await {
-            Ok(()) => {
-                add_log(&mut context, "success", Some(&step.id), "✓ Step completed");
-                let _ = window.emit("workflow-step", serde_json::json!({
-                    "step_id": step.id,
-                    "status": "completed",
-                }));
-                
-                // ✅ CRITICAL: Add delay after action steps to ensure device responsiveness
-                if step.step_type == "action" {
-                    // Priority: step.delay_after > workflow.step_delay > default 800ms
-                    let global_delay = workflow.step_delay.unwrap_or(800);
-                    let base_delay = step.delay_after.unwrap_or(global_delay);
-                    
-                    // Add ±15% variance for more human-like timing
-                    use rand::Rng;
-                    let variance = (base_delay as f64 * 0.15) as u64;
-                    let min_delay = base_delay.saturating_sub(variance);
-                    let max_delay = base_delay + variance;
-                    let actual_delay = rand::thread_rng().gen_range(min_delay..=max_delay);
-                    
-                    println!("[WORKFLOW] Delay {}ms (base: {}ms ±15%)", actual_delay, base_delay);
-                    tokio::time::sleep(tokio::time::Duration::from_millis(actual_delay)).await;
-                }
+    let mut cancelled = false;
+    let mut step_index = start_index;
+    let execution = async {
+        for (index, step) in workflow.steps.iter().enumerate().skip(start_index) {
+            step_index = index;
+            // Graceful cancel stops at the step boundary
+            if is_run_cancelled(&run_id) {
+                cancelled = true;
+                break;
             }
-            Err(e) => {
-                add_log(&mut context, "error", Some(&step.id), &format!("✗ Error: {}", e));
-                let _ = window.emit("workflow-step", serde_json::json!({
-                    "step_id": step.id,
-                    "status": "failed",
-                    "error": e.clone(),
-                }));
-                
-                // Handle error based on strategy
-                let should_abort = match &step.on_error {
-                    Some(config) => match config.strategy.as_str() {
-                        "skip" => {
-                            add_log(&mut context, "warning", Some(&step.id), "Skipping failed step");
-                            false
-                        }
-                        "retry" => {
-                            let retries = config.retries.unwrap_or(3);
-                            let mut retry_success = false;
-                            for retry in 1..=retries {
-                                add_log(&mut context, "info", Some(&step.id), 
-                                    &format!("Retry {}/{}", retry, retries));
-                                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                                if execute_step(&window, step, &mut context).await.is_ok() {
-                                    retry_success = true;
-                                    break;
-                                }
-                            }
-                            !retry_success
+            context.current_step_id = Some(step.id.clone());
+            context.timed_out_step = None;
+            set_run_step(&run_id, &step.id);
+            
+            add_log(&mut context, "info", Some(&step.id), 
+                &format!("▶️ Step: {}", step.name.as_deref().unwrap_or(&step.step_type)));
+            
+            // Emit step start event
+            let _ = window.emit("workflow-step", serde_json::json!({
+                "step_id": step.id,
+                "step_type": step.step_type,
+                "step_name": step.name,
+                "status": "running",
+            }));
+            
+            context.handled_error = None;
+            match execute_step(&window, step, &mut context).await.and_then(StepOutcome::outside_loop) {
+                Ok(()) => {
+                    match context.handled_error.take().filter(|handled| handled.step_id == step.id) {
+                        Some(handled) => {
+                            // The step failed but its on_error strategy kept the run going
+                            add_log(&mut context, "warning", Some(&step.id),
+                                &format!("⚠ Step {} after error: {}", handled.status, handled.error));
+                            let _ = window.emit("workflow-step", serde_json::json!({
+                                "step_id": step.id,
+                                "status": handled.status,
+                                "error": handled.error,
+                            }));
                         }
-                        "fallback" => {
-                            if let Some(fallback_steps) = &config.fallback {
-                                add_log(&mut context, "info", Some(&step.id), "Running fallback steps");
-                                for fb_step in fallback_steps {
-                                    if let Err(fb_err) = execute_step(&window, fb_step, &mut context).await {
-                                        add_log(&mut context, "error", Some(&fb_step.id), 
-                                            &format!("Fallback failed: {}", fb_err));
-                                    }
-                                }
-                            }
-                            false
+                        None => {
+                            add_log(&mut context, "success", Some(&step.id), "✓ Step completed");
+                            let _ = window.emit("workflow-step", serde_json::json!({
+                                "step_id": step.id,
+                                "status": "completed",
+                            }));
+                            
+                            // ✅ CRITICAL: Add delay after action steps to ensure device responsiveness
+                            delay_after_action(step, workflow.step_delay).await;
                         }
-                        _ => true, // "abort" or unknown
                     }
-                    None => true, // Default: abort on error
-                };
-                
-                if should_abort {
+                    
+                    context.loop_counters.clear();
+                    save_checkpoint(&workflow, &context, index + 1, "running", None);
+                }
+                Err(e) => {
+                    add_log(&mut context, "error", Some(&step.id), &format!("✗ Error: {}", e));
+                    let _ = window.emit("workflow-step", serde_json::json!({
+                        "step_id": step.id,
+                        "status": "failed",
+                        "error": e.clone(),
+                    }));
+                    
+                    // on_error strategies (skip/retry/fallback) already ran inside execute_step
                     error = Some(e);
                     break;
                 }
             }
         }
+    };
+    
+    let workflow_timeout = workflow.timeout.filter(|t| *t > 0).map(|t| t as u64);
+    let deadline = async {
+        match workflow_timeout {
+            Some(secs) => workflow_deadline(&run_id, secs).await,
+            None => std::future::pending::<()>().await,
+        }
+    };
+    
+    // Timeout and immediate cancel drop the in-flight step, which kills its child processes
+    let (timed_out, cancelled_now) = tokio::select! {
+        _ = execution => (false, false),
+        _ = deadline => (true, false),
+        _ = cancel_now.notified() => (false, true),
+    };
+    
+    if timed_out {
+        // The in-flight step future was dropped, which kills its child processes
+        let step_id = context.current_step_id.clone().unwrap_or_default();
+        let message = format!("Workflow timed out after {}s during step '{}'", workflow_timeout.unwrap_or_default(), step_id);
+        add_log(&mut context, "error", Some(&step_id), &format!("⏱ {}", message));
+        let _ = window.emit("workflow-step", serde_json::json!({
+            "step_id": step_id,
+            "status": "failed",
+            "error": message,
+        }));
+        context.timed_out_step = Some(step_id);
+        error = Some(message);
+    }
+    
+    // Steps interrupted by a graceful cancel fail with "Workflow cancelled"
+    if cancelled_now || (error.is_some() && is_run_cancelled(&run_id)) {
+        cancelled = true;
+    }
+    if cancelled {
+        let step_id = context.current_step_id.clone();
+        add_log(&mut context, "warning", step_id.as_deref(), "⏹ Workflow cancelled");
+        if cancelled_now {
+            let _ = window.emit("workflow-step", serde_json::json!({
+                "step_id": step_id,
+                "status": "cancelled",
+            }));
+        }
+        error = Some("Workflow cancelled".to_string());
     }
     
     let duration_ms = start_time.elapsed().as_millis() as i64;
     let success = error.is_none();
+    let status = if cancelled {
+        "cancelled"
+    } else if timed_out || (error.is_some() && context.timed_out_step.is_some()) {
+        "timeout"
+    } else if success {
+        "completed"
+    } else {
+        "failed"
+    };
+    
+    // Keep a checkpoint to resume from unless the run completed
+    if success {
+        delete_checkpoint_file(&run_id);
+    } else {
+        save_checkpoint(&workflow, &context, step_index, status, error.as_deref());
+    }
+    prune_checkpoints(&get_checkpoints_dir());
     
     // Build outputs from context
     let mut outputs = HashMap::new();
@@ -1613,102 +2367,420 @@ pub async fn run_workflow(
     if success {
         add_log(&mut context, "success", None, &format!("✅ Workflow completed in {}ms", duration_ms));
     } else {
-        add_log(&mut context, "error", None, &format!("❌ Workflow failed: {:?}", error));
+        add_log(&mut context, "error", None, &format!("❌ Workflow failed: {}", error.as_deref().unwrap_or_default()));
     }
     
     // Emit complete event
     let _ = window.emit("workflow-complete", serde_json::json!({
+        "run_id": run_id,
         "workflow_id": workflow.id,
+        "status": status,
         "success": success,
         "duration_ms": duration_ms,
         "error": error,
+        "timed_out_step": context.timed_out_step,
     }));
     
-    Ok(WorkflowResult {
+    let result = WorkflowResult {
         success,
-        workflow_id: workflow.id,
+        run_id,
+        status: status.to_string(),
+        workflow_id: workflow.id.clone(),
         outputs,
         logs: context.logs,
         duration_ms,
         error,
-    })
+        timed_out_step: context.timed_out_step,
+        input_errors: vec![],
+    };
+    
+    // Keep a device screenshot of failures for the run history
+    let screenshots = if matches!(status, "failed" | "timeout") {
+        crate::run_history::capture_failure_screenshot(&device_id, &result.run_id).await
+    } else {
+        vec![]
+    };
+    crate::run_history::record_workflow_run(&workflow, &context.inputs, &device_id, &started_at, &result, screenshots, resumed.then_some(result.run_id.as_str())).await;
+    
+    Ok(result)
+}
+
+/// Delay after action steps so the device can settle
+/// Priority: step.delay_after > workflow.step_delay > default 800ms
+async fn delay_after_action(step: &WorkflowStep, step_delay: Option<u64>) {
+    if step.step_type != "action" {
+        return;
+    }
+    let base_delay = step.delay_after.unwrap_or(step_delay.unwrap_or(800));
+    
+    // Add ±15% variance for more human-like timing
+    use rand::Rng;
+    let variance = (base_delay as f64 * 0.15) as u64;
+    let min_delay = base_delay.saturating_sub(variance);
+    let max_delay = base_delay + variance;
+    let actual_delay = rand::thread_rng().gen_range(min_delay..=max_delay);
+    
+    println!("[WORKFLOW] Delay {}ms (base: {}ms ±15%)", actual_delay, base_delay);
+    tokio::time::sleep(tokio::time::Duration::from_millis(actual_delay)).await;
 }
 
 /// Execute a single workflow step (uses BoxFuture for recursion)
-fn execute_step<'a>(
-    window: &'a tauri::Window,
+fn execute_step<'a, R: tauri::Runtime>(
+    window: &'a tauri::Window<R>,
     step: &'a WorkflowStep,
     context: &'a mut WorkflowContext,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send + 'a>> {
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<StepOutcome, String>> + Send + 'a>> {
     Box::pin(async move {
-        match step.step_type.as_str() {
+        // Nested steps also stop at the next boundary once the run is cancelled (cleanup still runs)
+        if !context.run_id.is_empty() && !context.in_finally && is_run_cancelled(&context.run_id) {
+            return Err("Workflow cancelled".to_string());
+        }
+        if !context.run_id.is_empty() && debug_checkpoint(window, step, context).await? {
+            println!("[WORKFLOW] Debugger skipped step {}", step.id);
+            return Ok(StepOutcome::Done);
+        }
+        warn_unknown_variables(window, step, context);
+        
+        let result = execute_step_attempt(window, step, context).await;
+        let cancelled = !context.run_id.is_empty() && is_run_cancelled(&context.run_id);
+        let result = match (result, &step.on_error) {
+            // A cancelled run aborts instead of retrying or falling back
+            (Err(e), Some(config)) if !cancelled => {
+                let handled = handle_step_error(window, step, context, config, e.clone()).await;
+                if handled.is_ok() {
+                    // A handled timeout no longer describes how the run ended
+                    context.timed_out_step = None;
+                    context.failed_step_id = None;
+                    clear_loop_progress(step, &mut context.loop_counters);
+                    let status = match config.strategy.as_str() {
+                        "skip" => Some("skipped"),
+                        "fallback" => Some("recovered"),
+                        _ => None, // a successful retry is a normal completion
+                    };
+                    context.handled_error = status.map(|status| HandledError {
+                        step_id: step.id.clone(),
+                        status: status.to_string(),
+                        error: e,
+                    });
+                }
+                handled
+            }
+            (result, _) => result,
+        };
+        if result.is_err() && context.failed_step_id.is_none() {
+            context.failed_step_id = Some(step.id.clone());
+        }
+        result
+    })
+}
+
+/// Run a step once: dispatch on its type, bounded by its own timeout
+async fn execute_step_attempt<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+) -> Result<StepOutcome, String> {
+    let run = async {
+        let result = match step.step_type.as_str() {
+            // Steps that can pass a break / continue on to the enclosing loop
+            "condition" => return execute_condition_step(window, step, context).await,
+            "try" => return execute_try_step(window, step, context).await,
+            "break" | "continue" => return execute_loop_control_step(step, context),
             "action" => execute_action_step(window, step, context).await,
-            "condition" => execute_condition_step(window, step, context).await,
             "loop" => execute_loop_step(window, step, context).await,
+            "foreach" => execute_foreach_step(window, step, context).await,
             "while" => execute_while_step(window, step, context).await,
             "parallel" => execute_parallel_step(window, step, context).await,
             "python" => execute_python_step(window, step, context).await,
             "scripter" => execute_scripter_step(window, step, context).await,
             "prompt" => execute_prompt_step(window, step, context).await,
             "wait" => execute_wait_step(step, context).await,
+            "wait_for" => execute_wait_for_step(window, step, context).await,
             "extract" => execute_extract_step(step, context).await,
             "skill" => execute_skill_step(window, step, context).await,
+            "call" => execute_call_step(window, step, context).await,
+            "set" => execute_set_step(step, context),
             _ => Err(format!("Unknown step type: {}", step.step_type)),
+        };
+        result.map(|()| StepOutcome::Done)
+    };
+    
+    match step.timeout {
+        Some(secs) if secs > 0 => match tokio::time::timeout(tokio::time::Duration::from_secs(secs), run).await {
+            Ok(result) => result,
+            Err(_) => {
+                // Dropping the step future kills its child processes (kill_on_drop)
+                context.timed_out_step = Some(step.id.clone());
+                Err(format!("Step '{}' timed out after {}s", step.id, secs))
+            }
+        },
+        _ => run.await,
+    }
+}
+
+/// Apply the step's on_error strategy - Ok means the failure was handled and the run goes on
+async fn handle_step_error<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+    config: &ErrorConfig,
+    error: String,
+) -> Result<StepOutcome, String> {
+    match config.strategy.as_str() {
+        "skip" => {
+            add_log(context, "warning", Some(&step.id), &format!("Skipping failed step: {}", error));
+            Ok(StepOutcome::Done)
         }
-    })
+        "retry" => retry_step(window, step, context, config, error).await,
+        "fallback" => {
+            if let Some(fallback_steps) = &config.fallback {
+                add_log(context, "info", Some(&step.id), &format!("Running fallback steps after error: {}", error));
+                for fb_step in fallback_steps {
+                    if let Err(fb_err) = execute_step(window, fb_step, context).await.and_then(StepOutcome::outside_loop) {
+                        add_log(context, "error", Some(&fb_step.id), &format!("Fallback failed: {}", fb_err));
+                    }
+                }
+            }
+            Ok(StepOutcome::Done)
+        }
+        _ => Err(error), // "abort" or unknown
+    }
+}
+
+/// Retry a failed step with backoff, running the recovery steps before each attempt
+async fn retry_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+    config: &ErrorConfig,
+    error: String,
+) -> Result<StepOutcome, String> {
+    let retries = config.retries.unwrap_or(3).max(0);
+    let retry_on = match config.retry_on.as_deref().map(regex::Regex::new) {
+        Some(Ok(pattern)) => Some(pattern),
+        Some(Err(e)) => return Err(format!("{} (retryOn pattern is invalid: {})", error, e)),
+        None => None,
+    };
+    
+    let mut error = error;
+    for attempt in 1..=retries {
+        if let Some(pattern) = &retry_on {
+            if !pattern.is_match(&error) {
+                add_log(context, "info", Some(&step.id), "Error does not match retryOn, not retrying");
+                return Err(error);
+            }
+        }
+        if !context.run_id.is_empty() && is_run_cancelled(&context.run_id) {
+            return Err("Workflow cancelled".to_string());
+        }
+        
+        let delay = retry_delay_ms(config, attempt);
+        add_log(context, "info", Some(&step.id), &format!("Retry {}/{} in {}ms", attempt, retries, delay));
+        let _ = window.emit("workflow-step", serde_json::json!({
+            "step_id": step.id,
+            "status": "retrying",
+            "attempt": attempt,
+            "retries": retries,
+            "delay_ms": delay,
+            "error": error,
+        }));
+        tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
+        
+        for recovery_step in config.recovery.iter().flatten() {
+            if let Err(e) = execute_step(window, recovery_step, context).await.and_then(StepOutcome::outside_loop) {
+                add_log(context, "warning", Some(&recovery_step.id), &format!("Recovery step failed: {}", e));
+            }
+        }
+        if let Some(last_error) = context.last_error.as_mut().filter(|e| e.step_id == step.id) {
+            last_error.retry_count = attempt;
+        }
+        
+        // A retry starts its loops over - progress is only kept to resume a stopped run
+        clear_loop_progress(step, &mut context.loop_counters);
+        match execute_step_attempt(window, step, context).await {
+            Ok(outcome) => {
+                add_log(context, "success", Some(&step.id), &format!("Retry {}/{} succeeded", attempt, retries));
+                return Ok(outcome);
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// Forget the loop progress of a step and the steps nested in it
+fn clear_loop_progress(step: &WorkflowStep, loop_counters: &mut HashMap<String, i32>) {
+    loop_counters.remove(&step.id);
+    let nested = [&step.then, &step.else_branch, &step.body, &step.catch, &step.finally]
+        .into_iter()
+        .flatten()
+        .chain(step.branches.iter().flatten())
+        .flatten();
+    for child in nested {
+        clear_loop_progress(child, loop_counters);
+    }
+}
+
+/// Delay before retry `attempt` (1-based): exponential backoff capped at max_delay, with jitter
+fn retry_delay_ms(config: &ErrorConfig, attempt: i32) -> u64 {
+    use rand::Rng;
+    let max_delay = config.max_delay.unwrap_or(60_000) as f64;
+    let backoff = config.backoff.unwrap_or(1.0).max(1.0);
+    let delay = (config.retry_delay.unwrap_or(1000) as f64 * backoff.powi(attempt - 1)).min(max_delay);
+    
+    let jitter = config.jitter.unwrap_or(0.0).clamp(0.0, 1.0);
+    let spread = if jitter > 0.0 { rand::thread_rng().gen_range(-jitter..=jitter) } else { 0.0 };
+    (delay * (1.0 + spread)).clamp(0.0, max_delay) as u64
+}
+
+/// How a step finished - `break` / `continue` unwind through nested steps to the nearest loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepOutcome {
+    Done,
+    Break,
+    Continue,
+}
+
+impl StepOutcome {
+    /// A signal that escapes every loop (or a parallel branch / sub-workflow) becomes a real error
+    fn outside_loop(self) -> Result<(), String> {
+        match self {
+            StepOutcome::Done => Ok(()),
+            StepOutcome::Break => Err("'break' step used outside of a loop".to_string()),
+            StepOutcome::Continue => Err("'continue' step used outside of a loop".to_string()),
+        }
+    }
+}
+
+fn add_log(context: &mut WorkflowContext, level: &str, step_id: Option<&str>, message: &str) {
+    context.logs.push(WorkflowLog {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        level: level.to_string(),
+        step_id: step_id.map(|s| s.to_string()),
+        message: message.to_string(),
+    });
 }
 
 /// Compile template value - replace {{variable}} with actual values
+/// Supports deep paths, filters and helpers (see template.rs); unresolved placeholders stay as text
 fn compile_value(template: &str, context: &WorkflowContext) -> String {
-    let mut result = template.to_string();
-    
-    // Replace {{input_name}} with input values
-    for (key, value) in &context.inputs {
-        let placeholder = format!("{{{{{}}}}}", key);
-        let value_str = match value {
-            serde_json::Value::String(s) => s.clone(),
-            serde_json::Value::Number(n) => n.to_string(),
-            serde_json::Value::Bool(b) => b.to_string(),
-            _ => value.to_string(),
-        };
-        result = result.replace(&placeholder, &value_str);
-    }
-    
-    // Replace {{variable_name}} and {{variable.key}} with context values
-    for (key, value) in &context.variables {
-        let placeholder = format!("{{{{{}}}}}", key);
-        let value_str = match value {
-            serde_json::Value::String(s) => s.clone(),
-            serde_json::Value::Number(n) => n.to_string(),
-            serde_json::Value::Bool(b) => b.to_string(),
-            serde_json::Value::Object(obj) => {
-                // Also handle nested access like {{result.key}}
-                for (sub_key, sub_value) in obj {
-                    let nested_placeholder = format!("{{{{{}.{}}}}}", key, sub_key);
-                    let sub_value_str = match sub_value {
-                        serde_json::Value::String(s) => s.clone(),
-                        serde_json::Value::Number(n) => n.to_string(),
-                        serde_json::Value::Bool(b) => b.to_string(),
-                        _ => sub_value.to_string(),
-                    };
-                    result = result.replace(&nested_placeholder, &sub_value_str);
-                }
-                serde_json::to_string(value).unwrap_or_default()
-            }
-            _ => value.to_string(),
-        };
-        result = result.replace(&placeholder, &value_str);
+    let (text, warnings) = crate::template::render(template, &|name| lookup_variable(name, context));
+    for warning in warnings {
+        println!("[WORKFLOW] ⚠️ Template {}", warning);
+    }
+    text
+}
+
+/// Text fields of a step that may contain `{{...}}` placeholders (scripts are left out, Python uses braces)
+pub(crate) fn step_template_texts(step: &WorkflowStep) -> Vec<&str> {
+    let mut texts: Vec<&str> = [
+        &step.count, &step.items, &step.duration, &step.prompt, &step.ai_prompt, &step.selector,
+        &step.workflow_id, &step.activity,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|s| s.as_str())
+    .collect();
+    texts.extend(step.params.iter().flatten().filter_map(|(_, v)| v.as_str()));
+    for operation in step.set.iter().flatten() {
+        texts.extend([&operation.value, &operation.increment, &operation.append].into_iter().flatten().filter_map(|v| v.as_str()));
+    }
+    texts
+}
+
+/// Log a warning (once per step) for placeholders whose variable is not defined yet
+fn warn_unknown_variables<R: tauri::Runtime>(window: &tauri::Window<R>, step: &WorkflowStep, context: &mut WorkflowContext) {
+    let unknown: Vec<String> = step_template_texts(step)
+        .into_iter()
+        .flat_map(crate::template::template_variables)
+        .filter(|name| lookup_variable(name, context).is_none())
+        .collect();
+    if unknown.is_empty() {
+        return;
     }
     
-    result
+    let message = format!("⚠️ Unknown variable(s) in step '{}': {}", step.id, unknown.join(", "));
+    if context.logs.iter().any(|log| log.message == message) {
+        return;
+    }
+    println!("[WORKFLOW] {}", message);
+    let _ = window.emit("workflow-step", serde_json::json!({
+        "step_id": step.id,
+        "step_type": step.step_type,
+        "status": "warning",
+        "message": message,
+    }));
+    context.logs.push(WorkflowLog {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        level: "warning".to_string(),
+        step_id: Some(step.id.clone()),
+        message,
+    });
+}
+
+/// Resolve a variable for expressions - runtime variables shadow inputs
+fn lookup_variable(name: &str, context: &WorkflowContext) -> Option<serde_json::Value> {
+    context.variables.get(name)
+        .or_else(|| context.inputs.get(name))
+        .cloned()
+}
+
+/// Evaluate a `condition` / `waitCondition` expression to a boolean
+/// Supports `{{count}} > 5`, `{{result.status}} == "ok"`, `&&`, `||`, `contains`, `matches`, `len()`...
+fn evaluate_condition(step: &WorkflowStep, condition: &str, context: &WorkflowContext) -> Result<bool, String> {
+    let value = crate::expression::evaluate(condition, &|name| lookup_variable(name, context))
+        .map_err(|e| format!("Invalid condition in step '{}': {}", step.id, e))?;
+    Ok(crate::expression::is_truthy(&value))
 }
 
 // ============================================
 // Step Executors
 // ============================================
 
-async fn execute_action_step(
-    _window: &tauri::Window,
+/// Stub used by tests instead of droidrun_executor.py: (interpreter, script)
+#[cfg(test)]
+static TEST_EXECUTOR: Mutex<Option<(String, std::path::PathBuf)>> = Mutex::new(None);
+
+/// Runs step executors through a stub until dropped (also when the test panics)
+#[cfg(test)]
+pub(crate) struct TestExecutorGuard;
+
+#[cfg(test)]
+impl TestExecutorGuard {
+    pub(crate) fn set(python: &str, script: std::path::PathBuf) -> Self {
+        *TEST_EXECUTOR.lock().unwrap_or_else(|e| e.into_inner()) = Some((python.to_string(), script));
+        TestExecutorGuard
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestExecutorGuard {
+    fn drop(&mut self) {
+        *TEST_EXECUTOR.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Interpreter and path of droidrun_executor.py
+fn droidrun_executor() -> Result<(String, std::path::PathBuf), String> {
+    #[cfg(test)]
+    if let Some(stub) = TEST_EXECUTOR.lock().ok().and_then(|stub| stub.clone()) {
+        return Ok(stub);
+    }
+    
+    if cfg!(debug_assertions) {
+        return Ok(("py".to_string(), std::path::PathBuf::from("src-tauri/droidrun_executor.py")));
+    }
+    // In production, the script is bundled with the app
+    let exe_dir = std::env::current_exe()
+        .map_err(|e| format!("Cannot get exe path: {}", e))?
+        .parent()
+        .ok_or("Cannot get exe parent dir")?
+        .to_path_buf();
+    Ok(("py".to_string(), exe_dir.join("droidrun_executor.py")))
+}
+
+async fn execute_action_step<R: tauri::Runtime>(
+    _window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
@@ -1734,21 +2806,10 @@ async fn execute_action_step(
     
     // Helper to run action via DroidRun API (Python executor)
     async fn run_droidrun_action(device_id: &str, action: &str, args: &[&str]) -> Result<String, String> {
-        // Get the path to droidrun_executor.py
-        let executor_path = if cfg!(debug_assertions) {
-            std::path::PathBuf::from("src-tauri/droidrun_executor.py")
-        } else {
-            // In production, the script is bundled with the app
-            let exe_dir = std::env::current_exe()
-                .map_err(|e| format!("Cannot get exe path: {}", e))?
-                .parent()
-                .ok_or("Cannot get exe parent dir")?
-                .to_path_buf();
-            exe_dir.join("droidrun_executor.py")
-        };
+        let (python, executor_path) = droidrun_executor()?;
         
         // Build command: py droidrun_executor.py <device_id> <action> [args...]
-        let mut cmd = new_async_command("py");
+        let mut cmd = new_async_command(&python);
         cmd.arg(&executor_path)
            .arg(device_id)
            .arg(action);
@@ -1757,8 +2818,8 @@ async fn execute_action_step(
             cmd.arg(arg);
         }
         
-        println!("[WORKFLOW] Running: py {} {} {} {:?}", 
-            executor_path.display(), device_id, action, args);
+        println!("[WORKFLOW] Running: {} {} {} {} {:?}", 
+            python, executor_path.display(), device_id, action, args);
         
         let output = cmd.output().await
             .map_err(|e| format!("DroidRun executor error: {}", e))?;
@@ -1844,10 +2905,33 @@ async fn execute_action_step(
         }
         
         "tap_element" | "tap_text" => {
-            // Find element by text and tap - only available via DroidRun
-            let text = params.get("text").ok_or("Missing 'text' param")?;
-            run_droidrun_action(device_id, "tap_text", &[text]).await?;
-            Ok(())
+            let tapped = if let Some(selector) = params.get("selector").or(step.selector.as_ref()) {
+                // Selector query against the Portal a11y tree, tap element center
+                let selector = compile_value(selector, context);
+                async {
+                    let portal = crate::portal_client::create_portal_client(device_id).await?;
+                    let state = portal.get_state().await?;
+                    let elem = crate::selector::select_first(&state.a11y_tree, &selector)?
+                        .ok_or_else(|| format!("No element matches selector '{}'", selector))?;
+                    let (x, y) = elem.center()
+                        .ok_or_else(|| format!("Element matching '{}' has no bounds", selector))?;
+                    portal.tap(x, y).await
+                }.await
+            } else {
+                // Find element by text and tap - only available via DroidRun
+                let text = params.get("text").ok_or("Missing 'text' or 'selector' param")?;
+                run_droidrun_action(device_id, "tap_text", &[text]).await.map(|_| ())
+            };
+            
+            // Optional x/y (e.g. recorded coordinates of a converted macro) when the element is not found
+            match (tapped, params.get("x"), params.get("y")) {
+                (Err(e), Some(x), Some(y)) => {
+                    println!("[WORKFLOW] tap_element failed: {}, tapping fallback ({}, {})", e, x, y);
+                    run_adb_fallback(device_id, &["shell", "input", "tap", x, y]).await?;
+                    Ok(())
+                }
+                (result, _, _) => result,
+            }
         }
         
         "swipe" => {
@@ -2035,35 +3119,168 @@ async fn execute_action_step(
     result
 }
 
-async fn execute_condition_step(
-    window: &tauri::Window,
+async fn execute_condition_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
-) -> Result<(), String> {
+) -> Result<StepOutcome, String> {
     let condition = step.condition.as_ref().ok_or("Condition step missing 'condition' field")?;
-    let compiled = compile_value(condition, context);
-    
-    // Evaluate condition
-    let result = match compiled.to_lowercase().as_str() {
-        "true" | "1" | "yes" => true,
-        "false" | "0" | "no" | "" => false,
-        _ => !compiled.is_empty(),
-    };
+    let result = evaluate_condition(step, condition, context)?;
     
-    println!("[WORKFLOW] Condition '{}' = {} -> {}", condition, compiled, result);
+    println!("[WORKFLOW] Condition '{}' -> {}", condition, result);
     
     let branch = if result { &step.then } else { &step.else_branch };
-    if let Some(steps) = branch {
-        for sub_step in steps {
-            execute_step(window, sub_step, context).await?;
+    for sub_step in branch.iter().flatten() {
+        match execute_step(window, sub_step, context).await? {
+            StepOutcome::Done => {}
+            signal => return Ok(signal),
+        }
+    }
+    
+    Ok(StepOutcome::Done)
+}
+
+/// try/catch/finally: the error is exposed as {{error.message}} / {{error.step_id}} (name from `variable`)
+async fn execute_try_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+) -> Result<StepOutcome, String> {
+    let body = step.body.as_ref().ok_or("Try step missing 'body' field")?;
+    let var_name = step.variable.as_deref().unwrap_or("error");
+    
+    context.failed_step_id = None;
+    let mut result = Ok(StepOutcome::Done);
+    for sub_step in body {
+        result = execute_step(window, sub_step, context).await;
+        if result != Ok(StepOutcome::Done) {
+            break;
+        }
+    }
+    
+    // A cancelled run is not caught, finally still gets its chance to clean up
+    let cancelled = !context.run_id.is_empty() && is_run_cancelled(&context.run_id);
+    if let Err(e) = &result {
+        let failed_step = context.failed_step_id.clone().unwrap_or_else(|| step.id.clone());
+        println!("[WORKFLOW] Try '{}' caught error in step {}: {}", step.id, failed_step, e);
+        context.variables.insert(var_name.to_string(), serde_json::json!({
+            "message": e,
+            "step_id": failed_step,
+        }));
+        
+        if let (Some(catch), false) = (&step.catch, cancelled) {
+            add_log(context, "warning", Some(&step.id), &format!("Caught error from step '{}': {}", failed_step, e));
+            context.failed_step_id = None;
+            result = Ok(StepOutcome::Done);
+            for sub_step in catch {
+                result = execute_step(window, sub_step, context).await;
+                if result != Ok(StepOutcome::Done) {
+                    break;
+                }
+            }
+        }
+    }
+    
+    let in_finally = std::mem::replace(&mut context.in_finally, true);
+    for sub_step in step.finally.iter().flatten() {
+        // The first error wins, but every cleanup step still runs
+        match (execute_step(window, sub_step, context).await, &result) {
+            (Err(e), Ok(_)) => result = Err(e),
+            (Ok(signal), Ok(StepOutcome::Done)) => result = Ok(signal),
+            _ => {}
         }
     }
+    context.in_finally = in_finally;
     
+    result
+}
+
+/// Assign variables without spawning Python: value, expression, increment, append, merge
+fn execute_set_step(step: &WorkflowStep, context: &mut WorkflowContext) -> Result<(), String> {
+    let operations = step.set.as_ref().ok_or("Set step missing 'set' field")?;
+    for operation in operations {
+        let value = apply_set_operation(operation, context)
+            .map_err(|e| format!("Set '{}' in step '{}': {}", operation.name, step.id, e))?;
+        println!("[WORKFLOW] Set {} = {}", operation.name, value);
+        context.variables.insert(operation.name.clone(), value);
+    }
     Ok(())
 }
 
-async fn execute_loop_step(
-    window: &tauri::Window,
+fn apply_set_operation(operation: &SetOperation, context: &WorkflowContext) -> Result<serde_json::Value, String> {
+    use serde_json::Value;
+    
+    if operation.name.trim().is_empty() {
+        return Err("variable name is empty".to_string());
+    }
+    let current = lookup_variable(&operation.name, context).unwrap_or(Value::Null);
+    
+    match operation {
+        SetOperation { value: Some(value), expression: None, increment: None, append: None, merge: None, .. } => {
+            Ok(resolve_json_templates(value, context))
+        }
+        SetOperation { value: None, expression: Some(expression), increment: None, append: None, merge: None, .. } => {
+            crate::expression::evaluate(expression, &|name| lookup_variable(name, context))
+                .map_err(|e| format!("invalid expression '{}': {}", expression, e))
+        }
+        SetOperation { value: None, expression: None, increment: Some(amount), append: None, merge: None, .. } => {
+            let as_number = |value: &Value| match value {
+                Value::Null => Some(0.0),
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse::<f64>().ok(),
+                _ => None,
+            };
+            let amount = resolve_json_templates(amount, context);
+            let base = as_number(&current).ok_or_else(|| format!("current value {} is not a number", current))?;
+            let step = as_number(&amount).ok_or_else(|| format!("increment {} is not a number", amount))?;
+            let total = base + step;
+            Ok(if total.fract() == 0.0 {
+                Value::from(total as i64)
+            } else {
+                serde_json::Number::from_f64(total).map(Value::Number).unwrap_or(Value::Null)
+            })
+        }
+        SetOperation { value: None, expression: None, increment: None, append: Some(item), merge: None, .. } => {
+            let mut list = match current {
+                Value::Null => vec![],
+                Value::Array(list) => list,
+                other => return Err(format!("cannot append to {}", other)),
+            };
+            list.push(resolve_json_templates(item, context));
+            Ok(Value::Array(list))
+        }
+        SetOperation { value: None, expression: None, increment: None, append: None, merge: Some(patch), .. } => {
+            let mut object = match current {
+                Value::Null => serde_json::Map::new(),
+                Value::Object(object) => object,
+                other => return Err(format!("cannot merge into {}", other)),
+            };
+            match resolve_json_templates(patch, context) {
+                Value::Object(patch) => object.extend(patch),
+                other => return Err(format!("merge value {} is not an object", other)),
+            }
+            Ok(Value::Object(object))
+        }
+        _ => Err("use exactly one of value, expression, increment, append or merge".to_string()),
+    }
+}
+
+/// Resolve templates in every string of a JSON value (objects and arrays included)
+fn resolve_json_templates(value: &serde_json::Value, context: &WorkflowContext) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => resolve_mapped_value(s, context),
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(|v| resolve_json_templates(v, context)).collect())
+        }
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.iter().map(|(k, v)| (k.clone(), resolve_json_templates(v, context))).collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+async fn execute_loop_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
@@ -2076,78 +3293,301 @@ async fn execute_loop_step(
     
     let body = step.body.as_ref().ok_or("Loop step missing 'body' field")?;
     
-    for i in 0..count {
+    // A resumed run continues the interrupted iteration
+    let start = context.loop_counters.get(&step.id).copied().unwrap_or(0).clamp(0, count.max(0));
+    if start > 0 {
+        add_log(context, "info", Some(&step.id), &format!("⏩ Resuming loop at iteration {}/{}", start + 1, count));
+    }
+    for i in start..count {
+        context.loop_counters.insert(step.id.clone(), i);
         context.variables.insert(var_name.to_string(), serde_json::json!(i));
         println!("[WORKFLOW] Loop iteration {}/{}", i + 1, count);
         
-        for sub_step in body {
-            execute_step(window, sub_step, context).await?;
+        if !execute_loop_body(window, body, context).await? {
+            break;
         }
     }
     
+    context.loop_counters.remove(&step.id);
+    
     Ok(())
 }
 
-async fn execute_while_step(
-    window: &tauri::Window,
+async fn execute_foreach_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
-    let condition = step.condition.as_ref().ok_or("While step missing 'condition' field")?;
-    let max_iterations = step.max_iterations.unwrap_or(100);
-    let body = step.body.as_ref().ok_or("While step missing 'body' field")?;
+    use futures::stream::{FuturesUnordered, StreamExt};
+    
+    let items_template = step.items.as_ref().ok_or("Foreach step missing 'items' field")?;
+    let items = match resolve_mapped_value(items_template, context) {
+        serde_json::Value::Array(items) => items,
+        // Python steps and inputs often hand over arrays as JSON text
+        serde_json::Value::String(text) => match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(serde_json::Value::Array(items)) => items,
+            _ => return Err(format!("Foreach items must be an array, got \"{}\"", text)),
+        },
+        other => return Err(format!("Foreach items must be an array, got {}", other)),
+    };
+    let item_var = step.variable.as_deref().unwrap_or("item");
+    let index_var = step.index_variable.as_deref().unwrap_or("index");
+    let concurrency = step.concurrency.unwrap_or(1).max(1);
+    let body = step.body.as_ref().ok_or("Foreach step missing 'body' field")?;
+    
+    println!("[WORKFLOW] Foreach over {} items, variable: {}, concurrency: {}", items.len(), item_var, concurrency);
+    
+    if concurrency == 1 {
+        // A resumed run continues the interrupted iteration
+        let start = context.loop_counters.get(&step.id).copied().unwrap_or(0).max(0) as usize;
+        if start > 0 {
+            add_log(context, "info", Some(&step.id), &format!("⏩ Resuming foreach at item {}/{}", start + 1, items.len()));
+        }
+        for (i, item) in items.iter().enumerate().skip(start) {
+            context.loop_counters.insert(step.id.clone(), i as i32);
+            context.variables.insert(item_var.to_string(), item.clone());
+            context.variables.insert(index_var.to_string(), serde_json::json!(i));
+            println!("[WORKFLOW] Foreach iteration {}/{}", i + 1, items.len());
+            
+            if !execute_loop_body(window, body, context).await? {
+                break;
+            }
+        }
+        context.loop_counters.remove(&step.id);
+        return Ok(());
+    }
     
-    let mut iteration = 0;
-    loop {
-        if iteration >= max_iterations {
-            println!("[WORKFLOW] While loop reached max iterations: {}", max_iterations);
-            break;
+    // Concurrent iterations run on copies of the context, like parallel branches
+    // They finish out of order, so there is no loop counter to resume from
+    if context.loop_counters.remove(&step.id).is_some() {
+        add_log(context, "warning", Some(&step.id), "⏩ Concurrent foreach keeps no progress, starting again at the first item");
+    }
+    let base_context = context.clone();
+    let base_logs = context.logs.len();
+    let base_history = context.history.len();
+    let start_item = |(i, item): (usize, &serde_json::Value)| {
+        let mut item_context = base_context.clone();
+        item_context.variables.insert(item_var.to_string(), item.clone());
+        item_context.variables.insert(index_var.to_string(), serde_json::json!(i));
+        async move {
+            let result = execute_loop_body(window, body, &mut item_context).await;
+            (i, result, item_context)
         }
-        
-        let compiled = compile_value(condition, context);
-        let should_continue = match compiled.to_lowercase().as_str() {
-            "true" | "1" | "yes" => true,
-            _ => false,
-        };
-        
-        if !should_continue {
-            break;
+    };
+    let mut pending = items.iter().enumerate();
+    let mut running: FuturesUnordered<_> = pending.by_ref().take(concurrency).map(start_item).collect();
+    
+    let mut error = None;
+    while let Some((i, result, item_context)) = running.next().await {
+        context.logs.extend(item_context.logs.into_iter().skip(base_logs));
+        context.history.extend(item_context.history.into_iter().skip(base_history));
+        if item_context.timed_out_step.is_some() {
+            context.timed_out_step = item_context.timed_out_step;
         }
         
-        println!("[WORKFLOW] While iteration {}", iteration + 1);
-        for sub_step in body {
-            execute_step(window, sub_step, context).await?;
+        match result {
+            // Variables changed by an iteration merge back, the last one to finish wins
+            Ok(keep_going) => {
+                context.variables.extend(item_context.variables.into_iter()
+                    .filter(|(k, v)| base_context.variables.get(k) != Some(v) && k != item_var && k != index_var));
+                // `break` stops scheduling and drops the iterations still running
+                if !keep_going {
+                    break;
+                }
+                if let Some(next) = pending.next() {
+                    running.push(start_item(next));
+                }
+            }
+            Err(e) => {
+                // Dropping the running futures stops the remaining iterations
+                error = Some(format!("Foreach iteration {} failed: {}", i + 1, e));
+                break;
+            }
         }
-        
-        iteration += 1;
     }
+    drop(running);
     
-    Ok(())
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
-async fn execute_parallel_step(
-    window: &tauri::Window,
-    step: &WorkflowStep,
+/// Run one iteration of a loop body - Ok(false) means a `break` step ended the loop
+async fn execute_loop_body<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    body: &[WorkflowStep],
+    context: &mut WorkflowContext,
+) -> Result<bool, String> {
+    for sub_step in body {
+        match execute_step(window, sub_step, context).await? {
+            StepOutcome::Done => {}
+            StepOutcome::Continue => return Ok(true),
+            StepOutcome::Break => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+/// `break` / `continue`, optionally guarded by `condition`, unwind to the nearest loop
+fn execute_loop_control_step(step: &WorkflowStep, context: &WorkflowContext) -> Result<StepOutcome, String> {
+    if let Some(condition) = &step.condition {
+        if !evaluate_condition(step, condition, context)? {
+            return Ok(StepOutcome::Done);
+        }
+    }
+    println!("[WORKFLOW] {} at step {}", step.step_type, step.id);
+    Ok(if step.step_type == "break" { StepOutcome::Break } else { StepOutcome::Continue })
+}
+
+async fn execute_while_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
-    let branches = step.branches.as_ref().ok_or("Parallel step missing 'branches' field")?;
+    let condition = step.condition.as_ref().ok_or("While step missing 'condition' field")?;
+    let max_iterations = step.max_iterations.unwrap_or(100);
+    let body = step.body.as_ref().ok_or("While step missing 'body' field")?;
+    
+    let mut iteration = context.loop_counters.get(&step.id).copied().unwrap_or(0);
+    if iteration > 0 {
+        add_log(context, "info", Some(&step.id), &format!("⏩ Resuming while loop at iteration {}", iteration + 1));
+    }
+    loop {
+        if iteration >= max_iterations {
+            println!("[WORKFLOW] While loop reached max iterations: {}", max_iterations);
+            break;
+        }
+        
+        if !evaluate_condition(step, condition, context)? {
+            break;
+        }
+        
+        println!("[WORKFLOW] While iteration {}", iteration + 1);
+        context.loop_counters.insert(step.id.clone(), iteration);
+        if !execute_loop_body(window, body, context).await? {
+            break;
+        }
+        
+        iteration += 1;
+    }
+    
+    context.loop_counters.remove(&step.id);
     
-    println!("[WORKFLOW] Parallel execution with {} branches", branches.len());
+    Ok(())
+}
+
+async fn execute_parallel_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+) -> Result<(), String> {
+    use futures::stream::{FuturesUnordered, StreamExt};
     
-    // For now, run branches sequentially (true parallel would need thread-safe context)
-    // TODO: Implement proper parallel execution with Arc<Mutex<Context>>
-    for (i, branch) in branches.iter().enumerate() {
-        println!("[WORKFLOW] Parallel branch {}", i + 1);
-        for sub_step in branch {
-            execute_step(window, sub_step, context).await?;
+    let branches = step.branches.as_ref().ok_or("Parallel step missing 'branches' field")?;
+    let first_success = step.join.as_deref() == Some("first_success");
+    let fail_fast = step.fail_fast.unwrap_or(true);
+    let namespaced = step.merge_strategy.as_deref() == Some("namespace");
+    
+    println!("[WORKFLOW] Parallel execution with {} branches (join: {}, fail_fast: {})",
+        branches.len(), if first_success { "first_success" } else { "all" }, fail_fast);
+    
+    // Each branch runs concurrently on its own copy of the context
+    let mut running: FuturesUnordered<_> = branches.iter().enumerate()
+        .map(|(i, branch)| {
+            let mut branch_context = context.clone();
+            async move {
+                let mut result = Ok(());
+                for sub_step in branch {
+                    if let Err(e) = execute_step(window, sub_step, &mut branch_context).await.and_then(StepOutcome::outside_loop) {
+                        result = Err(e);
+                        break;
+                    }
+                }
+                (i, result, branch_context)
+            }
+        })
+        .collect();
+    
+    let base_variables = context.variables.clone();
+    let base_logs = context.logs.len();
+    let base_history = context.history.len();
+    let mut branch_outputs = vec![serde_json::Value::Null; branches.len()];
+    let mut failures: Vec<String> = Vec::new();
+    let mut succeeded = false;
+    
+    // Merge in completion order, so with "last_writer" the branch that finishes last wins
+    while let Some((i, result, branch_context)) = running.next().await {
+        context.logs.extend(branch_context.logs.into_iter().skip(base_logs));
+        context.history.extend(branch_context.history.into_iter().skip(base_history));
+        if branch_context.timed_out_step.is_some() {
+            context.timed_out_step = branch_context.timed_out_step;
         }
+        
+        match result {
+            Ok(()) => {
+                println!("[WORKFLOW] Parallel branch {} completed", i + 1);
+                let _ = window.emit("workflow-step", serde_json::json!({
+                    "step_id": step.id,
+                    "status": "branch-completed",
+                    "branch": i,
+                }));
+                
+                let changed: serde_json::Map<String, serde_json::Value> = branch_context.variables.into_iter()
+                    .filter(|(k, v)| base_variables.get(k) != Some(v))
+                    .collect();
+                if namespaced {
+                    branch_outputs[i] = serde_json::Value::Object(changed);
+                } else {
+                    context.variables.extend(changed);
+                }
+                
+                if first_success {
+                    succeeded = true;
+                    break;
+                }
+            }
+            Err(e) => {
+                println!("[WORKFLOW] Parallel branch {} failed: {}", i + 1, e);
+                let _ = window.emit("workflow-step", serde_json::json!({
+                    "step_id": step.id,
+                    "status": "branch-failed",
+                    "branch": i,
+                    "error": e,
+                }));
+                failures.push(format!("branch {}: {}", i + 1, e));
+                
+                if fail_fast && !first_success {
+                    break;
+                }
+            }
+        }
+    }
+    
+    // Dropping unfinished branches kills their child processes
+    let cancelled = running.len();
+    drop(running);
+    if cancelled > 0 {
+        println!("[WORKFLOW] Cancelled {} unfinished parallel branches", cancelled);
+    }
+    
+    if namespaced {
+        let key = step.save_to.clone().unwrap_or_else(|| step.id.clone());
+        context.variables.insert(key, serde_json::Value::Array(branch_outputs));
+    }
+    
+    if first_success && !succeeded {
+        return Err(format!("All parallel branches failed: {}", failures.join("; ")));
+    }
+    if !first_success && !failures.is_empty() {
+        return Err(format!("Parallel step failed: {}", failures.join("; ")));
     }
     
     Ok(())
 }
 
-async fn execute_python_step(
-    window: &tauri::Window,
+async fn execute_python_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
@@ -2196,8 +3636,8 @@ async fn execute_python_step(
 }
 
 /// Execute ScripterAgent step - AI generates and runs Python code with Shared State
-async fn execute_scripter_step(
-    window: &tauri::Window,
+async fn execute_scripter_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
@@ -2360,6 +3800,7 @@ except Exception as e:
             error_message: script_result.error.clone().unwrap_or_default(),
             retry_count: 0,
             suggested_fix: None,
+            screen_state: None,
         });
         return Err(script_result.error.unwrap_or_else(|| "ScripterAgent execution failed".to_string()));
     }
@@ -2377,28 +3818,127 @@ except Exception as e:
     Ok(())
 }
 
-async fn execute_prompt_step(
-    window: &tauri::Window,
+async fn execute_prompt_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
     let prompt = step.prompt.as_ref().ok_or("Prompt step missing 'prompt' field")?;
     let compiled_prompt = compile_value(prompt, context);
+    let step_start = std::time::Instant::now();
     
-    println!("[WORKFLOW] Running AI prompt: {}", &compiled_prompt[..compiled_prompt.len().min(100)]);
+    let max_steps = step_max_steps(step, context)?;
+    let result = run_agent_prompt(window, step, context, &compiled_prompt, max_steps).await;
     
-    // Emit event for frontend to handle (requires active profile)
-    let _ = window.emit("workflow-prompt-request", serde_json::json!({
+    let result_value = prompt_result_value(&result);
+    if let Some(save_to) = &step.save_to {
+        context.variables.insert(save_to.clone(), result_value.clone());
+    }
+    
+    context.history.push(ActionRecord {
+        step_id: step.id.clone(),
+        step_type: "prompt".to_string(),
+        action: Some(compiled_prompt.chars().take(100).collect()),
+        result: Some(result_value),
+        success: result.is_ok(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        duration_ms: step_start.elapsed().as_millis() as i64,
+    });
+    
+    result.map(|_| ()).map_err(|e| e.message)
+}
+
+/// Skill variables from the step params - maxSteps configures the agent and is left out
+fn skill_inputs(step: &WorkflowStep, context: &WorkflowContext) -> HashMap<String, serde_json::Value> {
+    step.params.iter().flatten()
+        .filter(|(name, _)| !matches!(name.as_str(), "maxSteps" | "max_steps"))
+        .map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => resolve_mapped_value(s, context),
+                _ => value.clone(),
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+/// `maxSteps` param of prompt/skill steps (templates allowed)
+fn step_max_steps(step: &WorkflowStep, context: &WorkflowContext) -> Result<Option<i32>, String> {
+    match step.params.as_ref().and_then(|p| p.get("maxSteps").or_else(|| p.get("max_steps"))) {
+        Some(serde_json::Value::String(s)) => {
+            let compiled = compile_value(s, context);
+            compiled.trim().parse::<i32>().map(Some).map_err(|_| format!("Invalid maxSteps '{}'", compiled))
+        }
+        Some(v) => v.as_i64().map(|n| Some(n as i32)).ok_or_else(|| format!("Invalid maxSteps {}", v)),
+        None => Ok(None),
+    }
+}
+
+/// Run the droidrun agent on the workflow device with the active profile and wait for it
+async fn run_agent_prompt<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &WorkflowContext,
+    prompt: &str,
+    max_steps: Option<i32>,
+) -> Result<crate::task::TaskResult, crate::task::TaskError> {
+    // Same profile a task started from the Tasks page would use
+    let config = crate::config::load_config_sync()?;
+    let profile = config.active_profile_id.as_ref()
+        .and_then(|id| config.profiles.iter().find(|p| &p.id == id))
+        .ok_or("Running an AI agent requires an active AI profile")?;
+    let max_steps = max_steps.unwrap_or(profile.max_steps);
+    
+    println!("[WORKFLOW] Running AI agent (max {} steps): {}", max_steps,
+        prompt.chars().take(100).collect::<String>());
+    let _ = window.emit("workflow-step", serde_json::json!({
         "step_id": step.id,
-        "prompt": compiled_prompt,
-        "device_id": context.device_id,
-        "save_to": step.save_to,
+        "status": "agent-running",
+        "message": format!("AI agent running on {} (max {} steps)", context.device_id, max_steps)
     }));
     
-    // Note: Actual prompt execution would be handled by frontend using active profile
-    // This is a placeholder - in real implementation, would call run_task
-    
-    Ok(())
+    let tracing = &config.settings.tracing;
+    crate::task::run_task_internal(
+        window,
+        context.device_id.clone(),
+        profile.provider.name.clone(),
+        profile.provider.api_key.clone(),
+        profile.provider.model.clone(),
+        prompt.to_string(),
+        profile.provider.base_url.clone(),
+        Some(profile.vision),
+        Some(profile.reasoning),
+        Some(crate::task::TracingParams {
+            enabled: tracing.enabled,
+            provider: tracing.provider.clone(),
+            phoenix_url: tracing.phoenix_url.clone(),
+            phoenix_project_name: tracing.phoenix_project_name.clone(),
+            langfuse_secret_key: tracing.langfuse_secret_key.clone(),
+            langfuse_public_key: tracing.langfuse_public_key.clone(),
+            langfuse_host: tracing.langfuse_host.clone(),
+            langfuse_user_id: tracing.langfuse_user_id.clone(),
+            save_trajectory: tracing.save_trajectory.clone(),
+        }),
+        Some(max_steps),
+    ).await
+}
+
+/// Value stored in save_to: {success, answer, error, durationMs}
+fn prompt_result_value(result: &Result<crate::task::TaskResult, crate::task::TaskError>) -> serde_json::Value {
+    match result {
+        Ok(task) => serde_json::json!({
+            "success": task.success,
+            "answer": task.answer,
+            "error": null,
+            "durationMs": task.duration_ms,
+        }),
+        Err(e) => serde_json::json!({
+            "success": false,
+            "answer": e.answer,
+            "error": e.message,
+            "durationMs": null,
+        }),
+    }
 }
 
 async fn execute_wait_step(
@@ -2417,8 +3957,7 @@ async fn execute_wait_step(
         let start = std::time::Instant::now();
         
         loop {
-            let compiled = compile_value(condition, context);
-            if compiled.to_lowercase() == "true" {
+            if evaluate_condition(step, condition, context)? {
                 break;
             }
             if start.elapsed().as_millis() > max_wait {
@@ -2433,44 +3972,486 @@ async fn execute_wait_step(
     Ok(())
 }
 
+async fn execute_wait_for_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+) -> Result<(), String> {
+    let selector = step.selector.as_ref().map(|s| compile_value(s, context));
+    let activity = step.activity.as_ref().map(|a| compile_value(a, context));
+    let until = match (&step.until, &selector, &activity) {
+        (Some(until), _, _) => until.clone(),
+        (None, Some(_), _) => "appear".to_string(),
+        (None, None, Some(_)) => "activity".to_string(),
+        _ => return Err(format!("Wait_for step '{}' needs 'until', 'selector' or 'activity'", step.id)),
+    };
+    let poll_interval = step.poll_interval.unwrap_or(500).max(50);
+    let timeout = step.wait_timeout.unwrap_or(30000);
+    
+    let target = match until.as_str() {
+        "appear" | "disappear" => selector.clone()
+            .ok_or_else(|| format!("Wait_for '{}' needs a 'selector'", until))?,
+        "activity" => activity.clone()
+            .ok_or("Wait_for 'activity' needs an 'activity' field")?,
+        "keyboard" | "keyboard_hidden" | "screen_change" => String::new(),
+        other => return Err(format!("Unknown wait_for condition: {}", other)),
+    };
+    
+    println!("[WORKFLOW] Wait for {} {} (timeout {}ms)", until, target, timeout);
+    
+    let portal = crate::portal_client::create_portal_client(&context.device_id).await?;
+    let start = std::time::Instant::now();
+    let mut first_screenshot: Option<Vec<u8>> = None;
+    let mut last_state: Option<serde_json::Value> = None;
+    let mut last_poll_error: Option<String> = None;
+    let mut polls = 0u32;
+    
+    loop {
+        polls += 1;
+        
+        // Screen change compares screenshots, everything else reads the a11y tree
+        let matched: Option<serde_json::Value> = if until == "screen_change" {
+            match portal.screenshot().await {
+                Ok(bytes) => match &first_screenshot {
+                    None => {
+                        first_screenshot = Some(bytes);
+                        None
+                    }
+                    Some(first) if *first != bytes => Some(serde_json::json!(true)),
+                    Some(_) => None,
+                },
+                Err(e) => {
+                    last_poll_error = Some(e);
+                    None
+                }
+            }
+        } else {
+            match portal.get_state().await {
+                Ok(state) => {
+                    last_state = Some(screen_state_summary(&state));
+                    let phone = &state.phone_state;
+                    match until.as_str() {
+                        "appear" => crate::selector::select_first(&state.a11y_tree, &target)?
+                            .map(|elem| element_attribute(elem, "element"))
+                            .transpose()?,
+                        "disappear" => crate::selector::select_first(&state.a11y_tree, &target)?
+                            .is_none()
+                            .then_some(serde_json::Value::Bool(true)),
+                        "activity" => phone.current_activity.as_deref()
+                            .filter(|current| current.contains(target.as_str()))
+                            .map(|current| serde_json::json!(current)),
+                        "keyboard" => phone.keyboard_shown.unwrap_or(false).then_some(serde_json::Value::Bool(true)),
+                        _ => (!phone.keyboard_shown.unwrap_or(false)).then_some(serde_json::Value::Bool(true)),
+                    }
+                }
+                Err(e) => {
+                    // Portal can drop a request while the app is busy - keep polling
+                    last_poll_error = Some(e);
+                    None
+                }
+            }
+        };
+        
+        if let Some(value) = matched {
+            println!("[WORKFLOW] Wait_for {} satisfied after {} poll(s)", until, polls);
+            if let Some(save_to) = &step.save_to {
+                context.variables.insert(save_to.clone(), value);
+            }
+            return Ok(());
+        }
+        
+        let elapsed = start.elapsed().as_millis() as u64;
+        if elapsed >= timeout {
+            let mut message = format!("Wait_for timeout after {}ms: {} {}", elapsed, until, target);
+            if let Some(e) = &last_poll_error {
+                message.push_str(&format!(" (last error: {})", e));
+            }
+            context.last_error = Some(ErrorContext {
+                step_id: step.id.clone(),
+                error_message: message.clone(),
+                retry_count: 0,
+                suggested_fix: None,
+                screen_state: last_state,
+            });
+            return Err(message);
+        }
+        
+        let _ = window.emit("workflow-step", serde_json::json!({
+            "step_id": step.id,
+            "status": "waiting",
+            "message": format!("Waiting for {} {} ({}s)", until, target, elapsed / 1000)
+        }));
+        tokio::time::sleep(tokio::time::Duration::from_millis(poll_interval)).await;
+    }
+}
+
+/// Compact description of the screen for error reports
+fn screen_state_summary(state: &crate::portal_client::StateResponse) -> serde_json::Value {
+    let texts: Vec<String> = crate::portal_client::find_all_in_tree(&state.a11y_tree, &|elem| {
+        elem.text.as_deref().map(|t| !t.trim().is_empty()).unwrap_or(false)
+            || elem.content_desc.as_deref().map(|d| !d.trim().is_empty()).unwrap_or(false)
+    })
+    .iter()
+    .take(50)
+    .filter_map(|elem| elem.text.clone().filter(|t| !t.trim().is_empty()).or(elem.content_desc.clone()))
+    .collect();
+    
+    serde_json::json!({
+        "currentActivity": state.phone_state.current_activity,
+        "keyboardShown": state.phone_state.keyboard_shown,
+        "texts": texts,
+    })
+}
+
 async fn execute_extract_step(
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
     let selector = step.selector.as_ref().ok_or("Extract step missing 'selector' field")?;
     let save_to = step.save_to.as_ref().ok_or("Extract step missing 'save_to' field")?;
+    let selector = compile_value(selector, context);
+    
+    let param = |name: &str| -> Option<String> {
+        step.params.as_ref()?.get(name).map(|v| match v {
+            serde_json::Value::String(s) => compile_value(s, context),
+            _ => v.to_string(),
+        })
+    };
+    // attribute: "text" (default), "contentDesc", "bounds", "className", "index", "element"
+    let attribute = param("attribute").unwrap_or_else(|| "text".to_string());
+    if !EXTRACT_ATTRIBUTES.contains(&attribute.as_str()) {
+        return Err(format!("Unknown extract attribute '{}' in step '{}'", attribute, step.id));
+    }
+    let all = param("all").map(|v| v == "true").unwrap_or(false);
+    let required = param("required").map(|v| v == "true").unwrap_or(false);
+    let pattern = param("pattern")
+        .map(|p| regex::Regex::new(&p).map_err(|e| format!("Invalid extract pattern '{}': {}", p, e)))
+        .transpose()?;
+    
+    println!("[WORKFLOW] Extract '{}' ({}) -> {}", selector, attribute, save_to);
+    
+    let portal = crate::portal_client::create_portal_client(&context.device_id).await?;
+    let state = portal.get_state().await?;
+    
+    let matches = crate::selector::select(&state.a11y_tree, &selector)?;
+    
+    let values: Vec<serde_json::Value> = matches.iter()
+        .map(|elem| element_attribute(elem, &attribute))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|v| !v.is_null())
+        .filter_map(|v| match (&pattern, &v) {
+            // Keep capture group 1 (or the whole match) from text values
+            (Some(re), serde_json::Value::String(text)) => re.captures(text).map(|caps| {
+                let m = caps.get(1).or_else(|| caps.get(0)).map(|m| m.as_str()).unwrap_or("");
+                serde_json::json!(m)
+            }),
+            _ => Some(v),
+        })
+        .collect();
     
-    println!("[WORKFLOW] Extract '{}' -> {}", selector, save_to);
+    if values.is_empty() && required {
+        return Err(format!("Extract: no element matches '{}'", selector));
+    }
     
-    // TODO: Implement actual extraction using screen analysis
-    // For now, just create a placeholder
-    context.variables.insert(save_to.clone(), serde_json::json!(null));
+    println!("[WORKFLOW] Extract matched {} element(s)", values.len());
+    
+    let value = if all {
+        serde_json::Value::Array(values)
+    } else {
+        values.into_iter().next().unwrap_or(serde_json::Value::Null)
+    };
+    context.variables.insert(save_to.clone(), value);
     
     Ok(())
 }
 
-async fn execute_skill_step(
-    window: &tauri::Window,
+/// Attributes an extract step can read ("element" is the whole element object)
+pub(crate) const EXTRACT_ATTRIBUTES: &[&str] = &[
+    "text", "contentDesc", "content_desc", "desc", "className", "class", "index", "bounds", "element",
+];
+
+/// Read one attribute of a UI element as a workflow value
+fn element_attribute(elem: &crate::portal_client::UIElement, attribute: &str) -> Result<serde_json::Value, String> {
+    Ok(match attribute {
+        "text" => serde_json::json!(elem.text),
+        "contentDesc" | "content_desc" | "desc" => serde_json::json!(elem.content_desc),
+        "className" | "class" => serde_json::json!(elem.class_name),
+        "index" => serde_json::json!(elem.index),
+        "bounds" => match (elem.bounds_rect(), elem.center()) {
+            (Some((left, top, right, bottom)), Some((x, y))) => serde_json::json!({
+                "left": left, "top": top, "right": right, "bottom": bottom, "x": x, "y": y,
+            }),
+            _ => serde_json::json!(elem.bounds),
+        },
+        "element" => serde_json::to_value(elem).unwrap_or(serde_json::Value::Null),
+        other => return Err(format!(
+            "Unknown extract attribute '{}' (expected text, contentDesc, className, index, bounds or element)", other
+        )),
+    })
+}
+
+async fn execute_skill_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
     step: &WorkflowStep,
     context: &mut WorkflowContext,
 ) -> Result<(), String> {
     let skill_id = step.skill_id.as_ref().ok_or("Skill step missing 'skill_id' field")?;
+    let skill_id = compile_value(skill_id, context);
+    let skill = crate::skill::load_skill(&skill_id)?;
+    let step_start = std::time::Instant::now();
     
-    println!("[WORKFLOW] Running skill: {}", skill_id);
+    // Skill variables come from params (templates compiled against the workflow), then defaults and coercion
+    let mut prompt = crate::skill::compile_skill_prompt(&skill, skill_inputs(step, context))?;
+    if let Some(extra) = &step.prompt {
+        prompt = format!("{}\n\nAdditional instructions: {}", prompt, compile_value(extra, context));
+    }
     
-    // Emit event for frontend to handle skill execution
-    let _ = window.emit("workflow-skill-request", serde_json::json!({
-        "step_id": step.id,
-        "skill_id": skill_id,
-        "device_id": context.device_id,
-        "inputs": context.inputs,
-    }));
+    println!("[WORKFLOW] Running skill '{}' ({})", skill.name, skill_id);
+    
+    let max_steps = step_max_steps(step, context)?.or(skill.max_steps);
+    let agent = run_agent_prompt(window, step, context, &prompt, max_steps);
+    let result = match skill.timeout.filter(|t| *t > 0) {
+        Some(secs) => tokio::time::timeout(tokio::time::Duration::from_secs(secs), agent).await
+            .unwrap_or_else(|_| Err(format!("Skill '{}' timed out after {}s", skill.name, secs).into())),
+        None => agent.await,
+    };
+    
+    let mut result_value = prompt_result_value(&result);
+    result_value["skillId"] = serde_json::json!(skill_id);
+    if let Some(save_to) = &step.save_to {
+        context.variables.insert(save_to.clone(), result_value.clone());
+    }
+    
+    context.history.push(ActionRecord {
+        step_id: step.id.clone(),
+        step_type: "skill".to_string(),
+        action: Some(skill.name.clone()),
+        result: Some(result_value),
+        success: result.is_ok(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        duration_ms: step_start.elapsed().as_millis() as i64,
+    });
+    
+    match result {
+        Ok(_) => Ok(()),
+        // Skills generated with "onError": "skip" never stop the workflow
+        Err(e) if skill.on_error.as_deref() == Some("skip") => {
+            add_log(context, "warning", Some(&step.id), &format!("Skill '{}' failed, skipped: {}", skill.name, e));
+            Ok(())
+        }
+        Err(e) => Err(e.message),
+    }
+}
+
+// ============================================
+// Sub-workflows (CALL step)
+// ============================================
+
+/// Max nesting of call steps (workflow -> child -> grandchild ...)
+const MAX_CALL_DEPTH: usize = 8;
+
+/// Directories searched for workflow definitions, user dir first
+pub(crate) fn get_workflows_dirs() -> Vec<std::path::PathBuf> {
+    let mut candidates = vec![crate::utils::app_data_dir().join("workflows")];
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(exe_dir) = exe_path.parent() {
+            // Dev mode: target/debug -> src-tauri/workflows
+            if let Some(src_tauri) = exe_dir.parent().and_then(|p| p.parent()) {
+                candidates.push(src_tauri.join("workflows"));
+            }
+            candidates.push(exe_dir.join("workflows"));
+        }
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        candidates.push(cwd.join("workflows"));
+        candidates.push(cwd.join("src-tauri").join("workflows"));
+    }
+    candidates
+}
+
+/// Load a workflow definition by id (`<id>.json`, or any file whose "id" matches)
+pub fn load_workflow_definition(workflow_id: &str) -> Result<WorkflowDefinition, String> {
+    crate::utils::check_file_id("workflow", workflow_id)?;
+    
+    let dirs: Vec<std::path::PathBuf> = get_workflows_dirs().into_iter().filter(|d| d.is_dir()).collect();
+    let parse = |path: &std::path::Path| -> Result<WorkflowDefinition, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid workflow {}: {}", path.display(), e))
+    };
+    
+    for dir in &dirs {
+        let path = dir.join(format!("{}.json", workflow_id));
+        if path.is_file() {
+            return parse(&path);
+        }
+    }
+    
+    for dir in &dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Ok(workflow) = parse(&path) {
+                if workflow.id == workflow_id {
+                    return Ok(workflow);
+                }
+            }
+        }
+    }
+    
+    Err(format!("Workflow '{}' not found in workflows directory", workflow_id))
+}
+
+async fn execute_call_step<R: tauri::Runtime>(
+    window: &tauri::Window<R>,
+    step: &WorkflowStep,
+    context: &mut WorkflowContext,
+) -> Result<(), String> {
+    let workflow_id = step.workflow_id.as_ref().ok_or("Call step missing 'workflow_id' field")?;
+    let workflow_id = compile_value(workflow_id, context);
+    
+    if context.call_stack.contains(&workflow_id) {
+        let chain = [context.call_stack.clone(), vec![workflow_id.clone()]].concat().join(" -> ");
+        return Err(format!("Workflow call cycle detected: {}", chain));
+    }
+    if context.call_stack.len() >= MAX_CALL_DEPTH {
+        return Err(format!("Workflow call depth exceeds {} at '{}'", MAX_CALL_DEPTH, workflow_id));
+    }
+    
+    let child = load_workflow_definition(&workflow_id)?;
+    
+    // Child inputs: mapped params (templates compiled against the parent), then defaults and coercion
+    let mut inputs: HashMap<String, serde_json::Value> = HashMap::new();
+    for (name, value) in step.params.iter().flatten() {
+        let value = match value {
+            serde_json::Value::String(s) => resolve_mapped_value(s, context),
+            _ => value.clone(),
+        };
+        inputs.insert(name.clone(), value);
+    }
+    let prepared = crate::workflow_inputs::prepare_inputs(&child.inputs, inputs);
+    if !prepared.valid {
+        return Err(format!("Invalid inputs for workflow '{}': {}", workflow_id, prepared.error_summary()));
+    }
+    let inputs = prepared.inputs;
+    
+    println!("[WORKFLOW] Calling workflow '{}' with inputs: {:?}", workflow_id, inputs);
+    
+    let mut child_context = WorkflowContext {
+        inputs,
+        variables: HashMap::new(),
+        device_id: context.device_id.clone(),
+        current_step_id: None,
+        logs: vec![],
+        history: vec![],
+        plan: child.description.clone(),
+        last_error: None,
+        call_stack: [context.call_stack.clone(), vec![workflow_id.clone()]].concat(),
+        timed_out_step: None,
+        run_id: context.run_id.clone(),
+        loop_counters: HashMap::new(),
+        failed_step_id: None,
+        in_finally: context.in_finally,
+        handled_error: None,
+    };
     
-    // Note: Actual skill execution would be handled by frontend
+    // The child's own timeout bounds the whole call
+    let mut result = Ok(());
+    let child_run = async {
+        for child_step in &child.steps {
+            let nested_id = format!("{}/{}", step.id, child_step.id);
+            child_context.current_step_id = Some(child_step.id.clone());
+            let _ = window.emit("workflow-step", serde_json::json!({
+                "step_id": nested_id,
+                "step_type": child_step.step_type,
+                "step_name": child_step.name,
+                "status": "running",
+            }));
+            
+            if let Err(e) = execute_step(window, child_step, &mut child_context).await.and_then(StepOutcome::outside_loop) {
+                let _ = window.emit("workflow-step", serde_json::json!({
+                    "step_id": nested_id,
+                    "status": "failed",
+                    "error": e.clone(),
+                }));
+                child_context.logs.push(WorkflowLog {
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                    level: "error".to_string(),
+                    step_id: Some(child_step.id.clone()),
+                    message: format!("✗ Error: {}", e),
+                });
+                result = Err(format!("Workflow '{}' failed at step '{}': {}", workflow_id, child_step.id, e));
+                break;
+            }
+            
+            let _ = window.emit("workflow-step", serde_json::json!({
+                "step_id": nested_id,
+                "status": "completed",
+            }));
+            delay_after_action(child_step, child.step_delay).await;
+        }
+    };
+    let child_timeout = child.timeout.filter(|t| *t > 0).map(|t| t as u64);
+    let timed_out = match child_timeout {
+        Some(secs) => tokio::time::timeout(tokio::time::Duration::from_secs(secs), child_run).await.is_err(),
+        None => {
+            child_run.await;
+            false
+        }
+    };
+    if timed_out {
+        let child_step_id = child_context.current_step_id.clone().unwrap_or_default();
+        child_context.timed_out_step = Some(child_step_id.clone());
+        result = Err(format!("Workflow '{}' timed out after {}s during step '{}'",
+            workflow_id, child_timeout.unwrap_or_default(), child_step_id));
+    }
+    
+    // Nest child logs and history under this step id
+    for mut log in child_context.logs {
+        log.step_id = Some(match log.step_id {
+            Some(child_id) => format!("{}/{}", step.id, child_id),
+            None => step.id.clone(),
+        });
+        context.logs.push(log);
+    }
+    for mut record in child_context.history {
+        record.step_id = format!("{}/{}", step.id, record.step_id);
+        context.history.push(record);
+    }
+    
+    if result.is_err() {
+        context.last_error = child_context.last_error;
+        context.timed_out_step = child_context.timed_out_step.map(|id| format!("{}/{}", step.id, id));
+        return result;
+    }
+    
+    // Copy declared outputs back to the parent
+    let outputs: serde_json::Map<String, serde_json::Value> = child.outputs.iter()
+        .filter_map(|name| child_context.variables.get(name).map(|v| (name.clone(), v.clone())))
+        .collect();
+    match &step.save_to {
+        Some(save_to) => {
+            context.variables.insert(save_to.clone(), serde_json::Value::Object(outputs));
+        }
+        None => context.variables.extend(outputs),
+    }
     
     Ok(())
 }
 
+/// A mapped value that is exactly one placeholder ("{{user}}", "{{items[0]}}") keeps its JSON type, anything else is compiled as text
+fn resolve_mapped_value(template: &str, context: &WorkflowContext) -> serde_json::Value {
+    let (value, warnings) = crate::template::resolve(template, &|name| lookup_variable(name, context));
+    for warning in warnings {
+        println!("[WORKFLOW] ⚠️ Template {}", warning);
+    }
+    value
+}
+
 // ============================================
 // Python Workflow Executor
 // ============================================
@@ -2586,9 +4567,27 @@ pub async fn run_workflow_python(
 mod tests {
     use super::*;
 
+    /// Steps run for real on the mock runtime, their events go nowhere
+    fn test_window() -> (tauri::App<tauri::test::MockRuntime>, tauri::Window<tauri::test::MockRuntime>) {
+        let app = tauri::test::mock_app();
+        let webview = tauri::WebviewWindowBuilder::new(&app, "main", Default::default()).build().unwrap();
+        let window = webview.as_ref().window();
+        (app, window)
+    }
+
+    fn test_context() -> WorkflowContext {
+        serde_json::from_value(serde_json::json!({
+            "inputs": {}, "variables": {}, "deviceId": "emulator-5554", "currentStepId": null, "logs": [],
+        })).unwrap()
+    }
+
+    fn test_step(value: serde_json::Value) -> WorkflowStep {
+        serde_json::from_value(value).unwrap()
+    }
+
     #[test]
     fn test_compile_value() {
-        let mut context = WorkflowContext {
+        let context = WorkflowContext {
             inputs: HashMap::from([
                 ("count".to_string(), serde_json::json!(5)),
                 ("name".to_string(), serde_json::json!("test")),
@@ -2599,10 +4598,725 @@ mod tests {
             device_id: "test".to_string(),
             current_step_id: None,
             logs: vec![],
+            history: vec![],
+            plan: None,
+            last_error: None,
+            call_stack: vec![],
+            timed_out_step: None,
+            run_id: String::new(),
+            loop_counters: HashMap::new(),
+            failed_step_id: None,
+            in_finally: false,
+            handled_error: None,
         };
 
         assert_eq!(compile_value("{{count}}", &context), "5");
         assert_eq!(compile_value("Hello {{name}}!", &context), "Hello test!");
         assert_eq!(compile_value("{{result.value}}", &context), "42");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_evaluate_condition() {
+        let context = WorkflowContext {
+            inputs: HashMap::from([("count".to_string(), serde_json::json!("20"))]),
+            variables: HashMap::from([
+                ("result".to_string(), serde_json::json!({"status": "ok"})),
+            ]),
+            device_id: "test".to_string(),
+            current_step_id: None,
+            logs: vec![],
+            history: vec![],
+            plan: None,
+            last_error: None,
+            call_stack: vec![],
+            timed_out_step: None,
+            run_id: String::new(),
+            loop_counters: HashMap::new(),
+            failed_step_id: None,
+            in_finally: false,
+            handled_error: None,
+        };
+        let step: WorkflowStep = serde_json::from_value(serde_json::json!({
+            "id": "check-1",
+            "type": "condition",
+        })).unwrap();
+
+        assert!(evaluate_condition(&step, "{{count}} > 5 && {{result.status}} == \"ok\"", &context).unwrap());
+        assert!(!evaluate_condition(&step, "result.status != 'ok'", &context).unwrap());
+        let err = evaluate_condition(&step, "{{count}} >", &context).unwrap_err();
+        assert!(err.contains("check-1"));
+    }
+
+    #[test]
+    fn test_screen_state_summary() {
+        let state: crate::portal_client::StateResponse = serde_json::from_value(serde_json::json!({
+            "a11y_tree": [{
+                "className": "android.widget.FrameLayout",
+                "children": [
+                    {"className": "android.widget.TextView", "text": "Login"},
+                    {"className": "android.widget.ImageView", "contentDesc": "Close"},
+                    {"className": "android.widget.EditText", "text": " "},
+                ],
+            }],
+            "phone_state": {"current_activity": "com.app/.LoginActivity", "keyboard_shown": true},
+        })).unwrap();
+
+        let summary = screen_state_summary(&state);
+        assert_eq!(summary["currentActivity"], "com.app/.LoginActivity");
+        assert_eq!(summary["keyboardShown"], true);
+        assert_eq!(summary["texts"], serde_json::json!(["Login", "Close"]));
+    }
+
+    #[test]
+    fn test_element_attribute() {
+        let elem: crate::portal_client::UIElement = serde_json::from_value(serde_json::json!({
+            "className": "android.widget.Button", "text": "Follow", "bounds": "0,100,200,300",
+        })).unwrap();
+        assert_eq!(element_attribute(&elem, "text").unwrap(), serde_json::json!("Follow"));
+        assert_eq!(element_attribute(&elem, "bounds").unwrap()["y"], serde_json::json!(200));
+        assert_eq!(element_attribute(&elem, "element").unwrap()["className"], serde_json::json!("android.widget.Button"));
+        assert!(element_attribute(&elem, "txt").unwrap_err().contains("'txt'"));
+    }
+
+    #[test]
+    fn test_call_step_inputs() {
+        let mut context: WorkflowContext = serde_json::from_value(serde_json::json!({
+            "inputs": {"account": "demo"},
+            "variables": {"user": {"id": 7}},
+            "deviceId": "emulator-5554",
+            "currentStepId": null,
+            "logs": [],
+        })).unwrap();
+        context.variables.insert("count".to_string(), serde_json::json!(3));
+
+        assert_eq!(resolve_mapped_value("{{user}}", &context), serde_json::json!({"id": 7}));
+        assert_eq!(resolve_mapped_value("{{count}}", &context), serde_json::json!(3));
+        assert_eq!(resolve_mapped_value("{{account}}@mail", &context), serde_json::json!("demo@mail"));
+        assert!(load_workflow_definition("../config").is_err());
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let mut context: WorkflowContext = serde_json::from_value(serde_json::json!({
+            "inputs": {"count": 2},
+            "variables": {"profile": {"name": "demo"}},
+            "deviceId": "emulator-5554",
+            "currentStepId": null,
+            "logs": [],
+        })).unwrap();
+        let step: WorkflowStep = serde_json::from_value(serde_json::json!({
+            "id": "assign", "type": "set", "set": [
+                {"name": "count", "increment": 1},
+                {"name": "double", "expression": "count * 2"},
+                {"name": "seen", "append": "{{profile.name}}"},
+                {"name": "seen", "append": {"n": "{{count}}"}},
+                {"name": "profile", "merge": {"age": 30}},
+                {"name": "label", "value": "{{profile.name}}-{{count}}"}
+            ]
+        })).unwrap();
+        execute_set_step(&step, &mut context).unwrap();
+        
+        assert_eq!(context.variables["count"], serde_json::json!(3));
+        assert_eq!(context.variables["double"], serde_json::json!(6));
+        assert_eq!(context.variables["seen"], serde_json::json!(["demo", {"n": 3}]));
+        assert_eq!(context.variables["profile"], serde_json::json!({"name": "demo", "age": 30}));
+        assert_eq!(context.variables["label"], serde_json::json!("demo-3"));
+        
+        let invalid = SetOperation { name: "x".to_string(), value: Some(serde_json::json!(1)), increment: Some(serde_json::json!(1)), ..Default::default() };
+        assert!(apply_set_operation(&invalid, &context).is_err());
+    }
+
+    #[test]
+    fn test_skill_inputs() {
+        let mut context = test_context();
+        context.variables.insert("user".to_string(), serde_json::json!({"name": "mun"}));
+        let step = test_step(serde_json::json!({
+            "id": "follow",
+            "type": "skill",
+            "skillId": "follow_user",
+            "params": {"username": "{{user.name}}", "count": 3, "maxSteps": "{{limit}}"},
+        }));
+        let inputs = skill_inputs(&step, &context);
+        assert_eq!(inputs, HashMap::from([
+            ("username".to_string(), serde_json::json!("mun")),
+            ("count".to_string(), serde_json::json!(3)),
+        ]));
+    }
+
+    #[test]
+    fn test_prompt_result_value() {
+        let ok = Ok(crate::task::TaskResult {
+            task_id: "t".to_string(),
+            device_id: "emulator-5554".to_string(),
+            success: true,
+            output: String::new(),
+            screenshots: vec![],
+            duration_ms: 1200,
+            answer: Some("Liked 3 videos".to_string()),
+        });
+        assert_eq!(prompt_result_value(&ok), serde_json::json!({
+            "success": true, "answer": "Liked 3 videos", "error": null, "durationMs": 1200
+        }));
+        
+        let failed = Err(crate::task::TaskError {
+            message: "[emulator-5554] Task thất bại (exit code: 1)".to_string(),
+            answer: Some("Login screen not found".to_string()),
+        });
+        let value = prompt_result_value(&failed);
+        assert_eq!(value["success"], serde_json::json!(false));
+        assert_eq!(value["answer"], serde_json::json!("Login screen not found"));
+        assert_eq!(value["error"], serde_json::json!("[emulator-5554] Task thất bại (exit code: 1)"));
+
+        // Errors before the agent ran carry no answer
+        let value = prompt_result_value(&Err("Running an AI agent requires an active AI profile".into()));
+        assert_eq!(value["answer"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_debug_actions() {
+        let (_guard, _) = register_run(WorkflowRunInfo {
+            run_id: "debug-run".to_string(),
+            workflow_id: "wf".to_string(),
+            workflow_name: "Debug".to_string(),
+            device_id: "emulator-5554".to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            current_step_id: None,
+            cancel_requested: None,
+        }, Some(WorkflowDebugOptions { breakpoints: vec!["step-2".to_string()], pause_on_start: false })).unwrap();
+
+        // A second run with the same id is rejected and leaves the first one registered
+        let info = ACTIVE_RUNS.lock().unwrap()["debug-run"].info.clone();
+        let error = register_run(info, None).err().unwrap();
+        assert_eq!(error, "Run debug-run is already active");
+        assert!(with_debug_session("debug-run", |_| ()).unwrap().is_some());
+
+        // Actions only apply while paused
+        assert!(!send_debug_action("debug-run", None, DebugAction::Step).unwrap());
+        assert!(!send_debug_action("missing-run", None, DebugAction::Step).unwrap());
+
+        // Two parallel branches paused at the same time keep separate state
+        let context = test_context();
+        let ids = with_debug_session("debug-run", |debug| {
+            ["step-2", "branch-b"].map(|step_id| debug.pause(debug.snapshot("debug-run", step_id, &context)).0)
+        }).unwrap().unwrap();
+        assert_eq!(ids, ["step-2#1".to_string(), "branch-b#2".to_string()]);
+
+        assert!(send_debug_action("debug-run", Some("step-2"), DebugAction::Skip).unwrap());
+        assert!(!send_debug_action("debug-run", Some("other"), DebugAction::Skip).unwrap());
+        assert!(send_debug_action("debug-run", Some("branch-b#2"), DebugAction::Step).unwrap());
+        let actions = with_debug_session("debug-run", |debug| {
+            (debug.paused["step-2#1"].action, debug.paused["branch-b#2"].action)
+        }).unwrap();
+        assert_eq!(actions, Some((Some(DebugAction::Skip), Some(DebugAction::Step))));
+
+        with_debug_session("debug-run", |debug| debug.unpause("step-2#1")).unwrap();
+        let paused_since = with_debug_session("debug-run", |debug| debug.paused_since.is_some()).unwrap();
+        assert_eq!(paused_since, Some(true));
+        with_debug_session("debug-run", |debug| debug.unpause("branch-b#2")).unwrap();
+        let paused_since = with_debug_session("debug-run", |debug| debug.paused_since.is_some()).unwrap();
+        assert_eq!(paused_since, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_debug_pause_suspends_deadline() {
+        let (_guard, _) = register_run(WorkflowRunInfo {
+            run_id: "debug-deadline".to_string(),
+            workflow_id: "wf".to_string(),
+            workflow_name: "Debug".to_string(),
+            device_id: "emulator-5554".to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            current_step_id: None,
+            cancel_requested: None,
+        }, Some(WorkflowDebugOptions::default())).unwrap();
+        with_debug_session("debug-deadline", |debug| {
+            debug.pause(debug.snapshot("debug-deadline", "step-1", &test_context()));
+        }).unwrap();
+
+        let deadline = tokio::spawn(workflow_deadline("debug-deadline", 1));
+        tokio::time::sleep(std::time::Duration::from_millis(1300)).await;
+        assert!(!deadline.is_finished());
+
+        with_debug_session("debug-deadline", |debug| debug.unpause("step-1#1")).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(3), deadline).await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        assert!(checkpoint_path("../config").is_err());
+        assert!(checkpoint_path("run-1").unwrap().ends_with("checkpoints/run-1.json"));
+
+        let checkpoint: WorkflowCheckpoint = serde_json::from_value(serde_json::json!({
+            "runId": "run-1",
+            "workflow": {"id": "wf", "name": "Watch", "inputs": [], "outputs": [], "steps": [
+                {"id": "open", "type": "action", "action": "open_app"},
+                {"id": "watch", "type": "loop", "count": "30", "body": []},
+            ]},
+            "deviceId": "emulator-5554",
+            "inputs": {"video_count": 30},
+            "stepIndex": 1,
+            "stepId": "watch",
+            "variables": {"i": 12},
+            "loopCounters": {"watch": 12},
+            "status": "failed",
+            "error": "DroidRun action failed",
+            "updatedAt": "2026-01-01T00:00:00Z",
+        })).unwrap();
+        assert_eq!(checkpoint.loop_counters.get("watch"), Some(&12));
+        assert!(checkpoint.history.is_empty());
+        assert_eq!(checkpoint.workflow.steps[checkpoint.step_index].id, "watch");
+    }
+
+    #[tokio::test]
+    async fn test_resume_loop_from_checkpoint() {
+        let (_app, window) = test_window();
+        let checkpoint: WorkflowCheckpoint = serde_json::from_value(serde_json::json!({
+            "runId": "resume-run",
+            "workflow": {"id": "wf", "name": "Watch", "inputs": [], "outputs": [], "steps": [
+                {"id": "open", "type": "set", "set": [{"name": "opened", "value": true}]},
+                {"id": "watch", "type": "loop", "count": "5", "body": [
+                    {"id": "seen", "type": "set", "set": [{"name": "seen", "append": "{{i}}"}]},
+                ]},
+            ]},
+            "deviceId": "emulator-5554",
+            "inputs": {},
+            "stepIndex": 1,
+            "stepId": "watch",
+            "variables": {"i": 3, "seen": [0, 1, 2]},
+            "loopCounters": {"watch": 3},
+            "status": "failed",
+            "error": "DroidRun action failed",
+            "updatedAt": "2026-01-01T00:00:00Z",
+        })).unwrap();
+
+        // Resuming the interrupted step continues the loop at the saved iteration
+        let (workflow, mut context) = resume_context(checkpoint.clone(), 1);
+        execute_step(&window, &workflow.steps[1], &mut context).await.unwrap();
+        assert_eq!(context.variables["seen"], serde_json::json!([0, 1, 2, 3, 4]));
+        assert!(context.logs.iter().any(|log| log.message == "⏩ Resuming loop at iteration 4/5"));
+        assert!(context.loop_counters.is_empty());
+
+        // Resuming from another step starts its loops over
+        let (workflow, mut context) = resume_context(checkpoint, 0);
+        assert!(context.loop_counters.is_empty());
+        context.variables.remove("seen");
+        execute_step(&window, &workflow.steps[1], &mut context).await.unwrap();
+        assert_eq!(context.variables["seen"].as_array().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_resume_keeps_failed_record() {
+        let (_app, window) = test_window();
+        let workflow: WorkflowDefinition = serde_json::from_value(serde_json::json!({
+            "id": "resume-history", "name": "Resume", "inputs": [], "outputs": [], "steps": [
+                {"id": "fail", "type": "set", "set": [{"name": "n", "increment": "abc"}]},
+                {"id": "done", "type": "set", "set": [{"name": "done", "value": true}]},
+            ],
+        })).unwrap();
+        let mut context = test_context();
+        context.run_id = format!("resume-history-{}", std::process::id());
+        let run_id = context.run_id.clone();
+
+        let failed = execute_workflow_run(window.clone(), workflow, context, 0, false, None).await.unwrap();
+        assert_eq!(failed.status, "failed");
+        let resumed = resume_run(window, run_id.clone(), Some("done".to_string()), None).await.unwrap();
+        assert_eq!((resumed.status.as_str(), resumed.run_id.as_str()), ("completed", run_id.as_str()));
+
+        let query = crate::run_history::HistoryQuery { workflow_id: Some("resume-history".to_string()), ..Default::default() };
+        let mut records = crate::run_history::list_run_history(Some(query)).await.unwrap();
+        records.sort_by_key(|record| record.resumed_from.is_some());
+        let attempts: Vec<(&str, Option<&str>)> = records.iter()
+            .map(|record| (record.status.as_str(), record.resumed_from.as_deref()))
+            .collect();
+        assert_eq!(attempts, vec![("failed", None), ("completed", Some(run_id.as_str()))]);
+        assert_eq!(records[0].run_id, run_id);
+        assert_ne!(records[1].run_id, run_id);
+        let _ = delete_workflow_checkpoint(run_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_restarts_loop() {
+        let (_app, window) = test_window();
+        let mut context = test_context();
+        context.variables.insert("recovered".to_string(), serde_json::json!(0));
+        let step = test_step(serde_json::json!({
+            "id": "watch",
+            "type": "loop",
+            "count": "3",
+            "body": [
+                {"id": "seen", "type": "set", "set": [{"name": "seen", "append": "{{i}}"}]},
+                {"id": "check", "type": "condition", "condition": "{{i}} == 1 && {{recovered}} == 0", "then": [
+                    {"id": "fail", "type": "set", "set": [{"name": "n", "increment": "abc"}]},
+                ]},
+            ],
+            "onError": {
+                "strategy": "retry",
+                "retries": 1,
+                "retryDelay": 0,
+                "recovery": [{"id": "recover", "type": "set", "set": [{"name": "recovered", "value": 1}]}],
+            },
+        }));
+
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.variables["seen"], serde_json::json!([0, 1, 0, 1, 2]));
+        assert!(context.loop_counters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_foreach_sequential() {
+        let (_app, window) = test_window();
+        let mut context = test_context();
+        context.variables.insert("users".to_string(), serde_json::json!(["a", "b", "c"]));
+        let step = test_step(serde_json::json!({
+            "id": "each",
+            "type": "foreach",
+            "items": "{{users}}",
+            "variable": "user",
+            "indexVariable": "n",
+            "body": [{"id": "seen", "type": "set", "set": [{"name": "seen", "append": "{{n}}:{{user}}"}]}],
+        }));
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.variables["seen"], serde_json::json!(["0:a", "1:b", "2:c"]));
+        assert!(context.loop_counters.is_empty());
+
+        // Python steps hand arrays over as JSON text
+        let mut context = test_context();
+        context.variables.insert("raw".to_string(), serde_json::json!("[2, 3]"));
+        let mut step = step;
+        step.items = Some("{{raw}}".to_string());
+        step.body = Some(vec![test_step(serde_json::json!({"id": "sum", "type": "set", "set": [{"name": "total", "increment": "{{user}}"}]}))]);
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.variables["total"], 5);
+
+        context.variables.insert("raw".to_string(), serde_json::json!("not a list"));
+        let error = execute_step(&window, &step, &mut context).await.unwrap_err();
+        assert_eq!(error, "Foreach items must be an array, got \"not a list\"");
+    }
+
+    #[tokio::test]
+    async fn test_foreach_concurrent() {
+        let (_app, window) = test_window();
+        let mut context = test_context();
+        context.variables.insert("delays".to_string(), serde_json::json!([100, 100, 100, 100]));
+        // Progress of an earlier sequential attempt does not apply
+        context.loop_counters.insert("each".to_string(), 2);
+        let step = test_step(serde_json::json!({
+            "id": "each",
+            "type": "foreach",
+            "items": "{{delays}}",
+            "concurrency": 2,
+            "body": [
+                {"id": "watch", "type": "wait", "duration": "{{item}}"},
+                {"id": "last", "type": "set", "set": [{"name": "last_index", "value": "{{index}}"}]},
+            ],
+        }));
+
+        let started = std::time::Instant::now();
+        execute_step(&window, &step, &mut context).await.unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed >= std::time::Duration::from_millis(190) && elapsed < std::time::Duration::from_millis(390), "{:?}", elapsed);
+        assert!(context.variables.contains_key("last_index"));
+        assert!(!context.variables.contains_key("item") && !context.variables.contains_key("index"));
+        assert!(context.loop_counters.is_empty());
+        assert!(context.logs.iter().any(|log| log.message.contains("Concurrent foreach keeps no progress")));
+
+        // A failing iteration stops the others and names the item
+        context.variables.insert("delays".to_string(), serde_json::json!([10, 10, "abc", 10]));
+        let mut step = step;
+        step.body = Some(vec![test_step(serde_json::json!({"id": "sum", "type": "set", "set": [{"name": "n", "increment": "{{item}}"}]}))]);
+        let error = execute_step(&window, &step, &mut context).await.unwrap_err();
+        assert!(error.starts_with("Foreach iteration 3 failed: Set 'n' in step 'sum'"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_foreach_concurrent_breakpoint() {
+        let (_app, window) = test_window();
+        let (_guard, _) = register_run(WorkflowRunInfo {
+            run_id: "debug-foreach".to_string(),
+            workflow_id: "wf".to_string(),
+            workflow_name: "Debug".to_string(),
+            device_id: "emulator-5554".to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            current_step_id: None,
+            cancel_requested: None,
+        }, Some(WorkflowDebugOptions { breakpoints: vec!["watch".to_string()], pause_on_start: false })).unwrap();
+        let mut context = test_context();
+        context.run_id = "debug-foreach".to_string();
+        context.variables.insert("users".to_string(), serde_json::json!(["a", "b"]));
+        let step = test_step(serde_json::json!({
+            "id": "each",
+            "type": "foreach",
+            "items": "{{users}}",
+            "concurrency": 2,
+            "body": [{"id": "watch", "type": "set", "set": [{"name": "seen", "append": "{{item}}"}]}],
+        }));
+
+        let drive = async {
+            // Both iterations pause at the same step id with their own pause id
+            let states = loop {
+                let states = get_workflow_debug_state("debug-foreach".to_string()).await.unwrap();
+                if states.len() == 2 {
+                    break states;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            };
+            assert!(states.iter().all(|state| state.paused_at.as_deref() == Some("watch")));
+            assert_ne!(states[0].pause_id, states[1].pause_id);
+
+            // Resuming one iteration leaves the other paused
+            assert!(continue_workflow("debug-foreach".to_string(), Some(states[0].pause_id.clone())).await.unwrap());
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            let remaining = get_workflow_debug_state("debug-foreach".to_string()).await.unwrap();
+            assert_eq!(remaining.len(), 1);
+            assert_eq!(remaining[0].pause_id, states[1].pause_id);
+            assert!(continue_workflow("debug-foreach".to_string(), Some("watch".to_string())).await.unwrap());
+        };
+        let (result, _) = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            async { tokio::join!(execute_step(&window, &step, &mut context), drive) },
+        ).await.unwrap();
+        result.unwrap();
+        assert_eq!(context.variables["seen"].as_array().map(|seen| seen.len()), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_break_and_continue() {
+        let (_app, window) = test_window();
+        let mut context = test_context();
+        let step = test_step(serde_json::json!({
+            "id": "loop",
+            "type": "loop",
+            "count": "5",
+            "body": [
+                {"id": "skip-odd", "type": "condition", "condition": "{{i}} == 1", "then": [
+                    {"id": "next", "type": "continue"},
+                ]},
+                {"id": "guard", "type": "try", "body": [
+                    {"id": "stop", "type": "break", "condition": "{{i}} == 3"},
+                ]},
+                {"id": "seen", "type": "set", "set": [{"name": "seen", "append": "{{i}}"}]},
+            ],
+        }));
+
+        assert_eq!(execute_step(&window, &step, &mut context).await, Ok(StepOutcome::Done));
+        assert_eq!(context.variables["seen"], serde_json::json!([0, 2]));
+        assert!(context.failed_step_id.is_none());
+
+        // Outside of a loop the signal reaches the run and fails it
+        let step = test_step(serde_json::json!({"id": "stop", "type": "break"}));
+        let outcome = execute_step(&window, &step, &mut test_context()).await;
+        assert_eq!(outcome, Ok(StepOutcome::Break));
+        assert_eq!(outcome.and_then(StepOutcome::outside_loop).unwrap_err(), "'break' step used outside of a loop");
+    }
+
+    #[tokio::test]
+    async fn test_try_catch_finally() {
+        let (_app, window) = test_window();
+        let step = test_step(serde_json::json!({
+            "id": "guard",
+            "type": "try",
+            "body": [
+                {"id": "before", "type": "set", "set": [{"name": "before", "value": true}]},
+                {"id": "fail", "type": "set", "set": [{"name": "n", "increment": "abc"}]},
+                {"id": "after", "type": "set", "set": [{"name": "after", "value": true}]},
+            ],
+            "catch": [{"id": "report", "type": "set", "set": [{"name": "caught", "value": "{{error.step_id}}"}]}],
+            "finally": [{"id": "cleanup", "type": "set", "set": [{"name": "cleaned", "value": true}]}],
+        }));
+
+        let mut context = test_context();
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.variables["before"], true);
+        assert!(!context.variables.contains_key("after"));
+        assert_eq!(context.variables["caught"], "fail");
+        assert_eq!(context.variables["cleaned"], true);
+        assert!(context.failed_step_id.is_none());
+
+        // Without catch the error goes on after finally ran
+        let mut step = step;
+        step.catch = None;
+        let mut context = test_context();
+        let error = execute_step(&window, &step, &mut context).await.unwrap_err();
+        assert!(error.contains("increment \"abc\" is not a number"), "{}", error);
+        assert_eq!(context.variables["cleaned"], true);
+        assert_eq!(context.failed_step_id.as_deref(), Some("fail"));
+    }
+
+    #[tokio::test]
+    async fn test_finally_runs_after_cancel() {
+        let (_app, window) = test_window();
+        let (_guard, _) = register_run(WorkflowRunInfo {
+            run_id: "try-cancel".to_string(),
+            workflow_id: "wf".to_string(),
+            workflow_name: "Cancel".to_string(),
+            device_id: "emulator-5554".to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            current_step_id: None,
+            cancel_requested: None,
+        }, None).unwrap();
+        let mut context = test_context();
+        context.run_id = "try-cancel".to_string();
+        let step = test_step(serde_json::json!({
+            "id": "guard",
+            "type": "try",
+            "body": [
+                {"id": "watch", "type": "wait", "duration": "200"},
+                {"id": "after", "type": "set", "set": [{"name": "after", "value": true}]},
+            ],
+            "catch": [{"id": "report", "type": "set", "set": [{"name": "caught", "value": true}]}],
+            "finally": [{"id": "cleanup", "type": "condition", "condition": "true", "then": [
+                {"id": "close", "type": "set", "set": [{"name": "cleaned", "value": true}]},
+            ]}],
+        }));
+
+        tokio::spawn(async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            cancel_workflow("try-cancel".to_string(), None).await.unwrap();
+        });
+        let error = execute_step(&window, &step, &mut context).await.unwrap_err();
+        assert_eq!(error, "Workflow cancelled");
+        assert!(!context.variables.contains_key("after"));
+        assert!(!context.variables.contains_key("caught"));
+        assert_eq!(context.variables["cleaned"], true);
+        assert!(!context.in_finally);
+    }
+
+    #[tokio::test]
+    async fn test_handled_error_status() {
+        let (_app, window) = test_window();
+        let mut context = test_context();
+        let step = test_step(serde_json::json!({
+            "id": "group",
+            "type": "condition",
+            "condition": "true",
+            "then": [{"id": "fail", "type": "set", "set": [{"name": "n", "increment": "abc"}]}],
+            "onError": {"strategy": "skip"},
+        }));
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert!(context.failed_step_id.is_none());
+        let handled = context.handled_error.take().unwrap();
+        assert_eq!((handled.step_id.as_str(), handled.status.as_str()), ("group", "skipped"));
+        assert!(handled.error.contains("Set 'n' in step 'fail'"), "{}", handled.error);
+
+        let mut step = step;
+        step.on_error = serde_json::from_value(serde_json::json!({
+            "strategy": "fallback",
+            "fallback": [{"id": "back", "type": "set", "set": [{"name": "fell_back", "value": true}]}],
+        })).unwrap();
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.variables["fell_back"], true);
+        assert_eq!(context.handled_error.map(|handled| handled.status), Some("recovered".to_string()));
+    }
+
+    #[test]
+    fn test_prune_checkpoints() {
+        let dir = std::env::temp_dir().join(format!("mun-checkpoints-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for index in 0..MAX_CHECKPOINTS + 3 {
+            std::fs::write(dir.join(format!("run-{}.json", index)), "{}").unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "keep").unwrap();
+
+        prune_checkpoints(&dir);
+        let remaining = std::fs::read_dir(&dir).unwrap().flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .count();
+        assert_eq!(remaining, MAX_CHECKPOINTS);
+        assert!(dir.join("notes.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_retry_delay_backoff() {
+        let mut config: ErrorConfig = serde_json::from_value(serde_json::json!({
+            "strategy": "retry",
+            "retryDelay": 500,
+            "backoff": 2.0,
+            "maxDelay": 3000,
+        })).unwrap();
+        let delays: Vec<u64> = (1..=5).map(|attempt| retry_delay_ms(&config, attempt)).collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 3000, 3000]);
+        
+        config.jitter = Some(0.5);
+        for _ in 0..20 {
+            let delay = retry_delay_ms(&config, 2);
+            assert!((500..=1500).contains(&delay), "{}", delay);
+        }
+        assert_eq!(retry_delay_ms(&ErrorConfig { retry_delay: None, jitter: None, ..config }, 3), 3000);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_join_and_merge() {
+        let (_app, window) = test_window();
+        let set = |id: &str, name: &str, value: i64| serde_json::json!({"id": id, "type": "set", "set": [{"name": name, "value": value}]});
+        let wait = |id: &str, ms: u64| serde_json::json!({"id": id, "type": "wait", "duration": ms.to_string()});
+        let fail = serde_json::json!({"id": "boom", "type": "set", "set": [{"name": "n", "increment": "abc"}]});
+
+        // "last_writer" merges in completion order, "namespace" keeps one object per branch
+        let mut context = test_context();
+        let step = test_step(serde_json::json!({"id": "p", "type": "parallel", "branches": [
+            [wait("w1", 50), set("s1", "x", 1)],
+            [set("s2", "x", 2), set("s3", "y", 2)],
+        ]}));
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.variables["x"], serde_json::json!(1));
+        assert_eq!(context.variables["y"], serde_json::json!(2));
+
+        let mut context = test_context();
+        let step = test_step(serde_json::json!({"id": "p", "type": "parallel", "mergeStrategy": "namespace", "saveTo": "branches", "branches": [
+            [set("s1", "x", 1)],
+            [set("s2", "x", 2)],
+        ]}));
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.variables["branches"], serde_json::json!([{"x": 1}, {"x": 2}]));
+        assert!(!context.variables.contains_key("x"));
+
+        // fail_fast (default) drops the slow branch, failFast: false lets it finish
+        for fail_fast in [true, false] {
+            let mut context = test_context();
+            let step = test_step(serde_json::json!({"id": "p", "type": "parallel", "failFast": fail_fast, "branches": [
+                [fail.clone()],
+                [wait("w1", 100), set("s1", "slow", 1)],
+            ]}));
+            let error = execute_step(&window, &step, &mut context).await.unwrap_err();
+            assert!(error.starts_with("Parallel step failed: branch 1: Set 'n' in step 'boom'"), "{}", error);
+            assert_eq!(context.variables.contains_key("slow"), !fail_fast);
+        }
+
+        // join: "first_success" ignores failures and stops at the first branch that succeeds
+        let mut context = test_context();
+        let step = test_step(serde_json::json!({"id": "p", "type": "parallel", "join": "first_success", "branches": [
+            [fail.clone()],
+            [wait("w1", 20), set("s1", "fast", 1)],
+            [wait("w2", 5000), set("s2", "slow", 1)],
+        ]}));
+        let started = std::time::Instant::now();
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!(context.variables["fast"], serde_json::json!(1));
+        assert!(!context.variables.contains_key("slow"));
+
+        let step = test_step(serde_json::json!({"id": "p", "type": "parallel", "join": "first_success", "branches": [[fail.clone()], [fail]]}));
+        let error = execute_step(&window, &step, &mut test_context()).await.unwrap_err();
+        assert!(error.starts_with("All parallel branches failed: branch "), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_step_timeout() {
+        let (_app, window) = test_window();
+        let mut context = test_context();
+        let step = test_step(serde_json::json!({"id": "slow", "type": "wait", "duration": "5000", "timeout": 1}));
+        let started = std::time::Instant::now();
+        let error = execute_step(&window, &step, &mut context).await.unwrap_err();
+        assert_eq!(error, "Step 'slow' timed out after 1s");
+        assert!(started.elapsed() < std::time::Duration::from_secs(3));
+        assert_eq!(context.timed_out_step.as_deref(), Some("slow"));
+        assert_eq!(context.failed_step_id.as_deref(), Some("slow"));
+
+        // Once skipped, the timeout no longer shows up in the result
+        let mut context = test_context();
+        let step = test_step(serde_json::json!({"id": "slow", "type": "wait", "duration": "5000", "timeout": 1, "onError": {"strategy": "skip"}}));
+        execute_step(&window, &step, &mut context).await.unwrap();
+        assert_eq!(context.timed_out_step, None);
+        assert!(context.logs.iter().any(|log| log.message == "Skipping failed step: Step 'slow' timed out after 1s"));
+    }
+}
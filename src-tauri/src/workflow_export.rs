@@ -0,0 +1,1861 @@
+// Workflow Export Module - Compile a workflow definition into a standalone Python script
+// Script chạy độc lập không cần app: input qua tham số CLI, action gọi droidrun_executor.py giống run_workflow
+
+use crate::expression::{python_literal, python_str};
+use crate::template;
+use crate::workflow::{ErrorConfig, WorkflowDefinition, WorkflowInput, WorkflowStep};
+use serde_json::Value;
+use std::path::PathBuf;
+use tauri::command;
+
+/// Step types that only run inside the app (agent, scripter, Portal client, workflow library)
+const APP_ONLY_STEPS: &[&str] = &["prompt", "skill", "call", "python", "scripter", "parallel", "wait_for", "extract"];
+
+/// CLI flags used by the script itself - inputs with these names get an `--input-` prefix
+const RESERVED_FLAGS: &[&str] = &["device", "executor", "python", "help", "h"];
+
+// ============================================
+// Commands
+// ============================================
+
+/// Compile a workflow into a Python script; with `output_path` the script is also written there,
+/// next to a copy of droidrun_executor.py
+#[command]
+pub async fn export_workflow_python(
+    workflow: WorkflowDefinition,
+    output_path: Option<String>,
+) -> Result<String, String> {
+    let script = workflow_to_python(&workflow)?;
+
+    if let Some(path) = output_path.filter(|p| !p.trim().is_empty()).map(PathBuf::from) {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&path, &script).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+
+        // The script looks for the executor in its own folder first
+        let target = path.with_file_name("droidrun_executor.py");
+        if let (false, Some(executor)) = (target.exists(), find_executor()) {
+            if let Err(e) = std::fs::copy(&executor, &target) {
+                println!("[EXPORT] Cannot copy {}: {}", executor.display(), e);
+            }
+        }
+        println!("[EXPORT] Exported workflow '{}' to {}", workflow.name, path.display());
+    }
+
+    Ok(script)
+}
+
+fn find_executor() -> Option<PathBuf> {
+    let exe_dir = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|d| d.to_path_buf()));
+    let mut candidates: Vec<PathBuf> = exe_dir.iter()
+        .flat_map(|dir| [dir.join("droidrun_executor.py"), dir.join("../droidrun_executor.py")])
+        .collect();
+    candidates.push(PathBuf::from("src-tauri/droidrun_executor.py"));
+    candidates.push(PathBuf::from("droidrun_executor.py"));
+    candidates.into_iter().find(|p| p.exists())
+}
+
+// ============================================
+// Compiler
+// ============================================
+
+/// Compile a workflow into a self-contained Python script that runs it like run_workflow
+pub fn workflow_to_python(workflow: &WorkflowDefinition) -> Result<String, String> {
+    let mut exporter = PythonExporter::default();
+    let top_level: Vec<String> = workflow.steps.iter()
+        .map(|step| {
+            let function = exporter.step(step);
+            // Only top-level actions wait afterwards, like execute_workflow_run
+            let delay = match step.step_type.as_str() {
+                "action" => step.delay_after.or(workflow.step_delay).unwrap_or(800).to_string(),
+                _ => "None".to_string(),
+            };
+            format!("    ({}, {}, {}),", function, python_str(step.name.as_deref().unwrap_or(&step.step_type)), delay)
+        })
+        .collect();
+
+    if !exporter.unsupported.is_empty() {
+        return Err(format!(
+            "Cannot export '{}' to Python, these steps only run in the app: {}",
+            workflow.name,
+            exporter.unsupported.join(", ")
+        ));
+    }
+
+    let flags = input_flags(&workflow.inputs)?;
+    let specs = serde_json::to_value(&workflow.inputs).map_err(|e| e.to_string())?;
+    let outputs: Vec<Value> = workflow.outputs.iter().map(|o| Value::String(o.clone())).collect();
+    let timeout = workflow.timeout.filter(|t| *t > 0).map(|t| t.to_string()).unwrap_or_else(|| "None".to_string());
+
+    let mut script = header(workflow, &flags);
+    script.push_str(&format!("WORKFLOW_ID = {}\n", python_str(&workflow.id)));
+    script.push_str(&format!("WORKFLOW_NAME = {}\n", python_str(&workflow.name)));
+    script.push_str(&format!("DESCRIPTION = {}\n", python_str(workflow.description.as_deref().unwrap_or(&workflow.name))));
+    script.push_str(&format!("INPUT_SPECS = {}\n", python_literal(&specs)));
+    script.push_str(&format!("OUTPUTS = {}\n", python_literal(&Value::Array(outputs))));
+    script.push_str(&format!("TIMEOUT = {}\n", timeout));
+    script.push_str(PYTHON_RUNTIME);
+
+    script.push_str("\n\n# ============================================\n# Steps\n# ============================================\n");
+    for function in &exporter.functions {
+        script.push_str("\n\n");
+        script.push_str(function);
+    }
+    script.push_str(&format!("\n\nWORKFLOW_STEPS = [\n{}\n]\n", top_level.join("\n")));
+
+    script.push_str("\n\ndef add_input_arguments(parser):\n");
+    script.push_str("    group = parser.add_argument_group(\"workflow inputs\")\n");
+    for (input, flag) in workflow.inputs.iter().zip(&flags) {
+        script.push_str(&format!("    {}\n", input_argument(input, flag)));
+    }
+    script.push_str(PYTHON_MAIN);
+    Ok(script)
+}
+
+#[derive(Default)]
+struct PythonExporter {
+    /// Generated step functions, children before their parents (decorators reference them)
+    functions: Vec<String>,
+    count: usize,
+    unsupported: Vec<String>,
+}
+
+impl PythonExporter {
+    /// Emit the function for one step and return its name
+    fn step(&mut self, step: &WorkflowStep) -> String {
+        self.count += 1;
+        let name = format!("step_{}", self.count);
+        let id = python_str(&step.id);
+        let fail = |message: String| format!("raise WorkflowError({})", python_str(&message));
+        let mut body: Vec<String> = Vec::new();
+
+        match step.step_type.as_str() {
+            "action" => match &step.action {
+                Some(action) => {
+                    let mut params: Vec<(&String, &Value)> = step.params.iter().flatten().collect();
+                    params.sort_by_key(|(key, _)| key.as_str());
+                    if matches!(action.as_str(), "tap_element" | "tap_text")
+                        && (step.selector.is_some() || params.iter().any(|(key, _)| *key == "selector"))
+                    {
+                        self.unsupported.push(format!("{} (tap_element with selector)", step.id));
+                    }
+                    // Non-string params are passed as their JSON text, like execute_action_step
+                    let params: Vec<String> = params.into_iter()
+                        .map(|(key, value)| {
+                            let value = match value {
+                                Value::String(s) => template::to_python(s, false),
+                                other => python_str(&other.to_string()),
+                            };
+                            format!("{}: {}", python_str(key), value)
+                        })
+                        .collect();
+                    body.push(format!("action({}, {{{}}})", python_str(action), params.join(", ")));
+                }
+                None => body.push(fail("Action step missing 'action' field".to_string())),
+            },
+            "condition" => match &step.condition {
+                Some(condition) => {
+                    let then = step.then.as_deref().map(|steps| self.steps(steps));
+                    let otherwise = step.else_branch.as_deref().map(|steps| self.steps(steps));
+                    body.push(format!("if condition({}, {}):", id, expression_thunk(condition)));
+                    body.push(format!("    run_steps({})", then.as_deref().unwrap_or("[]")));
+                    if let Some(otherwise) = otherwise {
+                        body.push("else:".to_string());
+                        body.push(format!("    run_steps({})", otherwise));
+                    }
+                }
+                None => body.push(fail("Condition step missing 'condition' field".to_string())),
+            },
+            "loop" => match (&step.count, &step.body) {
+                (Some(count), steps) => {
+                    body.push(format!("count = loop_count({})", template::to_python(count, false)));
+                    match steps {
+                        Some(steps) => {
+                            let steps = self.steps(steps);
+                            body.push("for iteration in range(count):".to_string());
+                            body.push(format!("    VARIABLES[{}] = iteration", python_str(step.variable.as_deref().unwrap_or("i"))));
+                            body.push(format!("    if not run_body({}):", steps));
+                            body.push("        break".to_string());
+                        }
+                        None => body.push(fail("Loop step missing 'body' field".to_string())),
+                    }
+                }
+                (None, _) => body.push(fail("Loop step missing 'count' field".to_string())),
+            },
+            "foreach" => match (&step.items, &step.body) {
+                (Some(items), steps) => {
+                    if step.concurrency.unwrap_or(1) > 1 {
+                        self.unsupported.push(format!("{} (foreach with concurrency)", step.id));
+                    }
+                    body.push(format!("items = foreach_items({})", template::to_python(items, true)));
+                    match steps {
+                        Some(steps) => {
+                            let steps = self.steps(steps);
+                            body.push("for index, item in enumerate(items):".to_string());
+                            body.push(format!("    VARIABLES[{}] = item", python_str(step.variable.as_deref().unwrap_or("item"))));
+                            body.push(format!("    VARIABLES[{}] = index", python_str(step.index_variable.as_deref().unwrap_or("index"))));
+                            body.push(format!("    if not run_body({}):", steps));
+                            body.push("        break".to_string());
+                        }
+                        None => body.push(fail("Foreach step missing 'body' field".to_string())),
+                    }
+                }
+                (None, _) => body.push(fail("Foreach step missing 'items' field".to_string())),
+            },
+            "while" => match (&step.condition, &step.body) {
+                (Some(condition), Some(steps)) => {
+                    let steps = self.steps(steps);
+                    body.push("iteration = 0".to_string());
+                    body.push(format!(
+                        "while iteration < {} and condition({}, {}):",
+                        step.max_iterations.unwrap_or(100), id, expression_thunk(condition)
+                    ));
+                    body.push(format!("    if not run_body({}):", steps));
+                    body.push("        break".to_string());
+                    body.push("    iteration += 1".to_string());
+                }
+                (None, _) => body.push(fail("While step missing 'condition' field".to_string())),
+                (_, None) => body.push(fail("While step missing 'body' field".to_string())),
+            },
+            "break" | "continue" => {
+                let signal = if step.step_type == "break" { "raise Break()" } else { "raise Continue()" };
+                match &step.condition {
+                    Some(condition) => {
+                        body.push(format!("if condition({}, {}):", id, expression_thunk(condition)));
+                        body.push(format!("    {}", signal));
+                    }
+                    None => body.push(signal.to_string()),
+                }
+            }
+            "try" => match &step.body {
+                Some(steps) => {
+                    let steps = self.steps(steps);
+                    let catch = step.catch.as_deref().map(|s| self.steps(s)).unwrap_or_else(|| "None".to_string());
+                    let finally = self.steps(step.finally.as_deref().unwrap_or_default());
+                    body.push(format!(
+                        "run_try({}, {}, {}, {}, {})",
+                        id, python_str(step.variable.as_deref().unwrap_or("error")), steps, catch, finally
+                    ));
+                }
+                None => body.push(fail("Try step missing 'body' field".to_string())),
+            },
+            "set" => match &step.set {
+                Some(operations) => {
+                    for operation in operations {
+                        let name = python_str(&operation.name);
+                        let values = [
+                            ("value", &operation.value),
+                            ("increment", &operation.increment),
+                            ("append", &operation.append),
+                            ("merge", &operation.merge),
+                        ];
+                        let given: Vec<(&str, &Value)> = values.iter()
+                            .filter_map(|(kind, value)| value.as_ref().map(|v| (*kind, v)))
+                            .collect();
+                        body.push(match (given.as_slice(), &operation.expression) {
+                            ([(kind, value)], None) => {
+                                format!("set_var({}, {}, \"{}\", lambda: {})", id, name, kind, json_to_python(value))
+                            }
+                            ([], Some(expression)) => format!(
+                                "set_var({}, {}, \"expression\", {}, {})",
+                                id, name, expression_thunk(expression), python_str(expression)
+                            ),
+                            _ => format!("set_var({}, {}, \"invalid\", None)", id, name),
+                        });
+                    }
+                    if operations.is_empty() {
+                        body.push("pass".to_string());
+                    }
+                }
+                None => body.push(fail("Set step missing 'set' field".to_string())),
+            },
+            "wait" => match (&step.duration, &step.wait_condition) {
+                (Some(duration), _) => body.push(format!("wait_ms({})", template::to_python(duration, false))),
+                (None, Some(condition)) => body.push(format!(
+                    "wait_until({}, {}, {})",
+                    id, expression_thunk(condition), python_str(condition)
+                )),
+                (None, None) => body.push("sleep_ms(1000)".to_string()),
+            },
+            other if APP_ONLY_STEPS.contains(&other) => {
+                self.unsupported.push(format!("{} ({})", step.id, other));
+                body.push(fail(format!("Step type '{}' only runs in the app", other)));
+            }
+            other => body.push(fail(format!("Unknown step type: {}", other))),
+        }
+
+        let mut args = vec![id];
+        if let Some(config) = &step.on_error {
+            args.push(format!("on_error={}", self.error_config(config)));
+        }
+        if let Some(secs) = step.timeout.filter(|s| *s > 0) {
+            args.push(format!("timeout={}", secs));
+        }
+
+        let title = format!("{} ({})", step.id, step.step_type);
+        let title = match &step.name {
+            Some(label) => format!("{}: {}", title, label),
+            None => title,
+        };
+        let mut function = format!(
+            "# {}\n@step({})\ndef {}():\n",
+            title.replace(['\r', '\n'], " "),
+            args.join(", "),
+            name
+        );
+        for line in body {
+            function.push_str(&format!("    {}\n", line));
+        }
+        self.functions.push(function);
+        name
+    }
+
+    fn steps(&mut self, steps: &[WorkflowStep]) -> String {
+        let names: Vec<String> = steps.iter().map(|step| self.step(step)).collect();
+        format!("[{}]", names.join(", "))
+    }
+
+    fn error_config(&mut self, config: &ErrorConfig) -> String {
+        let mut entries = vec![format!("\"strategy\": {}", python_str(&config.strategy))];
+        if let Some(retries) = config.retries {
+            entries.push(format!("\"retries\": {}", retries));
+        }
+        if let Some(delay) = config.retry_delay {
+            entries.push(format!("\"retryDelay\": {}", delay));
+        }
+        if let Some(backoff) = config.backoff {
+            entries.push(format!("\"backoff\": {:?}", backoff));
+        }
+        if let Some(max_delay) = config.max_delay {
+            entries.push(format!("\"maxDelay\": {}", max_delay));
+        }
+        if let Some(jitter) = config.jitter {
+            entries.push(format!("\"jitter\": {:?}", jitter));
+        }
+        if let Some(pattern) = &config.retry_on {
+            entries.push(format!("\"retryOn\": {}", python_str(pattern)));
+        }
+        if let Some(steps) = &config.fallback {
+            entries.push(format!("\"fallback\": {}", self.steps(steps)));
+        }
+        if let Some(steps) = &config.recovery {
+            entries.push(format!("\"recovery\": {}", self.steps(steps)));
+        }
+        format!("{{{}}}", entries.join(", "))
+    }
+}
+
+/// Invalid expressions still export - they fail when evaluated, like in the app
+fn expression_thunk(source: &str) -> String {
+    match crate::expression::to_python(source) {
+        Ok(code) => format!("lambda: {}", code),
+        Err(e) => format!("lambda: _invalid({})", python_str(&e.to_string())),
+    }
+}
+
+/// Python for a `set` value: strings are templates that keep their JSON type
+fn json_to_python(value: &Value) -> String {
+    match value {
+        Value::String(s) => template::to_python(s, true),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(json_to_python).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<String> = map.iter()
+                .map(|(key, value)| format!("{}: {}", python_str(key), json_to_python(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        other => python_literal(other),
+    }
+}
+
+// ============================================
+// Inputs
+// ============================================
+
+/// `--video-count` for `video_count`; names clashing with the script's own flags get `--input-`
+fn input_flags(inputs: &[WorkflowInput]) -> Result<Vec<String>, String> {
+    let mut flags: Vec<String> = Vec::new();
+    for input in inputs {
+        let slug: String = input.name.to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let slug = slug.split('-').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("-");
+        if slug.is_empty() {
+            return Err(format!("Input '{}' has no usable name for a command line flag", input.name));
+        }
+        let flag = if RESERVED_FLAGS.contains(&slug.as_str()) {
+            format!("--input-{}", slug)
+        } else {
+            format!("--{}", slug)
+        };
+        if let Some(index) = flags.iter().position(|f| *f == flag) {
+            return Err(format!("Inputs '{}' and '{}' both map to {}", inputs[index].name, input.name, flag));
+        }
+        flags.push(flag);
+    }
+    Ok(flags)
+}
+
+/// Values stay strings on the command line, prepare_inputs in the script coerces them
+fn input_argument(input: &WorkflowInput, flag: &str) -> String {
+    let mut details = vec![input.input_type.clone()];
+    if let Some(options) = input.options.as_ref().filter(|o| !o.is_empty()) {
+        let values: Vec<&str> = options.iter().map(|o| o.value.as_str()).collect();
+        details.push(format!("one of {}", values.join(", ")));
+    }
+    match (input.min, input.max) {
+        (Some(min), Some(max)) => details.push(format!("{}..{}", min, max)),
+        (Some(min), None) => details.push(format!(">= {}", min)),
+        (None, Some(max)) => details.push(format!("<= {}", max)),
+        (None, None) => {}
+    }
+    match input.default.as_ref().filter(|v| !v.is_null()) {
+        Some(default) => details.push(format!("default: {}", crate::expression::to_display_string(default))),
+        None if input.required.unwrap_or(true) => details.push("required".to_string()),
+        None => {}
+    }
+
+    let label = input.label.as_deref().unwrap_or(&input.name);
+    let help = format!("{} ({})", label, details.join(", ")).replace('%', "%%");
+    format!(
+        "group.add_argument({}, dest={}, metavar={}, help={})",
+        python_str(flag),
+        python_str(&format!("inputs.{}", input.name)),
+        python_str(&input.input_type.to_uppercase()),
+        python_str(&help)
+    )
+}
+
+fn header(workflow: &WorkflowDefinition, flags: &[String]) -> String {
+    let one_line = |text: &str| text.replace(['\r', '\n'], " ");
+    let mut header = String::from("#!/usr/bin/env python3\n");
+    header.push_str(&format!("# {} - exported from workflow \"{}\"\n", one_line(&workflow.name), one_line(&workflow.id)));
+    for line in workflow.description.iter().flat_map(|d| d.lines()) {
+        header.push_str(&format!("# {}\n", line));
+    }
+
+    let usage: Vec<String> = workflow.inputs.iter().zip(flags)
+        .map(|(input, flag)| {
+            let argument = format!("{} {}", flag, input.input_type.to_uppercase());
+            if input.default.as_ref().is_some_and(|v| !v.is_null()) || input.required == Some(false) {
+                format!("[{}]", argument)
+            } else {
+                argument
+            }
+        })
+        .collect();
+    let file_name = match crate::workflow_library::slugify(&workflow.name) {
+        slug if slug.is_empty() => "workflow".to_string(),
+        slug => slug,
+    };
+    header.push_str("#\n# Usage:\n");
+    header.push_str(&format!("#     python {}.py --device 127.0.0.1:5555 {}\n", file_name, usage.join(" ")).replace(" \n", "\n"));
+    header.push_str("#\n");
+    header.push_str("# Actions run through droidrun_executor.py (next to this script, or --executor) with the\n");
+    header.push_str("# same ADB fallbacks as the app. The run result is printed as JSON, exit code 1 on failure.\n\n");
+    header.push_str("import argparse\nimport json\nimport math\nimport os\nimport random\nimport re\n");
+    header.push_str("import subprocess\nimport sys\nimport time\nimport uuid\n");
+    header.push_str("from datetime import datetime, timezone\nfrom urllib.parse import quote\n\n");
+    header
+}
+
+// ============================================
+// Python Runtime
+// ============================================
+
+/// Helpers used by the generated steps - same semantics as workflow.rs, expression.rs,
+/// template.rs and workflow_inputs.rs
+const PYTHON_RUNTIME: &str = r##"
+INPUTS = {}
+VARIABLES = {}
+LOGS = []
+STATE = {
+    "device": "127.0.0.1:5555",
+    "executor": "droidrun_executor.py",
+    "python": sys.executable,
+    "current_step": None,
+    "failed_step": None,
+    "timed_out_step": None,
+    # (step id, status, error) of the last failure absorbed by on_error skip/fallback
+    "handled": None,
+}
+HANDLED_STATUS = {"skip": "skipped", "fallback": "recovered"}
+DEADLINES = []
+
+
+# ============================================
+# Runtime
+# ============================================
+
+class WorkflowError(Exception):
+    """A failed step - handled by onError and try steps"""
+
+
+class ExprError(WorkflowError):
+    """An expression or template that cannot be evaluated"""
+
+
+class LoopSignal(Exception):
+    """Raised by break / continue steps, unwinds to the nearest loop"""
+
+
+class Break(LoopSignal):
+    pass
+
+
+class Continue(LoopSignal):
+    pass
+
+
+class Deadline(object):
+    def __init__(self, seconds, step_id):
+        self.at = time.monotonic() + seconds
+        self.seconds = seconds
+        self.step_id = step_id
+
+
+class Expired(BaseException):
+    """A step or workflow timeout - skips error handling up to the step that set it"""
+
+    def __init__(self, deadline):
+        BaseException.__init__(self)
+        self.deadline = deadline
+
+
+def say(message):
+    print("[WORKFLOW] %s" % message, file=sys.stderr)
+
+
+def log(level, step_id, message):
+    LOGS.append({
+        "timestamp": datetime.now(timezone.utc).isoformat(),
+        "level": level,
+        "stepId": step_id,
+        "message": message,
+    })
+    say(message)
+
+
+# --- Values ---
+
+def V(name):
+    """Runtime variables shadow inputs"""
+    if name in VARIABLES:
+        return VARIABLES[name]
+    return INPUTS.get(name)
+
+
+def _has(name):
+    return name in VARIABLES or name in INPUTS
+
+
+def _invalid(message):
+    raise ExprError(message)
+
+
+def _is_number(value):
+    return isinstance(value, (int, float)) and not isinstance(value, bool)
+
+
+def _parse_float(text):
+    try:
+        return float(text.strip())
+    except ValueError:
+        return None
+
+
+def _to_number(value):
+    if isinstance(value, bool):
+        return 1.0 if value else 0.0
+    if _is_number(value):
+        return float(value)
+    if isinstance(value, str):
+        return _parse_float(value)
+    return None
+
+
+def _number_value(n):
+    n = float(n)
+    if math.isnan(n) or math.isinf(n):
+        return None
+    if n == math.floor(n) and abs(n) < 9007199254740992:
+        return int(n)
+    return n
+
+
+def _format_float(n):
+    n = float(n)
+    if not math.isinf(n) and n == math.floor(n):
+        return str(int(n))
+    return repr(n)
+
+
+def _json(value):
+    return json.dumps(value, ensure_ascii=False, sort_keys=True, separators=(",", ":"))
+
+
+def display(value):
+    """How a value appears inside text"""
+    if value is None:
+        return ""
+    if isinstance(value, str):
+        return value
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if _is_number(value):
+        return repr(value) if isinstance(value, float) else str(value)
+    return _json(value)
+
+
+def _truthy(value):
+    if value is None:
+        return False
+    if isinstance(value, bool):
+        return value
+    if _is_number(value):
+        return value != 0
+    if isinstance(value, str):
+        return value.strip().lower() not in ("", "false", "0", "no", "null")
+    return len(value) > 0
+
+
+def _type_name(value):
+    if value is None:
+        return "null"
+    if isinstance(value, bool):
+        return "boolean"
+    if _is_number(value):
+        return "number"
+    if isinstance(value, str):
+        return "string"
+    if isinstance(value, list):
+        return "array"
+    return "object"
+
+
+def _same(a, b):
+    """JSON equality that keeps true != 1 and 1 != 1.0"""
+    if type(a) is not type(b):
+        return False
+    if isinstance(a, list):
+        return len(a) == len(b) and all(_same(x, y) for x, y in zip(a, b))
+    if isinstance(a, dict):
+        return a.keys() == b.keys() and all(_same(a[k], b[k]) for k in a)
+    return a == b
+
+
+def _loose_eq(a, b):
+    if (_is_number(a) and isinstance(b, str)) or (isinstance(a, str) and _is_number(b)):
+        x, y = _to_number(a), _to_number(b)
+        return x is not None and y is not None and x == y
+    if _is_number(a) and _is_number(b):
+        return float(a) == float(b)
+    if isinstance(a, bool) and isinstance(b, str):
+        return b.strip().lower() == display(a)
+    if isinstance(a, str) and isinstance(b, bool):
+        return a.strip().lower() == display(b)
+    return _same(a, b)
+
+
+def _compare(op, a, b):
+    if isinstance(a, str) and isinstance(b, str):
+        x, y = _parse_float(a), _parse_float(b)
+        if x is None or y is None:
+            x, y = a, b
+    else:
+        x, y = _to_number(a), _to_number(b)
+        if x is None or y is None or a is None or b is None:
+            raise ExprError("Cannot compare %s with %s using '%s'" % (_type_name(a), _type_name(b), op))
+    if (isinstance(x, float) and math.isnan(x)) or (isinstance(y, float) and math.isnan(y)):
+        raise ExprError("Cannot compare NaN")
+    if op == "<":
+        return x < y
+    if op == "<=":
+        return x <= y
+    if op == ">":
+        return x > y
+    return x >= y
+
+
+def _contains(haystack, needle):
+    if isinstance(haystack, str):
+        return display(needle) in haystack
+    if isinstance(haystack, list):
+        return any(_loose_eq(item, needle) for item in haystack)
+    if isinstance(haystack, dict):
+        return display(needle) in haystack
+    return False
+
+
+def _matches(text, pattern):
+    pattern = display(pattern)
+    try:
+        return re.search(pattern, display(text)) is not None
+    except re.error as e:
+        raise ExprError("Invalid regex '%s': %s" % (pattern, e))
+
+
+def _arithmetic(op, a, b):
+    # String concatenation when either side is a non-numeric string
+    if op == "+" and (isinstance(a, str) or isinstance(b, str)) and (_to_number(a) is None or _to_number(b) is None):
+        return display(a) + display(b)
+    x, y = _to_number(a), _to_number(b)
+    if x is None or y is None or a is None or b is None:
+        raise ExprError("Cannot apply '%s' to %s and %s" % (op, _type_name(a), _type_name(b)))
+    if op in ("/", "%") and y == 0:
+        raise ExprError("Division by zero")
+    if op == "+":
+        return _number_value(x + y)
+    if op == "-":
+        return _number_value(x - y)
+    if op == "*":
+        return _number_value(x * y)
+    if op == "/":
+        return _number_value(x / y)
+    return _number_value(math.fmod(x, y))
+
+
+def _length(value):
+    if isinstance(value, (str, list, dict)):
+        return len(value)
+    return 0
+
+
+def _member(target, key):
+    return target.get(key) if isinstance(target, dict) else None
+
+
+def _index(target, index):
+    if isinstance(target, list):
+        n = _to_number(index)
+        if n is not None and 0 <= n < len(target):
+            return target[int(n)]
+        return None
+    if isinstance(target, dict):
+        return target.get(display(index))
+    return None
+
+
+def _neg(value):
+    n = _to_number(value)
+    if n is None or value is None:
+        raise ExprError("Cannot negate %s" % _type_name(value))
+    return _number_value(-n)
+
+
+def _binary(op, a, b):
+    if op == "==":
+        return _loose_eq(a, b)
+    if op == "!=":
+        return not _loose_eq(a, b)
+    if op in ("<", "<=", ">", ">="):
+        return _compare(op, a, b)
+    if op == "contains":
+        return _contains(a, b)
+    if op == "matches":
+        return _matches(a, b)
+    if op == "startsWith":
+        return display(a).startswith(display(b))
+    if op == "endsWith":
+        return display(a).endswith(display(b))
+    return _arithmetic(op, a, b)
+
+
+def _round(n):
+    """Half away from zero, like f64::round"""
+    return math.floor(n + 0.5) if n >= 0 else -math.floor(-n + 0.5)
+
+
+def _call(name, args):
+    def arg(i):
+        return args[i] if i < len(args) else None
+
+    def expect(n):
+        if len(args) != n:
+            raise ExprError("%s() expects %d argument(s), got %d" % (name, n, len(args)))
+
+    def number_arg(i):
+        n = _to_number(arg(i))
+        if n is None:
+            raise ExprError("%s() expects a number" % name)
+        return n
+
+    if name in ("len", "length"):
+        expect(1)
+        return _length(arg(0))
+    if name == "isNull":
+        expect(1)
+        return arg(0) is None
+    if name == "exists":
+        expect(1)
+        return arg(0) is not None
+    if name == "isEmpty":
+        expect(1)
+        value = arg(0)
+        return value is None or (not _is_number(value) and not isinstance(value, bool) and _length(value) == 0)
+    if name in ("contains", "matches", "startsWith", "endsWith"):
+        expect(2)
+        return _binary(name, arg(0), arg(1))
+    if name == "lower":
+        expect(1)
+        return display(arg(0)).lower()
+    if name == "upper":
+        expect(1)
+        return display(arg(0)).upper()
+    if name == "trim":
+        expect(1)
+        return display(arg(0)).strip()
+    if name == "string":
+        expect(1)
+        return display(arg(0))
+    if name == "number":
+        expect(1)
+        return _number_value(number_arg(0))
+    if name in ("abs", "round", "floor", "ceil"):
+        expect(1)
+        functions = {"abs": abs, "round": _round, "floor": math.floor, "ceil": math.ceil}
+        return _number_value(functions[name](number_arg(0)))
+    if name in ("min", "max"):
+        if not args:
+            raise ExprError("%s() expects at least 1 argument" % name)
+        numbers = [number_arg(i) for i in range(len(args))]
+        return _number_value(min(numbers) if name == "min" else max(numbers))
+    if name == "random":
+        # random() -> float in [0, 1), random(min, max) -> integer in [min, max]
+        if len(args) == 0:
+            return random.random()
+        if len(args) == 2:
+            low, high = int(_round(number_arg(0))), int(_round(number_arg(1)))
+            return random.randint(min(low, high), max(low, high))
+        raise ExprError("random() expects 0 or 2 arguments, got %d" % len(args))
+    if name == "randomChoice":
+        expect(1)
+        value = arg(0)
+        if not isinstance(value, list):
+            raise ExprError("randomChoice() expects an array, got %s" % _type_name(value))
+        return random.choice(value) if value else None
+    if name == "now":
+        expect(0)
+        return datetime.now().astimezone().isoformat()
+    raise ExprError("Unknown function '%s'" % name)
+
+
+# --- Templates ---
+
+def _P(raw, source, names, has_default, value, filters):
+    return (raw, source, names, has_default, value, filters)
+
+
+def _evaluate(placeholder):
+    raw, source, names, has_default, compute, filters = placeholder
+    try:
+        # Names that are not valid expressions (e.g. "step-1") still resolve as plain variables
+        if _has(source):
+            value = V(source)
+        else:
+            missing = [name for name in names if not _has(name)]
+            if missing:
+                raise ExprError("unknown variable '%s'" % "', '".join(missing))
+            value = compute()
+    except ExprError:
+        if not has_default:
+            raise
+        value = None
+    if value is None and not has_default:
+        raise ExprError("'%s' has no value" % source)
+
+    for name, arg_thunks in filters:
+        values = []
+        for thunk in arg_thunks:
+            try:
+                values.append(thunk())
+            except ExprError as e:
+                raise ExprError("filter '%s': %s" % (name, e))
+        value = _filter(name, values, value)
+    return value
+
+
+def _render(parts):
+    """Unresolved placeholders stay as text and are reported"""
+    text = []
+    for part in parts:
+        if isinstance(part, str):
+            text.append(part)
+            continue
+        try:
+            text.append(display(_evaluate(part)))
+        except ExprError as e:
+            say("⚠️ Template %s: %s" % (part[0], e))
+            text.append(part[0])
+    return "".join(text)
+
+
+def _resolve(template, placeholder):
+    """A lone placeholder keeps the JSON type of its value"""
+    try:
+        return _evaluate(placeholder)
+    except ExprError as e:
+        say("⚠️ Template %s: %s" % (placeholder[0], e))
+        return template
+
+
+def _filter(name, args, value):
+    if name == "default":
+        empty = value is None or value == ""
+        return (args[0] if args else None) if empty else value
+    if name == "upper":
+        return display(value).upper()
+    if name == "lower":
+        return display(value).lower()
+    if name == "trim":
+        return display(value).strip()
+    if name == "json":
+        return json.dumps(value, ensure_ascii=False, sort_keys=True, separators=(",", ":"))
+    if name == "urlencode":
+        return quote(display(value), safe="")
+    if name == "number":
+        return _format_number(value, args)
+    if name == "date":
+        return _format_date(value, args)
+    raise ExprError("unknown filter '%s'" % name)
+
+
+def _format_number(value, args):
+    n = float(value) if _is_number(value) else _parse_float(value) if isinstance(value, str) else None
+    if n is None:
+        raise ExprError("filter 'number': %s is not a number" % _json(value))
+    decimals = int(max(args[0], 0)) if args and _is_number(args[0]) else 0
+    formatted = "%.*f" % (decimals, n)
+    separator = display(args[1]) if len(args) > 1 else ""
+    if not separator:
+        return formatted
+
+    sign = ""
+    if formatted.startswith("-"):
+        sign, formatted = "-", formatted[1:]
+    integer, _, fraction = formatted.partition(".")
+    grouped = ""
+    for i, digit in enumerate(integer):
+        if i > 0 and (len(integer) - i) % 3 == 0:
+            grouped += separator
+        grouped += digit
+    return sign + grouped + ("." + fraction if fraction else "")
+
+
+def _from_timestamp(ts):
+    # Values this large are milliseconds
+    millis = ts if abs(ts) > 100000000000 else ts * 1000
+    try:
+        return datetime.fromtimestamp(millis / 1000.0).astimezone()
+    except (OverflowError, OSError, ValueError):
+        raise ExprError("invalid timestamp %d" % ts)
+
+
+def _format_date(value, args):
+    if isinstance(value, str) and value.strip().lower() == "now":
+        date = datetime.now().astimezone()
+    elif isinstance(value, str):
+        text = value.strip()
+        try:
+            date = datetime.fromisoformat(text.replace("Z", "+00:00")).astimezone()
+        except ValueError:
+            if not re.fullmatch(r"[+-]?[0-9]+", text):
+                raise ExprError("filter 'date': cannot parse \"%s\"" % value)
+            date = _from_timestamp(int(text))
+    elif _is_number(value):
+        date = _from_timestamp(int(value))
+    else:
+        raise ExprError("filter 'date': %s is not a date" % _json(value))
+
+    pattern = display(args[0]) if args else "%Y-%m-%d %H:%M:%S"
+    try:
+        return date.strftime(pattern)
+    except ValueError:
+        raise ExprError("filter 'date': invalid format \"%s\"" % pattern)
+
+
+# --- Inputs ---
+
+def _input_error(name, code, message):
+    return {"name": name, "code": code, "message": message}
+
+
+def _is_blank(value, spec):
+    """Empty strings count as "not given" for typed inputs and for required text inputs"""
+    if value is None:
+        return True
+    if isinstance(value, str) and not value.strip():
+        return spec.get("type") not in ("string", "text") or spec.get("required") is True
+    return False
+
+
+def _coerce(spec, value):
+    kind = spec.get("type")
+    if kind == "number":
+        if _is_number(value):
+            return value
+        if isinstance(value, str):
+            text = value.strip()
+            if re.fullmatch(r"[+-]?[0-9]+", text) and -2 ** 63 <= int(text) < 2 ** 63:
+                return int(text)
+            n = _parse_float(text)
+            if n is None or math.isnan(n) or math.isinf(n):
+                raise ValueError("must be a number, got \"%s\"" % text)
+            return n
+        raise ValueError("must be a number, got %s" % _json(value))
+    if kind == "boolean":
+        if isinstance(value, bool):
+            return value
+        if _is_number(value) and value in (0, 1):
+            return value == 1
+        if isinstance(value, str):
+            text = value.strip().lower()
+            if text in ("true", "1", "yes", "on"):
+                return True
+            if text in ("false", "0", "no", "off"):
+                return False
+            raise ValueError("must be true or false, got \"%s\"" % text)
+        raise ValueError("must be true or false, got %s" % _json(value))
+    if kind in ("string", "text", "select"):
+        if isinstance(value, str):
+            return value
+        if isinstance(value, bool) or _is_number(value):
+            return display(value)
+        raise ValueError("must be text, got %s" % _json(value))
+    # Unknown types are passed through untouched
+    return value
+
+
+def _check_constraints(spec, label, value):
+    name = spec["name"]
+    if spec.get("type") == "number" and _is_number(value):
+        n = float(value)
+        if spec.get("min") is not None and n < spec["min"]:
+            return _input_error(name, "below_min", "'%s' must be at least %s, got %s" % (
+                label, _format_float(spec["min"]), _format_float(n)))
+        if spec.get("max") is not None and n > spec["max"]:
+            return _input_error(name, "above_max", "'%s' must be at most %s, got %s" % (
+                label, _format_float(spec["max"]), _format_float(n)))
+    if spec.get("type") == "select":
+        allowed = [option["value"] for option in spec.get("options") or []]
+        selected = value if isinstance(value, str) else ""
+        if allowed and selected not in allowed:
+            return _input_error(name, "invalid_option", "'%s' must be one of %s, got \"%s\"" % (
+                label, ", ".join(allowed), selected))
+    return None
+
+
+def prepare_inputs(specs, given):
+    """Apply defaults, coerce every declared input to its type and check min/max/options"""
+    inputs = dict(given)
+    errors = []
+    for spec in specs:
+        name = spec["name"]
+        label = spec.get("label") if spec.get("label") is not None else name
+        provided = inputs.pop(name, None)
+        if provided is not None and _is_blank(provided, spec):
+            provided = None
+        raw = provided if provided is not None else spec.get("default")
+        if raw is None:
+            # Inputs without a default must be given unless explicitly optional
+            required = spec.get("required")
+            if (spec.get("default") is None) if required is None else required:
+                errors.append(_input_error(name, "missing", "'%s' is required" % label))
+            continue
+        try:
+            value = _coerce(spec, raw)
+        except ValueError as e:
+            errors.append(_input_error(name, "invalid_type", "'%s' %s" % (label, e)))
+            inputs[name] = raw
+            continue
+        error = _check_constraints(spec, label, value)
+        if error:
+            errors.append(error)
+        inputs[name] = value
+    return inputs, errors
+
+
+# --- Device ---
+
+def _check_deadlines():
+    now = time.monotonic()
+    for deadline in reversed(DEADLINES):
+        if now >= deadline.at:
+            raise Expired(deadline)
+
+
+def _remaining():
+    if not DEADLINES:
+        return None
+    return max(0.0, min(d.at for d in DEADLINES) - time.monotonic())
+
+
+def sleep_ms(ms):
+    end = time.monotonic() + ms / 1000.0
+    while True:
+        _check_deadlines()
+        left = end - time.monotonic()
+        if left <= 0:
+            return
+        remaining = _remaining()
+        time.sleep(left if remaining is None else min(left, remaining + 0.01))
+
+
+def _run(command):
+    env = dict(os.environ, PYTHONIOENCODING="utf-8")
+    try:
+        proc = subprocess.run(command, capture_output=True, env=env, timeout=_remaining())
+    except subprocess.TimeoutExpired:
+        # The child was killed, like dropping the step future in the app
+        _check_deadlines()
+        raise Expired(min(DEADLINES, key=lambda d: d.at))
+    return proc.returncode, proc.stdout.decode("utf-8", "replace"), proc.stderr.decode("utf-8", "replace")
+
+
+def droidrun(action_name, args):
+    """py droidrun_executor.py <device_id> <action> [args...]"""
+    command = [STATE["python"], STATE["executor"], STATE["device"], action_name] + list(args)
+    try:
+        code, stdout, stderr = _run(command)
+    except OSError as e:
+        raise WorkflowError("DroidRun executor error: %s" % e)
+    if code != 0:
+        raise WorkflowError("DroidRun action failed: %s | %s" % (stdout.strip(), stderr.strip()))
+    try:
+        result = json.loads(stdout)
+    except ValueError:
+        return stdout
+    result = result if isinstance(result, dict) else {}
+    message = result.get("message") if isinstance(result.get("message"), str) else None
+    if result.get("success") is True:
+        say("✓ %s" % (message or "OK"))
+        return message or "OK"
+    raise WorkflowError(message or "Unknown error")
+
+
+def adb(args):
+    say("Fallback to ADB: %s" % args)
+    try:
+        code, stdout, stderr = _run(["adb", "-s", STATE["device"]] + list(args))
+    except OSError as e:
+        raise WorkflowError("ADB error: %s" % e)
+    if code != 0:
+        raise WorkflowError("ADB failed: %s" % stderr)
+    return stdout
+
+
+def _require(params, key, message=None):
+    if key not in params:
+        raise WorkflowError(message or "Missing '%s' param" % key)
+    return params[key]
+
+
+def _either(params, key, alias):
+    if key in params:
+        return params[key]
+    return _require(params, alias, "Missing '%s' or '%s' param" % (key, alias))
+
+
+def _droidrun_or_adb(action_name, args, fallback):
+    try:
+        droidrun(action_name, args)
+    except WorkflowError as e:
+        say("DroidRun failed: %s, trying ADB fallback" % e)
+        adb(fallback)
+
+
+_SWIPES = {
+    "swipe_up": ["500", "1500", "500", "500", "300"],
+    "swipe_down": ["500", "500", "500", "1500", "300"],
+    "swipe_left": ["800", "500", "100", "500", "300"],
+    "swipe_right": ["100", "500", "800", "500", "300"],
+}
+
+_KEYS = {
+    "back": ("back", "KEYCODE_BACK"),
+    "home": ("home", "KEYCODE_HOME"),
+    "enter": ("enter", "KEYCODE_ENTER"),
+    "dismiss_popup": ("back", "KEYCODE_BACK"),
+}
+
+
+def action(name, params):
+    """Same action mapping as the app: DroidRun executor first, ADB as fallback"""
+    say("Action: %s with params: %s" % (name, params))
+    if name in ("open_app", "start_app"):
+        package = _require(params, "package")
+        try:
+            droidrun("open_app", [package, params.get("activity", "")])
+        except WorkflowError as e:
+            say("DroidRun failed: %s, trying ADB fallback" % e)
+            adb(["shell", "monkey", "-p", package, "1"])
+            sleep_ms(2000)
+    elif name == "tap":
+        x, y = _require(params, "x"), _require(params, "y")
+        _droidrun_or_adb("tap", [x, y], ["shell", "input", "tap", x, y])
+    elif name in ("tap_index", "tap_by_index"):
+        droidrun("tap_index", [_require(params, "index")])
+    elif name in ("tap_element", "tap_text"):
+        text = _require(params, "text", "Missing 'text' or 'selector' param")
+        try:
+            droidrun("tap_text", [text])
+        except WorkflowError as e:
+            # Optional x/y (e.g. recorded coordinates of a converted macro) when the element is not found
+            if "x" not in params or "y" not in params:
+                raise
+            say("tap_element failed: %s, tapping fallback (%s, %s)" % (e, params["x"], params["y"]))
+            adb(["shell", "input", "tap", params["x"], params["y"]])
+    elif name == "swipe":
+        points = [_either(params, "x1", "start_x"), _either(params, "y1", "start_y"),
+                  _either(params, "x2", "end_x"), _either(params, "y2", "end_y"), params.get("duration", "300")]
+        _droidrun_or_adb("swipe", points, ["shell", "input", "swipe"] + points)
+    elif name in _SWIPES:
+        try:
+            droidrun(name, [])
+        except WorkflowError:
+            adb(["shell", "input", "swipe"] + _SWIPES[name])
+    elif name in ("type", "input_text"):
+        text = _require(params, "text")
+        escaped = text.replace(" ", "%s").replace("'", "\\'")
+        _droidrun_or_adb("input_text", [text, params.get("clear", "false")], ["shell", "input", "text", escaped])
+    elif name in _KEYS:
+        executor_action, keycode = _KEYS[name]
+        try:
+            droidrun(executor_action, [])
+        except WorkflowError:
+            adb(["shell", "input", "keyevent", keycode])
+    elif name == "press_key":
+        keycode = _require(params, "keycode")
+        try:
+            droidrun("press_key", [keycode])
+        except WorkflowError:
+            adb(["shell", "input", "keyevent", keycode])
+    elif name == "recent_apps":
+        adb(["shell", "input", "keyevent", "KEYCODE_APP_SWITCH"])
+    elif name == "wake":
+        adb(["shell", "input", "keyevent", "KEYCODE_WAKEUP"])
+    elif name == "screenshot":
+        path = params.get("path", "screenshot.png")
+        try:
+            droidrun("screenshot", [path])
+        except WorkflowError:
+            # Fallback: save on device then pull
+            adb(["shell", "screencap", "-p", "/sdcard/temp_screenshot.png"])
+            adb(["pull", "/sdcard/temp_screenshot.png", path])
+    elif name == "get_state":
+        droidrun("get_state", [])
+    elif name == "long_press":
+        droidrun("long_press", [_require(params, "x"), _require(params, "y"), params.get("duration", "2000")])
+    elif name == "double_tap":
+        droidrun("double_tap", [_require(params, "x"), _require(params, "y")])
+    else:
+        raise WorkflowError("Unknown action: %s" % name)
+
+
+def delay_after(base):
+    """Delay after top-level actions, +/-15% for more human-like timing"""
+    variance = int(base * 0.15)
+    delay = random.randint(max(base - variance, 0), base + variance)
+    say("Delay %dms (base: %dms ±15%%)" % (delay, base))
+    sleep_ms(delay)
+
+
+# --- Steps ---
+
+def step(step_id, on_error=None, timeout=None):
+    """Run a generated step like execute_step: own timeout, onError strategy, failed step id"""
+    def wrap(body):
+        def run():
+            _check_deadlines()
+            try:
+                try:
+                    _attempt(step_id, body, timeout)
+                except WorkflowError as e:
+                    if on_error is None:
+                        raise
+                    _handle_error(step_id, body, timeout, on_error, e)
+                    # A handled error no longer describes how the run ended
+                    STATE["timed_out_step"] = None
+                    STATE["failed_step"] = None
+                    if on_error.get("strategy") in HANDLED_STATUS:
+                        STATE["handled"] = (step_id, HANDLED_STATUS[on_error["strategy"]], str(e))
+            except WorkflowError:
+                if STATE["failed_step"] is None:
+                    STATE["failed_step"] = step_id
+                raise
+        run.step_id = step_id
+        return run
+    return wrap
+
+
+def _attempt(step_id, body, timeout):
+    if not timeout:
+        return body()
+    deadline = Deadline(timeout, step_id)
+    DEADLINES.append(deadline)
+    try:
+        body()
+    except Expired as e:
+        if e.deadline is not deadline:
+            raise
+        STATE["timed_out_step"] = step_id
+        raise WorkflowError("Step '%s' timed out after %ss" % (step_id, timeout))
+    finally:
+        DEADLINES.remove(deadline)
+
+
+def _handle_error(step_id, body, timeout, config, error):
+    strategy = config.get("strategy")
+    if strategy == "skip":
+        log("warning", step_id, "Skipping failed step: %s" % error)
+    elif strategy == "retry":
+        _retry(step_id, body, timeout, config, error)
+    elif strategy == "fallback":
+        if config.get("fallback") is not None:
+            log("info", step_id, "Running fallback steps after error: %s" % error)
+            for fallback in config["fallback"]:
+                try:
+                    fallback()
+                except (WorkflowError, LoopSignal) as e:
+                    log("error", fallback.step_id, "Fallback failed: %s" % e)
+    else:
+        raise error
+
+
+def _retry_delay_ms(config, attempt):
+    max_delay = float(config.get("maxDelay", 60000))
+    backoff = max(float(config.get("backoff", 1.0)), 1.0)
+    delay = min(config.get("retryDelay", 1000) * backoff ** (attempt - 1), max_delay)
+    jitter = min(max(float(config.get("jitter", 0.0)), 0.0), 1.0)
+    spread = random.uniform(-jitter, jitter) if jitter > 0 else 0.0
+    return int(min(max(delay * (1 + spread), 0.0), max_delay))
+
+
+def _retry(step_id, body, timeout, config, error):
+    retries = max(config.get("retries", 3), 0)
+    retry_on = None
+    if config.get("retryOn") is not None:
+        try:
+            retry_on = re.compile(config["retryOn"])
+        except re.error as e:
+            raise WorkflowError("%s (retryOn pattern is invalid: %s)" % (error, e))
+
+    for attempt in range(1, retries + 1):
+        if retry_on is not None and not retry_on.search(str(error)):
+            log("info", step_id, "Error does not match retryOn, not retrying")
+            raise error
+        delay = _retry_delay_ms(config, attempt)
+        log("info", step_id, "Retry %d/%d in %dms" % (attempt, retries, delay))
+        sleep_ms(delay)
+        for recovery in config.get("recovery", []):
+            try:
+                recovery()
+            except (WorkflowError, LoopSignal) as e:
+                log("warning", recovery.step_id, "Recovery step failed: %s" % e)
+        try:
+            _attempt(step_id, body, timeout)
+            log("success", step_id, "Retry %d/%d succeeded" % (attempt, retries))
+            return
+        except WorkflowError as e:
+            error = e
+    raise error
+
+
+def run_steps(steps):
+    for run in steps:
+        run()
+
+
+def run_body(steps):
+    """One loop iteration - False when a break step ended the loop"""
+    try:
+        run_steps(steps)
+    except Continue:
+        return True
+    except Break:
+        return False
+    return True
+
+
+def condition(step_id, expression):
+    try:
+        return _truthy(expression())
+    except ExprError as e:
+        raise WorkflowError("Invalid condition in step '%s': %s" % (step_id, e))
+
+
+def loop_count(text):
+    if not re.fullmatch(r"[+-]?[0-9]+", text) or not -2 ** 31 <= int(text) < 2 ** 31:
+        raise WorkflowError("Invalid loop count: %s" % text)
+    return int(text)
+
+
+def foreach_items(items):
+    if isinstance(items, list):
+        return items
+    if isinstance(items, str):
+        # Inputs often hand over arrays as JSON text
+        try:
+            parsed = json.loads(items)
+        except ValueError:
+            parsed = None
+        if isinstance(parsed, list):
+            return parsed
+        raise WorkflowError("Foreach items must be an array, got \"%s\"" % items)
+    raise WorkflowError("Foreach items must be an array, got %s" % _json(items))
+
+
+def wait_ms(text):
+    duration = int(text) if re.fullmatch(r"\+?[0-9]+", text) else 1000
+    say("Waiting %dms" % duration)
+    sleep_ms(duration)
+
+
+def wait_until(step_id, expression, source):
+    start = time.monotonic()
+    while not condition(step_id, expression):
+        if (time.monotonic() - start) * 1000 > 30000:
+            raise WorkflowError("Wait condition timeout: %s" % source)
+        sleep_ms(500)
+
+
+def run_try(step_id, variable, body, catch, final):
+    """try/catch/finally: the error is exposed as {{error.message}} / {{error.step_id}}"""
+    STATE["failed_step"] = None
+    result = None
+    try:
+        run_steps(body)
+    except (WorkflowError, LoopSignal) as e:
+        result = e
+
+    if isinstance(result, WorkflowError):
+        failed_step = STATE["failed_step"] or step_id
+        VARIABLES[variable] = {"message": str(result), "step_id": failed_step}
+        if catch is not None:
+            log("warning", step_id, "Caught error from step '%s': %s" % (failed_step, result))
+            STATE["failed_step"] = None
+            result = None
+            try:
+                run_steps(catch)
+            except (WorkflowError, LoopSignal) as e:
+                result = e
+
+    # The first error wins, but every cleanup step still runs
+    for run in final:
+        try:
+            run()
+        except (WorkflowError, LoopSignal) as e:
+            if result is None:
+                result = e
+    if result is not None:
+        raise result
+
+
+def _as_number(value):
+    if value is None:
+        return 0.0
+    if _is_number(value):
+        return float(value)
+    if isinstance(value, str):
+        return _parse_float(value)
+    return None
+
+
+def _set_value(name, kind, value, source):
+    if not name.strip():
+        raise WorkflowError("variable name is empty")
+    current = V(name)
+    if kind == "value":
+        return value()
+    if kind == "expression":
+        try:
+            return value()
+        except ExprError as e:
+            raise WorkflowError("invalid expression '%s': %s" % (source, e))
+    if kind == "increment":
+        amount = value()
+        base, step_by = _as_number(current), _as_number(amount)
+        if base is None:
+            raise WorkflowError("current value %s is not a number" % _json(current))
+        if step_by is None:
+            raise WorkflowError("increment %s is not a number" % _json(amount))
+        return _number_value(base + step_by)
+    if kind == "append":
+        if current is not None and not isinstance(current, list):
+            raise WorkflowError("cannot append to %s" % _json(current))
+        return list(current or []) + [value()]
+    if kind == "merge":
+        if current is not None and not isinstance(current, dict):
+            raise WorkflowError("cannot merge into %s" % _json(current))
+        patch = value()
+        if not isinstance(patch, dict):
+            raise WorkflowError("merge value %s is not an object" % _json(patch))
+        merged = dict(current or {})
+        merged.update(patch)
+        return merged
+    raise WorkflowError("use exactly one of value, expression, increment, append or merge")
+
+
+def set_var(step_id, name, kind, value, source=None):
+    """One operation of a set step, later operations see the result"""
+    try:
+        VARIABLES[name] = _set_value(name, kind, value, source)
+    except WorkflowError as e:
+        raise WorkflowError("Set '%s' in step '%s': %s" % (name, step_id, e))
+    say("Set %s = %s" % (name, _json(VARIABLES[name])))
+"##;
+
+/// Entry point - runs WORKFLOW_STEPS like execute_workflow_run and prints the WorkflowResult
+const PYTHON_MAIN: &str = r##"
+
+# ============================================
+# Main
+# ============================================
+
+def _loop_signal_error(error):
+    if isinstance(error, Break):
+        return "'break' step used outside of a loop"
+    if isinstance(error, Continue):
+        return "'continue' step used outside of a loop"
+    return str(error)
+
+
+def run(given):
+    """Run the workflow and return a result shaped like the app's WorkflowResult"""
+    started = time.monotonic()
+    result = {
+        "success": False,
+        "runId": str(uuid.uuid4()),
+        "status": "failed",
+        "workflowId": WORKFLOW_ID,
+        "outputs": {},
+        "logs": LOGS,
+        "durationMs": 0,
+        "error": None,
+        "timedOutStep": None,
+        "inputErrors": [],
+    }
+
+    # Reject bad inputs before anything touches the device
+    inputs, errors = prepare_inputs(INPUT_SPECS, given)
+    if errors:
+        summary = "; ".join(e["message"] for e in errors)
+        say("Invalid inputs for %s: %s" % (WORKFLOW_NAME, summary))
+        result.update(status="invalid_inputs", error="Invalid inputs: %s" % summary, inputErrors=errors)
+        return result
+    INPUTS.update(inputs)
+
+    log("info", None, "🚀 Starting workflow: %s" % WORKFLOW_NAME)
+    error = None
+    status = None
+    if TIMEOUT:
+        DEADLINES.append(Deadline(TIMEOUT, None))
+    try:
+        for body, label, delay in WORKFLOW_STEPS:
+            STATE["current_step"] = body.step_id
+            STATE["timed_out_step"] = None
+            log("info", body.step_id, "▶️ Step: %s" % label)
+            STATE["handled"] = None
+            try:
+                body()
+            except (WorkflowError, LoopSignal) as e:
+                error = _loop_signal_error(e)
+                log("error", body.step_id, "✗ Error: %s" % error)
+                break
+            handled = STATE["handled"]
+            if handled is not None and handled[0] == body.step_id:
+                log("warning", body.step_id, "⚠ Step %s after error: %s" % (handled[1], handled[2]))
+                continue
+            log("success", body.step_id, "✓ Step completed")
+            if delay is not None:
+                delay_after(delay)
+    except Expired:
+        step_id = STATE["current_step"] or ""
+        error = "Workflow timed out after %ss during step '%s'" % (TIMEOUT, step_id)
+        log("error", step_id, "⏱ %s" % error)
+        STATE["timed_out_step"] = step_id
+        status = "timeout"
+    except KeyboardInterrupt:
+        log("warning", STATE["current_step"], "⏹ Workflow cancelled")
+        error = "Workflow cancelled"
+        status = "cancelled"
+    finally:
+        del DEADLINES[:]
+
+    if status is None:
+        if error is None:
+            status = "completed"
+        else:
+            status = "timeout" if STATE["timed_out_step"] else "failed"
+    duration_ms = int((time.monotonic() - started) * 1000)
+    if error is None:
+        log("success", None, "✅ Workflow completed in %dms" % duration_ms)
+    else:
+        log("error", None, "❌ Workflow failed: %s" % error)
+
+    result.update(
+        success=error is None,
+        status=status,
+        outputs={name: VARIABLES[name] for name in OUTPUTS if name in VARIABLES},
+        durationMs=duration_ms,
+        error=error,
+        timedOutStep=STATE["timed_out_step"],
+    )
+    return result
+
+
+def _default_executor():
+    here = os.path.dirname(os.path.abspath(__file__))
+    for path in (os.path.join(here, "droidrun_executor.py"), "droidrun_executor.py",
+                 os.path.join("src-tauri", "droidrun_executor.py")):
+        if os.path.exists(path):
+            return path
+    return os.path.join(here, "droidrun_executor.py")
+
+
+def main():
+    for stream in (sys.stdout, sys.stderr):
+        if hasattr(stream, "reconfigure"):
+            stream.reconfigure(errors="replace")
+
+    parser = argparse.ArgumentParser(description=DESCRIPTION)
+    parser.add_argument("--device", default="127.0.0.1:5555", help="Device serial (default: %(default)s)")
+    parser.add_argument("--executor", default=_default_executor(), help="Path to droidrun_executor.py")
+    parser.add_argument("--python", default=sys.executable, help="Python used to run the executor")
+    add_input_arguments(parser)
+    args = vars(parser.parse_args())
+
+    STATE.update(device=args["device"], executor=args["executor"], python=args["python"])
+    given = {key[len("inputs."):]: value for key, value in args.items()
+             if key.startswith("inputs.") and value is not None}
+    result = run(given)
+    print(json.dumps(result, ensure_ascii=False, indent=2))
+    sys.exit(0 if result["success"] else 1)
+
+
+if __name__ == "__main__":
+    main()
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_workflow() -> WorkflowDefinition {
+        serde_json::from_value(json!({
+            "id": "tiktok_warmup",
+            "name": "TikTok Warmup",
+            "inputs": [
+                {"name": "video_count", "label": "Video count", "type": "number", "default": 3, "min": 1},
+                {"name": "device", "type": "string"},
+                {"name": "mode", "type": "select", "default": "like",
+                 "options": [{"value": "like", "label": "Like"}, {"value": "skip", "label": "Skip"}]}
+            ],
+            "steps": [
+                {"id": "open", "type": "action", "action": "open_app",
+                 "params": {"package": "com.zhiliaoapp.musically"}, "delayAfter": 3000},
+                {"id": "watch", "type": "loop", "count": "{{video_count}}", "variable": "n", "body": [
+                    {"id": "maybe_like", "type": "condition", "condition": "mode == 'like' && n % 2 == 0",
+                     "then": [{"id": "like", "type": "action", "action": "double_tap", "params": {"x": 540, "y": 960}}],
+                     "else": [{"id": "stop", "type": "break", "condition": "n >= 5"}]},
+                    {"id": "next", "type": "action", "action": "swipe_up",
+                     "onError": {"strategy": "retry", "retries": 2, "recovery": [{"id": "back", "type": "action", "action": "back"}]}}
+                ]},
+                {"id": "count", "type": "set", "set": [{"name": "watched", "value": "{{video_count}}"}]}
+            ],
+            "outputs": ["watched"],
+            "timeout": 600
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_workflow_to_python() {
+        let script = workflow_to_python(&sample_workflow()).unwrap();
+
+        // Inputs become CLI flags, "device" is taken by the script itself
+        assert!(script.contains("group.add_argument(\"--video-count\", dest=\"inputs.video_count\", metavar=\"NUMBER\", help=\"Video count (number, >= 1, default: 3)\")"));
+        assert!(script.contains("\"--input-device\", dest=\"inputs.device\""));
+        assert!(script.contains("help=\"mode (select, one of like, skip, default: like)\""));
+        assert!(script.contains("#     python tiktok-warmup.py --device 127.0.0.1:5555 [--video-count NUMBER] --input-device STRING [--mode SELECT]\n"));
+
+        // Children are defined before the steps that reference them
+        assert!(script.contains("@step(\"watch\")\ndef step_2():\n    count = loop_count(_render([_P(\"{{video_count}}\", \"video_count\", [\"video_count\"], False, lambda: V(\"video_count\"), [])]))\n    for iteration in range(count):\n        VARIABLES[\"n\"] = iteration\n        if not run_body([step_3, step_6]):\n            break\n"));
+        assert!(script.find("def step_7():").unwrap() < script.find("@step(\"next\", on_error=").unwrap());
+        assert!(script.contains("@step(\"next\", on_error={\"strategy\": \"retry\", \"retries\": 2, \"recovery\": [step_7]})"));
+        assert!(script.contains("    if condition(\"maybe_like\", lambda: (_truthy(_binary(\"==\", V(\"mode\"), \"like\")) and _truthy(_binary(\"==\", _binary(\"%\", V(\"n\"), 2), 0)))):\n        run_steps([step_4])\n    else:\n        run_steps([step_5])\n"));
+        assert!(script.contains("    if condition(\"stop\", lambda: _binary(\">=\", V(\"n\"), 5)):\n        raise Break()\n"));
+        assert!(script.contains("    action(\"double_tap\", {\"x\": \"540\", \"y\": \"960\"})\n"));
+        assert!(script.contains("    set_var(\"count\", \"watched\", \"value\", lambda: _resolve(\"{{video_count}}\", _P("));
+
+        // Only top-level actions get the delay, with the step's own delayAfter
+        assert!(script.contains("WORKFLOW_STEPS = [\n    (step_1, \"action\", 3000),\n    (step_2, \"loop\", None),\n    (step_8, \"set\", None),\n]"));
+        assert!(script.contains("OUTPUTS = [\"watched\"]\nTIMEOUT = 600\n"));
+    }
+
+    #[test]
+    fn test_app_only_steps_are_rejected() {
+        let mut workflow = sample_workflow();
+        workflow.steps.push(serde_json::from_value(json!({"id": "ask", "type": "prompt", "prompt": "Like the video"})).unwrap());
+        workflow.steps.push(serde_json::from_value(json!(
+            {"id": "tap_follow", "type": "action", "action": "tap_element", "params": {"selector": "text=Follow"}}
+        )).unwrap());
+
+        let error = workflow_to_python(&workflow).unwrap_err();
+        assert!(error.contains("ask (prompt)"));
+        assert!(error.contains("tap_follow (tap_element with selector)"));
+    }
+
+    fn python3() -> Option<&'static str> {
+        let output = std::process::Command::new("python3").arg("--version").output().ok()?;
+        output.status.success().then_some("python3")
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mun-export-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_generated_script_compiles() {
+        let Some(python) = python3() else {
+            println!("python3 not found, skipping");
+            return;
+        };
+        let dir = scratch_dir("compile");
+        let path = dir.join("tiktok-warmup.py");
+        std::fs::write(&path, workflow_to_python(&sample_workflow()).unwrap()).unwrap();
+
+        let output = std::process::Command::new(python).args(["-m", "py_compile"]).arg(&path).output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Records every call next to itself and always succeeds
+    const STUB_EXECUTOR: &str = r#"import json, os, sys
+with open(os.path.join(os.path.dirname(os.path.abspath(__file__)), "calls.jsonl"), "a") as calls:
+    calls.write(json.dumps(sys.argv[1:]) + "\n")
+print(json.dumps({"success": True, "message": "stub %s" % sys.argv[2]}))
+"#;
+
+    fn golden_workflows() -> Vec<WorkflowDefinition> {
+        let fail = |id: &str| json!({"id": id, "type": "set", "set": [{"name": "n", "increment": "abc"}]});
+        serde_json::from_value(json!([
+            {
+                "id": "golden_loop", "name": "Golden loop", "outputs": ["seen", "done"],
+                "inputs": [{"name": "count", "type": "number", "default": 5}],
+                "steps": [
+                    {"id": "open", "type": "action", "action": "open_app", "params": {"package": "com.example"}, "delayAfter": 0},
+                    {"id": "watch", "type": "loop", "count": "{{count}}", "body": [
+                        {"id": "skip_one", "type": "continue", "condition": "{{i}} == 1"},
+                        {"id": "stop", "type": "break", "condition": "{{i}} == 3"},
+                        {"id": "seen", "type": "set", "set": [{"name": "seen", "append": "{{i}}"}]},
+                        {"id": "swipe", "type": "action", "action": "swipe_up"}
+                    ]},
+                    {"id": "finish", "type": "set", "set": [{"name": "done", "value": true}]}
+                ]
+            },
+            {
+                "id": "golden_errors", "name": "Golden errors", "inputs": [], "outputs": ["caught", "cleaned", "fell_back"],
+                "steps": [
+                    {"id": "guard", "type": "try",
+                     "body": [fail("fail"), {"id": "never", "type": "set", "set": [{"name": "never", "value": true}]}],
+                     "catch": [{"id": "report", "type": "set", "set": [{"name": "caught", "value": "{{error.step_id}}"}]}],
+                     "finally": [{"id": "cleanup", "type": "set", "set": [{"name": "cleaned", "value": true}]}]},
+                    {"id": "flaky", "type": "set", "set": [{"name": "n", "increment": "abc"}], "onError": {"strategy": "skip"}},
+                    {"id": "recover", "type": "set", "set": [{"name": "n", "increment": "abc"}], "onError": {"strategy": "fallback",
+                     "fallback": [{"id": "fall_back", "type": "set", "set": [{"name": "fell_back", "value": true}]}]}},
+                    fail("boom"),
+                    {"id": "unreached", "type": "set", "set": [{"name": "unreached", "value": true}]}
+                ]
+            },
+            {
+                "id": "golden_timeout", "name": "Golden timeout", "inputs": [], "outputs": [],
+                "steps": [{"id": "slow", "type": "wait", "duration": "3000", "timeout": 1}]
+            }
+        ]))
+        .unwrap()
+    }
+
+    /// Result fields both runners must agree on - timestamps and durations differ between runs
+    fn comparable(result: &Value) -> Value {
+        let duration = regex::Regex::new(r"in \d+ms").unwrap();
+        let logs: Vec<Value> = result["logs"].as_array().unwrap().iter()
+            .map(|log| json!([log["level"], log["stepId"], duration.replace(log["message"].as_str().unwrap(), "in _ms")]))
+            .collect();
+        json!({
+            "success": result["success"],
+            "status": result["status"],
+            "error": result["error"],
+            "outputs": result["outputs"],
+            "timedOutStep": result["timedOutStep"],
+            "logs": logs,
+        })
+    }
+
+    fn take_calls(dir: &std::path::Path) -> Vec<Value> {
+        let path = dir.join("calls.jsonl");
+        let calls = std::fs::read_to_string(&path).unwrap_or_default();
+        let _ = std::fs::remove_file(&path);
+        calls.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_golden_runs_match_engine() {
+        let Some(python) = python3() else {
+            println!("python3 not found, skipping");
+            return;
+        };
+        let dir = scratch_dir("golden");
+        let stub = dir.join("stub_executor.py");
+        std::fs::write(&stub, STUB_EXECUTOR).unwrap();
+        let _executor = crate::workflow::TestExecutorGuard::set(python, stub.clone());
+
+        let app = tauri::test::mock_app();
+        let webview = tauri::WebviewWindowBuilder::new(&app, "main", Default::default()).build().unwrap();
+        let window = webview.as_ref().window();
+
+        let mut statuses = vec![];
+        for workflow in golden_workflows() {
+            let run_id = format!("{}-{}", workflow.id, std::process::id());
+            let prepared = crate::workflow_inputs::prepare_inputs(&workflow.inputs, Default::default());
+            let context = serde_json::from_value(json!({
+                "inputs": prepared.inputs, "variables": {}, "deviceId": "emulator-5554", "currentStepId": null,
+                "logs": [], "runId": run_id, "callStack": [workflow.id],
+            })).unwrap();
+            let engine = crate::workflow::execute_workflow_run(window.clone(), workflow.clone(), context, 0, false, None).await.unwrap();
+            let _ = crate::workflow::delete_workflow_checkpoint(run_id).await;
+            let engine_calls = take_calls(&dir);
+
+            let script = dir.join(format!("{}.py", workflow.id));
+            std::fs::write(&script, workflow_to_python(&workflow).unwrap()).unwrap();
+            let output = std::process::Command::new(python)
+                .arg(&script)
+                .args(["--device", "emulator-5554", "--python", python, "--executor"])
+                .arg(&stub)
+                .output()
+                .unwrap();
+            let exported: Value = serde_json::from_slice(&output.stdout)
+                .unwrap_or_else(|e| panic!("{}: {}\n{}", workflow.id, e, String::from_utf8_lossy(&output.stderr)));
+
+            assert_eq!(comparable(&exported), comparable(&serde_json::to_value(&engine).unwrap()), "{}", workflow.id);
+            assert_eq!(take_calls(&dir), engine_calls, "{}", workflow.id);
+            assert_eq!(output.status.success(), engine.success);
+            statuses.push((workflow.id.clone(), engine.status.clone(), engine.outputs.get("seen").cloned()));
+        }
+        assert_eq!(statuses, vec![
+            ("golden_loop".to_string(), "completed".to_string(), Some(json!([0, 2]))),
+            ("golden_errors".to_string(), "failed".to_string(), None),
+            ("golden_timeout".to_string(), "timeout".to_string(), None),
+        ]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
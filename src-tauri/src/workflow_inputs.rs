@@ -0,0 +1,241 @@
+// Workflow Inputs Module - Apply defaults, coerce types and check constraints of workflow inputs
+// Chuẩn hoá input trước khi chạy: "20" -> 20, kiểm tra min/max, select options, input bắt buộc
+
+use crate::workflow::{WorkflowDefinition, WorkflowInput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::command;
+
+// ============================================
+// Data Models
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputError {
+    pub name: String,
+    /// "missing", "invalid_type", "below_min", "above_max", "invalid_option"
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputValidation {
+    pub valid: bool,
+    /// Inputs after defaults and coercion (undeclared inputs are passed through)
+    pub inputs: HashMap<String, Value>,
+    pub errors: Vec<InputError>,
+}
+
+impl InputValidation {
+    /// One line summary for logs and WorkflowResult.error
+    pub fn error_summary(&self) -> String {
+        self.errors.iter().map(|e| e.message.clone()).collect::<Vec<_>>().join("; ")
+    }
+}
+
+// ============================================
+// Commands
+// ============================================
+
+/// Check inputs the same way run_workflow does, without running anything
+#[command]
+pub async fn validate_workflow_inputs(
+    workflow: WorkflowDefinition,
+    inputs: HashMap<String, Value>,
+) -> Result<InputValidation, String> {
+    Ok(prepare_inputs(&workflow.inputs, inputs))
+}
+
+// ============================================
+// Coercion
+// ============================================
+
+/// Apply defaults, coerce every declared input to its type and check min/max/options
+pub fn prepare_inputs(declared: &[WorkflowInput], mut inputs: HashMap<String, Value>) -> InputValidation {
+    let mut errors = Vec::new();
+
+    for input in declared {
+        let label = input.label.clone().unwrap_or_else(|| input.name.clone());
+        let provided = inputs.remove(&input.name).filter(|v| !is_blank(v, input));
+
+        let Some(raw) = provided.or_else(|| input.default.clone().filter(|v| !v.is_null())) else {
+            if is_required(input) {
+                errors.push(input_error(input, "missing", format!("'{}' is required", label)));
+            }
+            continue;
+        };
+
+        match coerce(input, &raw) {
+            Ok(value) => {
+                if let Some(error) = check_constraints(input, &label, &value) {
+                    errors.push(error);
+                }
+                inputs.insert(input.name.clone(), value);
+            }
+            Err(message) => {
+                errors.push(input_error(input, "invalid_type", format!("'{}' {}", label, message)));
+                inputs.insert(input.name.clone(), raw);
+            }
+        }
+    }
+
+    InputValidation {
+        valid: errors.is_empty(),
+        inputs,
+        errors,
+    }
+}
+
+/// Inputs without a default must be given unless explicitly optional
+fn is_required(input: &WorkflowInput) -> bool {
+    input.required.unwrap_or(input.default.is_none())
+}
+
+/// Empty strings count as "not given" for typed inputs and for required text inputs
+fn is_blank(value: &Value, input: &WorkflowInput) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) if s.trim().is_empty() => {
+            !matches!(input.input_type.as_str(), "string" | "text") || is_required(input)
+        }
+        _ => false,
+    }
+}
+
+fn coerce(input: &WorkflowInput, value: &Value) -> Result<Value, String> {
+    match input.input_type.as_str() {
+        "number" => match value {
+            Value::Number(_) => Ok(value.clone()),
+            Value::String(s) => {
+                let s = s.trim();
+                if let Ok(n) = s.parse::<i64>() {
+                    Ok(Value::from(n))
+                } else {
+                    s.parse::<f64>()
+                        .ok()
+                        .and_then(serde_json::Number::from_f64)
+                        .map(Value::Number)
+                        .ok_or_else(|| format!("must be a number, got \"{}\"", s))
+                }
+            }
+            other => Err(format!("must be a number, got {}", other)),
+        },
+        "boolean" => match value {
+            Value::Bool(_) => Ok(value.clone()),
+            Value::Number(n) if n.as_f64() == Some(0.0) => Ok(Value::Bool(false)),
+            Value::Number(n) if n.as_f64() == Some(1.0) => Ok(Value::Bool(true)),
+            Value::String(s) => match s.trim().to_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(Value::Bool(true)),
+                "false" | "0" | "no" | "off" => Ok(Value::Bool(false)),
+                other => Err(format!("must be true or false, got \"{}\"", other)),
+            },
+            other => Err(format!("must be true or false, got {}", other)),
+        },
+        "string" | "text" | "select" => match value {
+            Value::String(_) => Ok(value.clone()),
+            Value::Number(n) => Ok(Value::String(n.to_string())),
+            Value::Bool(b) => Ok(Value::String(b.to_string())),
+            other => Err(format!("must be text, got {}", other)),
+        },
+        // Unknown types are passed through untouched
+        _ => Ok(value.clone()),
+    }
+}
+
+fn check_constraints(input: &WorkflowInput, label: &str, value: &Value) -> Option<InputError> {
+    if let Some(n) = value.as_f64().filter(|_| input.input_type == "number") {
+        if let Some(min) = input.min.filter(|min| n < *min) {
+            return Some(input_error(input, "below_min", format!("'{}' must be at least {}, got {}", label, min, n)));
+        }
+        if let Some(max) = input.max.filter(|max| n > *max) {
+            return Some(input_error(input, "above_max", format!("'{}' must be at most {}, got {}", label, max, n)));
+        }
+    }
+
+    if input.input_type == "select" {
+        let options = input.options.as_deref().unwrap_or_default();
+        let selected = value.as_str().unwrap_or_default();
+        if !options.is_empty() && !options.iter().any(|o| o.value == selected) {
+            let allowed: Vec<&str> = options.iter().map(|o| o.value.as_str()).collect();
+            return Some(input_error(input, "invalid_option",
+                format!("'{}' must be one of {}, got \"{}\"", label, allowed.join(", "), selected)));
+        }
+    }
+    None
+}
+
+fn input_error(input: &WorkflowInput, code: &str, message: String) -> InputError {
+    InputError {
+        name: input.name.clone(),
+        code: code.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn declared() -> Vec<WorkflowInput> {
+        serde_json::from_value(json!([
+            {"name": "video_count", "type": "number", "default": 20, "min": 1, "max": 100},
+            {"name": "like", "type": "boolean", "default": "true"},
+            {"name": "mode", "type": "select", "options": [{"value": "fast", "label": "Fast"}, {"value": "slow", "label": "Slow"}]},
+            {"name": "note", "type": "text", "required": false}
+        ])).unwrap()
+    }
+
+    #[test]
+    fn test_prepare_inputs_coerces_and_defaults() {
+        let inputs = HashMap::from([
+            ("video_count".to_string(), json!("15")),
+            ("mode".to_string(), json!("slow")),
+            ("extra".to_string(), json!(1)),
+        ]);
+        let result = prepare_inputs(&declared(), inputs);
+        assert!(result.valid, "{:?}", result.errors);
+        assert_eq!(result.inputs["video_count"], json!(15));
+        assert_eq!(result.inputs["like"], json!(true));
+        assert_eq!(result.inputs["extra"], json!(1));
+        assert!(!result.inputs.contains_key("note"));
+    }
+
+    #[test]
+    fn test_prepare_inputs_errors() {
+        let inputs = HashMap::from([
+            ("video_count".to_string(), json!("500")),
+            ("like".to_string(), json!("maybe")),
+        ]);
+        let result = prepare_inputs(&declared(), inputs);
+        let codes: Vec<(&str, &str)> = result.errors.iter().map(|e| (e.name.as_str(), e.code.as_str())).collect();
+        assert!(!result.valid);
+        assert_eq!(codes, vec![("video_count", "above_max"), ("like", "invalid_type"), ("mode", "missing")]);
+
+        let result = prepare_inputs(&declared(), HashMap::from([("mode".to_string(), json!("turbo"))]));
+        assert_eq!(result.errors[0].code, "invalid_option");
+    }
+
+    #[test]
+    fn test_prepare_inputs_blank_text() {
+        let declared: Vec<WorkflowInput> = serde_json::from_value(json!([
+            {"name": "keyword", "type": "text"},
+            {"name": "note", "type": "text", "required": false},
+            {"name": "title", "type": "string", "default": "Untitled"}
+        ])).unwrap();
+        let inputs = HashMap::from([
+            ("keyword".to_string(), json!("  ")),
+            ("note".to_string(), json!("")),
+            ("title".to_string(), json!("")),
+        ]);
+        let result = prepare_inputs(&declared, inputs);
+        // Text without a default is required, so "" counts as missing
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!((result.errors[0].name.as_str(), result.errors[0].code.as_str()), ("keyword", "missing"));
+        assert_eq!(result.inputs["note"], json!(""));
+        assert_eq!(result.inputs["title"], json!(""));
+    }
+}
@@ -0,0 +1,491 @@
+// Workflow Library Module - Manage workflow definitions in the app-data dir with immutable versions
+// Mỗi lần lưu tạo 1 version không đổi được, có thể so sánh từng step giữa 2 version và rollback
+
+use crate::workflow::{load_workflow_definition, WorkflowDefinition};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tauri::command;
+
+// ============================================
+// Data Models
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowSummary {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub step_count: usize,
+    /// Shipped with the app (read-only until saved into the library)
+    pub builtin: bool,
+    pub latest_version: Option<u32>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowVersion {
+    pub workflow_id: String,
+    pub version: u32,
+    pub name: String,
+    pub step_count: usize,
+    pub message: Option<String>,
+    pub saved_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionFile {
+    #[serde(flatten)]
+    meta: WorkflowVersion,
+    workflow: WorkflowDefinition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepChange {
+    pub step_id: String,
+    /// "added", "removed", "modified" or "moved"
+    pub change: String,
+    /// Location such as "steps[2].then[0]" in each version
+    pub from_path: Option<String>,
+    pub to_path: Option<String>,
+    /// Changed step fields (nested steps are compared on their own)
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowDiff {
+    pub workflow_id: String,
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Changed workflow fields other than steps (name, inputs, timeout...)
+    pub fields: Vec<String>,
+    pub steps: Vec<StepChange>,
+}
+
+// ============================================
+// Storage
+// ============================================
+
+/// Same directory load_workflow_definition searches first, so call steps see library workflows
+fn library_dir() -> PathBuf {
+    crate::utils::app_data_dir().join("workflows")
+}
+
+fn versions_dir(workflow_id: &str) -> PathBuf {
+    crate::utils::app_data_dir().join("workflow_versions").join(workflow_id)
+}
+
+/// "TikTok Farm #2" -> "tiktok-farm-2"
+pub(crate) fn slugify(name: &str) -> String {
+    let slug: String = name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    slug.split('-').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("-")
+}
+
+fn read_versions(workflow_id: &str) -> Vec<VersionFile> {
+    let Ok(entries) = std::fs::read_dir(versions_dir(workflow_id)) else { return vec![] };
+    let mut versions: Vec<VersionFile> = entries.flatten()
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    versions.sort_by_key(|v| v.meta.version);
+    versions
+}
+
+fn read_version(workflow_id: &str, version: u32) -> Result<VersionFile, String> {
+    crate::utils::check_file_id("workflow", workflow_id)?;
+    let path = versions_dir(workflow_id).join(format!("{}.json", version));
+    let content = std::fs::read_to_string(&path)
+        .map_err(|_| format!("Version {} of workflow '{}' not found", version, workflow_id))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid version file {}: {}", path.display(), e))
+}
+
+/// Highest N among the `N.json` version files, readable or not
+fn latest_version_number(workflow_id: &str) -> u32 {
+    let Ok(entries) = std::fs::read_dir(versions_dir(workflow_id)) else { return 0 };
+    entries.flatten()
+        .filter_map(|entry| entry.path().file_stem()?.to_str()?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Write the next version file; create_new never replaces a version, a number taken
+/// by a concurrent save moves on to the following one
+fn write_new_version(workflow: &WorkflowDefinition, message: Option<String>) -> Result<WorkflowVersion, String> {
+    let dir = versions_dir(&workflow.id);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let mut version = latest_version_number(&workflow.id) + 1;
+    loop {
+        let path = dir.join(format!("{}.json", version));
+        let file = match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                version += 1;
+                continue;
+            }
+            Err(e) => return Err(format!("Cannot write {}: {}", path.display(), e)),
+        };
+        let meta = WorkflowVersion {
+            workflow_id: workflow.id.clone(),
+            version,
+            name: workflow.name.clone(),
+            step_count: workflow.steps.len(),
+            message,
+            saved_at: chrono::Utc::now().to_rfc3339(),
+        };
+        serde_json::to_writer_pretty(file, &VersionFile { meta: meta.clone(), workflow: workflow.clone() })
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        return Ok(meta);
+    }
+}
+
+fn write_json<T: Serialize>(path: &std::path::Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+/// Write the workflow and a new immutable version (nothing new when it did not change)
+fn save_with_version(mut workflow: WorkflowDefinition, message: Option<String>) -> Result<WorkflowVersion, String> {
+    if workflow.name.trim().is_empty() {
+        return Err("Workflow name is required".to_string());
+    }
+    if workflow.id.trim().is_empty() {
+        workflow.id = format!("{}-{}", slugify(&workflow.name), chrono::Utc::now().timestamp());
+    }
+    crate::utils::check_file_id("workflow", &workflow.id)?;
+    workflow.is_builtin = None;
+    if workflow.created_at.is_none() {
+        workflow.created_at = Some(chrono::Utc::now().to_rfc3339());
+    }
+
+    let versions = read_versions(&workflow.id);
+    if let Some(latest) = versions.last() {
+        let unchanged = serde_json::to_value(&latest.workflow).ok() == serde_json::to_value(&workflow).ok();
+        if unchanged && library_dir().join(format!("{}.json", workflow.id)).is_file() {
+            return Ok(latest.meta.clone());
+        }
+    }
+
+    let meta = write_new_version(&workflow, message)?;
+    write_json(&library_dir().join(format!("{}.json", workflow.id)), &workflow)?;
+
+    println!("[WORKFLOW_LIBRARY] Saved '{}' as version {}", workflow.id, meta.version);
+    Ok(meta)
+}
+
+// ============================================
+// Commands
+// ============================================
+
+/// Library workflows plus bundled ones that were never saved into the library
+#[command]
+pub async fn list_library_workflows() -> Result<Vec<WorkflowSummary>, String> {
+    let user_dir = library_dir();
+    let mut seen = HashSet::new();
+    let mut summaries = Vec::new();
+
+    for dir in crate::workflow::get_workflows_dirs().into_iter().filter(|d| d.is_dir()) {
+        let builtin = dir != user_dir;
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(workflow) = std::fs::read_to_string(&path).ok()
+                .and_then(|content| serde_json::from_str::<WorkflowDefinition>(&content).ok()) else { continue };
+            if !seen.insert(workflow.id.clone()) {
+                continue;
+            }
+            let latest = read_versions(&workflow.id).pop().map(|v| v.meta);
+            summaries.push(WorkflowSummary {
+                id: workflow.id,
+                name: workflow.name,
+                description: workflow.description,
+                category: workflow.category,
+                step_count: workflow.steps.len(),
+                builtin,
+                latest_version: latest.as_ref().map(|v| v.version),
+                updated_at: latest.map(|v| v.saved_at),
+            });
+        }
+    }
+
+    summaries.sort_by_key(|s| s.name.to_lowercase());
+    Ok(summaries)
+}
+
+#[command]
+pub async fn load_library_workflow(workflow_id: String) -> Result<WorkflowDefinition, String> {
+    load_workflow_definition(&workflow_id)
+}
+
+/// Save (create or update) a workflow, returns the version it was stored as
+#[command]
+pub async fn save_library_workflow(workflow: WorkflowDefinition, message: Option<String>) -> Result<WorkflowVersion, String> {
+    save_with_version(workflow, message)
+}
+
+/// Copy a workflow under a new id, returns the copy
+#[command]
+pub async fn duplicate_library_workflow(workflow_id: String, name: Option<String>) -> Result<WorkflowDefinition, String> {
+    let mut workflow = load_workflow_definition(&workflow_id)?;
+    workflow.name = name.unwrap_or_else(|| format!("{} (Copy)", workflow.name));
+    workflow.id = String::new();
+    workflow.created_at = None;
+    let meta = save_with_version(workflow, Some(format!("Duplicated from '{}'", workflow_id)))?;
+    Ok(read_version(&meta.workflow_id, meta.version)?.workflow)
+}
+
+/// Delete a library workflow; versions are kept (so it can be rolled back) unless delete_versions
+#[command]
+pub async fn delete_library_workflow(workflow_id: String, delete_versions: Option<bool>) -> Result<bool, String> {
+    crate::utils::check_file_id("workflow", &workflow_id)?;
+    let path = library_dir().join(format!("{}.json", workflow_id));
+    if !path.is_file() {
+        return Err(format!("Workflow '{}' is not in the library", workflow_id));
+    }
+    std::fs::remove_file(&path).map_err(|e| format!("Cannot delete workflow: {}", e))?;
+    if delete_versions.unwrap_or(false) {
+        let _ = std::fs::remove_dir_all(versions_dir(&workflow_id));
+    }
+    Ok(true)
+}
+
+/// Versions of a workflow, newest first
+#[command]
+pub async fn list_workflow_versions(workflow_id: String) -> Result<Vec<WorkflowVersion>, String> {
+    crate::utils::check_file_id("workflow", &workflow_id)?;
+    Ok(read_versions(&workflow_id).into_iter().rev().map(|v| v.meta).collect())
+}
+
+#[command]
+pub async fn get_workflow_version(workflow_id: String, version: u32) -> Result<WorkflowDefinition, String> {
+    Ok(read_version(&workflow_id, version)?.workflow)
+}
+
+#[command]
+pub async fn diff_workflow_versions(workflow_id: String, from_version: u32, to_version: u32) -> Result<WorkflowDiff, String> {
+    let from = read_version(&workflow_id, from_version)?.workflow;
+    let to = read_version(&workflow_id, to_version)?.workflow;
+    let (fields, steps) = diff_workflows(&from, &to)?;
+    Ok(WorkflowDiff {
+        workflow_id,
+        from_version,
+        to_version,
+        fields,
+        steps,
+    })
+}
+
+/// Restore an old version by saving it again as the newest version
+#[command]
+pub async fn rollback_workflow(workflow_id: String, version: u32) -> Result<WorkflowVersion, String> {
+    let old = read_version(&workflow_id, version)?;
+    save_with_version(old.workflow, Some(format!("Rollback to version {}", version)))
+}
+
+// ============================================
+// Step Diff
+// ============================================
+
+/// Step id -> (path, step fields with nested step lists replaced by their ids)
+type FlatSteps = HashMap<String, (String, serde_json::Map<String, Value>)>;
+
+struct FlatWorkflow {
+    /// Workflow fields without `steps`
+    fields: serde_json::Map<String, Value>,
+    /// Step ids in document order
+    order: Vec<String>,
+    steps: FlatSteps,
+}
+
+fn is_step_list(items: &[Value]) -> bool {
+    !items.is_empty() && items.iter().all(|item| item.get("id").is_some() && item.get("type").is_some())
+}
+
+/// Walk any field (then/else/body/branches/catch/onError.recovery...) and pull nested steps out
+fn shallow(value: &Value, path: &str, order: &mut Vec<String>, out: &mut FlatSteps) -> Value {
+    match value {
+        Value::Array(items) if is_step_list(items) => {
+            let ids = items.iter().enumerate()
+                .map(|(i, step)| flatten_step(step, &format!("{}[{}]", path, i), order, out))
+                .collect();
+            Value::Array(ids)
+        }
+        Value::Array(items) => Value::Array(items.iter().enumerate()
+            .map(|(i, item)| shallow(item, &format!("{}[{}]", path, i), order, out))
+            .collect()),
+        Value::Object(map) => Value::Object(map.iter()
+            .map(|(key, item)| (key.clone(), shallow(item, &format!("{}.{}", path, key), order, out)))
+            .collect()),
+        other => other.clone(),
+    }
+}
+
+fn flatten_step(step: &Value, path: &str, order: &mut Vec<String>, out: &mut FlatSteps) -> Value {
+    let id = step.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    order.push(id.clone());
+    let mut fields = serde_json::Map::new();
+    for (key, value) in step.as_object().into_iter().flatten() {
+        let value = shallow(value, &format!("{}.{}", path, key), order, out);
+        // Unset optional fields serialize as null, treat them as absent
+        if !value.is_null() {
+            fields.insert(key.clone(), value);
+        }
+    }
+    out.insert(id.clone(), (path.to_string(), fields));
+    Value::String(id)
+}
+
+fn flatten_workflow(workflow: &WorkflowDefinition) -> Result<FlatWorkflow, String> {
+    let Value::Object(mut fields) = serde_json::to_value(workflow).map_err(|e| e.to_string())? else {
+        return Err("Workflow is not an object".to_string());
+    };
+    let steps = fields.remove("steps").unwrap_or(Value::Null);
+    let mut order = Vec::new();
+    let mut flat = HashMap::new();
+    shallow(&steps, "steps", &mut order, &mut flat);
+    Ok(FlatWorkflow { fields, order, steps: flat })
+}
+
+fn changed_keys(from: &serde_json::Map<String, Value>, to: &serde_json::Map<String, Value>) -> Vec<String> {
+    let mut keys: Vec<String> = from.keys().chain(to.keys())
+        .filter(|key| from.get(*key) != to.get(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+fn diff_workflows(from: &WorkflowDefinition, to: &WorkflowDefinition) -> Result<(Vec<String>, Vec<StepChange>), String> {
+    let from = flatten_workflow(from)?;
+    let to = flatten_workflow(to)?;
+    let (from_steps, to_steps) = (&from.steps, &to.steps);
+
+    let mut changes = Vec::new();
+    for id in &to.order {
+        let (to_path, to_fields) = &to_steps[id];
+        let change = match from_steps.get(id) {
+            None => StepChange {
+                step_id: id.clone(),
+                change: "added".to_string(),
+                from_path: None,
+                to_path: Some(to_path.clone()),
+                fields: vec![],
+            },
+            Some((from_path, from_fields)) => {
+                let fields = changed_keys(from_fields, to_fields);
+                let change = if !fields.is_empty() {
+                    "modified"
+                } else if from_path != to_path {
+                    "moved"
+                } else {
+                    continue;
+                };
+                StepChange {
+                    step_id: id.clone(),
+                    change: change.to_string(),
+                    from_path: Some(from_path.clone()),
+                    to_path: Some(to_path.clone()),
+                    fields,
+                }
+            }
+        };
+        changes.push(change);
+    }
+    for id in from.order.iter().filter(|id| !to_steps.contains_key(*id)) {
+        changes.push(StepChange {
+            step_id: id.clone(),
+            change: "removed".to_string(),
+            from_path: Some(from_steps[id].0.clone()),
+            to_path: None,
+            fields: vec![],
+        });
+    }
+
+    Ok((changed_keys(&from.fields, &to.fields), changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_save_and_rollback_versions() {
+        let workflow = |steps: Value| -> WorkflowDefinition {
+            serde_json::from_value(json!({"id": "farm-versions", "name": "Farm", "inputs": [], "outputs": [], "steps": steps})).unwrap()
+        };
+        let open = json!([{"id": "open", "type": "action", "action": "open_app"}]);
+        let v1 = save_library_workflow(workflow(open.clone()), Some("first".to_string())).await.unwrap();
+        assert_eq!(v1.version, 1);
+        // Saving the same definition again adds no version
+        let saved = load_library_workflow("farm-versions".to_string()).await.unwrap();
+        assert_eq!(save_library_workflow(saved, None).await.unwrap().version, 1);
+
+        let swipe = json!([{"id": "swipe", "type": "action", "action": "swipe_up"}]);
+        assert_eq!(save_library_workflow(workflow(swipe.clone()), None).await.unwrap().version, 2);
+
+        // An unreadable version file still holds its number
+        std::fs::write(versions_dir("farm-versions").join("3.json"), "{").unwrap();
+        assert_eq!(save_library_workflow(workflow(json!([])), None).await.unwrap().version, 4);
+        assert_eq!(std::fs::read_to_string(versions_dir("farm-versions").join("3.json")).unwrap(), "{");
+
+        let rolled_back = rollback_workflow("farm-versions".to_string(), 1).await.unwrap();
+        assert_eq!(rolled_back.version, 5);
+        assert_eq!(rolled_back.message.as_deref(), Some("Rollback to version 1"));
+        assert_eq!(load_library_workflow("farm-versions".to_string()).await.unwrap().steps[0].id, "open");
+
+        let versions: Vec<u32> = list_workflow_versions("farm-versions".to_string()).await.unwrap()
+            .into_iter().map(|v| v.version).collect();
+        assert_eq!(versions, vec![5, 4, 2, 1]);
+        assert!(list_workflow_versions("../farm".to_string()).await.is_err());
+    }
+
+    #[test]
+    fn test_diff_workflows_by_step() {
+        let from: WorkflowDefinition = serde_json::from_value(json!({
+            "id": "wf", "name": "Farm", "inputs": [], "outputs": [],
+            "steps": [
+                {"id": "open", "type": "action", "action": "open_app", "params": {"package": "a"}},
+                {"id": "loop", "type": "loop", "count": "3", "body": [
+                    {"id": "swipe", "type": "action", "action": "swipe_up"},
+                    {"id": "like", "type": "action", "action": "tap_element", "params": {"text": "Like"}}
+                ]}
+            ]
+        })).unwrap();
+        let to: WorkflowDefinition = serde_json::from_value(json!({
+            "id": "wf", "name": "Farm v2", "inputs": [], "outputs": [],
+            "steps": [
+                {"id": "open", "type": "action", "action": "open_app", "params": {"package": "b"}},
+                {"id": "swipe", "type": "action", "action": "swipe_up"},
+                {"id": "loop", "type": "loop", "count": "3", "body": [
+                    {"id": "wait", "type": "wait", "duration": "500"}
+                ]}
+            ]
+        })).unwrap();
+
+        let (fields, steps) = diff_workflows(&from, &to).unwrap();
+        let summary: Vec<(&str, &str)> = steps.iter().map(|c| (c.step_id.as_str(), c.change.as_str())).collect();
+        assert_eq!(fields, vec!["name"]);
+        assert_eq!(summary, vec![("open", "modified"), ("swipe", "moved"), ("loop", "modified"), ("wait", "added"), ("like", "removed")]);
+        assert_eq!(steps[0].fields, vec!["params"]);
+        assert_eq!(steps[2].fields, vec!["body"]);
+        assert_eq!(steps[1].from_path.as_deref(), Some("steps[1].body[0]"));
+        assert_eq!(slugify("TikTok Farm #2"), "tiktok-farm-2");
+    }
+}
@@ -0,0 +1,428 @@
+// Workflow Schema Module - JSON Schema for workflow definitions, versioned with the app
+// Schema mô tả WorkflowDefinition/WorkflowStep/ErrorConfig + params của từng action, dùng để kiểm tra JSON do AI tạo
+
+use crate::workflow_validator::ACTIONS;
+use serde_json::{json, Map, Value};
+use tauri::command;
+
+/// Schema version follows the app version so saved workflows can be matched to the schema they were checked with
+pub const WORKFLOW_SCHEMA_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Step types understood by execute_step
+const STEP_TYPES: &[&str] = &[
+    "action", "condition", "loop", "foreach", "while", "break", "continue", "set", "parallel", "python", "scripter",
+    "prompt", "wait", "wait_for", "extract", "skill", "call", "try",
+];
+
+// ============================================
+// Commands
+// ============================================
+
+/// Get the JSON Schema for workflow definitions
+#[command]
+pub async fn get_workflow_schema() -> Result<Value, String> {
+    Ok(workflow_schema())
+}
+
+/// Check a workflow JSON against the schema, returns the list of problems (empty if valid)
+#[command]
+pub async fn check_workflow_schema(workflow: Value) -> Result<Vec<String>, String> {
+    Ok(validate_against_schema(&workflow))
+}
+
+// ============================================
+// Schema
+// ============================================
+
+/// Build the JSON Schema (draft-07) for WorkflowDefinition
+pub fn workflow_schema() -> Value {
+    let steps = json!({"type": "array", "items": {"$ref": "#/definitions/WorkflowStep"}});
+    let optional_steps = json!({"type": ["array", "null"], "items": {"$ref": "#/definitions/WorkflowStep"}});
+    let optional_string = json!({"type": ["string", "null"]});
+    let optional_uint = json!({"type": ["integer", "null"], "minimum": 0});
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "$id": format!("urn:mun-sdk-ai:workflow:{}", WORKFLOW_SCHEMA_VERSION),
+        "title": "WorkflowDefinition",
+        "version": WORKFLOW_SCHEMA_VERSION,
+        "$ref": "#/definitions/WorkflowDefinition",
+        "definitions": {
+            "WorkflowDefinition": {
+                "type": "object",
+                "required": ["id", "name", "inputs", "steps", "outputs"],
+                "properties": {
+                    "id": {"type": "string", "minLength": 1},
+                    "name": {"type": "string"},
+                    "description": optional_string,
+                    "icon": optional_string,
+                    "color": optional_string,
+                    "category": optional_string,
+                    "inputs": {"type": "array", "items": {"$ref": "#/definitions/WorkflowInput"}},
+                    "steps": steps,
+                    "outputs": {"type": "array", "items": {"type": "string"}},
+                    "timeout": {"type": ["integer", "null"]},
+                    "stepDelay": optional_uint,
+                    "createdAt": optional_string,
+                    "isBuiltin": {"type": ["boolean", "null"]}
+                }
+            },
+            "WorkflowInput": {
+                "type": "object",
+                "required": ["name", "type"],
+                "properties": {
+                    "name": {"type": "string", "minLength": 1},
+                    "label": optional_string,
+                    "type": {"enum": ["string", "number", "boolean", "select", "text"]},
+                    "default": {},
+                    "options": {
+                        "type": ["array", "null"],
+                        "items": {
+                            "type": "object",
+                            "required": ["value", "label"],
+                            "properties": {"value": {"type": "string"}, "label": {"type": "string"}}
+                        }
+                    },
+                    "min": {"type": ["number", "null"]},
+                    "max": {"type": ["number", "null"]},
+                    "placeholder": optional_string,
+                    "required": {"type": ["boolean", "null"]}
+                }
+            },
+            "WorkflowStep": step_schema(&optional_steps, &optional_string, &optional_uint),
+            "ErrorConfig": {
+                "type": "object",
+                "required": ["strategy"],
+                "additionalProperties": false,
+                "properties": {
+                    "strategy": {"enum": ["abort", "skip", "retry", "fallback"]},
+                    "retries": {"type": ["integer", "null"], "minimum": 0},
+                    "fallback": optional_steps,
+                    "retryDelay": optional_uint,
+                    "backoff": {"type": ["number", "null"], "minimum": 1},
+                    "maxDelay": optional_uint,
+                    "jitter": {"type": ["number", "null"], "minimum": 0},
+                    "retryOn": optional_string,
+                    "recovery": optional_steps
+                },
+                "allOf": [
+                    {"if": {"properties": {"strategy": {"const": "fallback"}}}, "then": {"required": ["fallback"]}}
+                ]
+            },
+            "SetOperation": {
+                "type": "object",
+                "required": ["name"],
+                "additionalProperties": false,
+                "properties": {
+                    "name": {"type": "string", "minLength": 1},
+                    "value": {},
+                    "expression": optional_string,
+                    "increment": {},
+                    "append": {},
+                    "merge": {"type": ["object", "string", "null"]}
+                }
+            },
+            "ActionParams": action_params_schema()
+        }
+    })
+}
+
+fn step_schema(optional_steps: &Value, optional_string: &Value, optional_uint: &Value) -> Value {
+    let mut action_names: Vec<Value> = ACTIONS.iter().flat_map(|(names, _, _)| names.iter()).map(|n| json!(n)).collect();
+    action_names.push(Value::Null);
+
+    let mut properties = Map::new();
+    for (name, schema) in [
+        ("id", json!({"type": "string", "minLength": 1})),
+        ("type", json!({"enum": STEP_TYPES})),
+        ("name", optional_string.clone()),
+        ("action", json!({"enum": action_names})),
+        ("params", json!({"type": ["object", "null"]})),
+        ("condition", optional_string.clone()),
+        ("then", optional_steps.clone()),
+        ("else", optional_steps.clone()),
+        ("count", optional_string.clone()),
+        ("variable", optional_string.clone()),
+        ("body", optional_steps.clone()),
+        ("items", optional_string.clone()),
+        ("indexVariable", optional_string.clone()),
+        ("concurrency", json!({"type": ["integer", "null"], "minimum": 1})),
+        ("maxIterations", json!({"type": ["integer", "null"]})),
+        ("catch", optional_steps.clone()),
+        ("finally", optional_steps.clone()),
+        ("branches", json!({"type": ["array", "null"], "items": {"type": "array", "items": {"$ref": "#/definitions/WorkflowStep"}}})),
+        ("join", json!({"enum": ["all", "first_success", null]})),
+        ("failFast", json!({"type": ["boolean", "null"]})),
+        ("mergeStrategy", json!({"enum": ["last_writer", "namespace", null]})),
+        ("script", optional_string.clone()),
+        ("saveTo", optional_string.clone()),
+        ("aiPrompt", optional_string.clone()),
+        ("prompt", optional_string.clone()),
+        ("duration", optional_string.clone()),
+        ("waitCondition", optional_string.clone()),
+        ("until", json!({"enum": ["appear", "disappear", "activity", "keyboard", "keyboard_hidden", "screen_change", null]})),
+        ("activity", optional_string.clone()),
+        ("pollInterval", optional_uint.clone()),
+        ("waitTimeout", optional_uint.clone()),
+        ("delayAfter", optional_uint.clone()),
+        ("timeout", optional_uint.clone()),
+        ("selector", optional_string.clone()),
+        ("skillId", optional_string.clone()),
+        ("workflowId", optional_string.clone()),
+        ("set", json!({"type": ["array", "null"], "items": {"$ref": "#/definitions/SetOperation"}})),
+        ("onError", json!({"anyOf": [{"type": "null"}, {"$ref": "#/definitions/ErrorConfig"}]})),
+    ] {
+        properties.insert(name.to_string(), schema);
+    }
+    // Snake case spelling still accepted by serde (serde alias)
+    properties.insert("else_branch".to_string(), optional_steps.clone());
+
+    let when = |step_type: &str, then: Value| json!({
+        "if": {"properties": {"type": {"const": step_type}}},
+        "then": then
+    });
+
+    json!({
+        "type": "object",
+        "required": ["id", "type"],
+        "additionalProperties": false,
+        "properties": properties,
+        "allOf": [
+            when("action", json!({"required": ["action"], "$ref": "#/definitions/ActionParams"})),
+            when("condition", json!({"required": ["condition"]})),
+            when("loop", json!({"required": ["count", "body"]})),
+            when("foreach", json!({"required": ["items", "body"]})),
+            when("while", json!({"required": ["condition", "body"]})),
+            when("parallel", json!({"required": ["branches"]})),
+            when("python", json!({"anyOf": [{"required": ["script"]}, {"required": ["aiPrompt"]}]})),
+            when("scripter", json!({"anyOf": [{"required": ["prompt"]}, {"required": ["aiPrompt"]}]})),
+            when("prompt", json!({"required": ["prompt"]})),
+            when("extract", json!({"required": ["selector", "saveTo"]})),
+            when("skill", json!({"required": ["skillId"]})),
+            when("call", json!({"required": ["workflowId"]})),
+            when("try", json!({"required": ["body"]})),
+            when("set", json!({"required": ["set"]}))
+        ]
+    })
+}
+
+/// Required params per action, taken from the validator's action table
+fn action_params_schema() -> Value {
+    let rules: Vec<Value> = ACTIONS
+        .iter()
+        .filter(|(_, required, _)| !required.is_empty())
+        .map(|(names, required, _)| {
+            let groups: Vec<Value> = required
+                .iter()
+                .map(|group| json!({"anyOf": group.iter().map(|p| json!({"required": [p]})).collect::<Vec<_>>()}))
+                .collect();
+            json!({
+                "if": {"properties": {"action": {"enum": names}}},
+                "then": {"required": ["params"], "properties": {"params": {"type": "object", "allOf": groups}}}
+            })
+        })
+        .collect();
+    json!({"allOf": rules})
+}
+
+// ============================================
+// Validation (subset of draft-07 used by workflow_schema)
+// ============================================
+
+/// Validate a value against workflow_schema(), returns "path: message" strings
+pub fn validate_against_schema(value: &Value) -> Vec<String> {
+    let schema = workflow_schema();
+    let mut errors = Vec::new();
+    check(&schema, value, &schema, "$", &mut errors);
+    errors
+}
+
+fn check(schema: &Value, value: &Value, root: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else { return };
+
+    if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+        if let Some(target) = reference.strip_prefix("#/definitions/").and_then(|name| root["definitions"].get(name)) {
+            check(target, value, root, path, errors);
+        }
+    }
+
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+        if !allowed.iter().any(|t| type_matches(t, value)) {
+            errors.push(format!("{}: expected {}, got {}", path, allowed.join(" or "), type_name(value)));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            let names: Vec<String> = options.iter().filter(|o| !o.is_null()).map(|o| o.to_string()).collect();
+            errors.push(format!("{}: {} is not one of {}", path, value, names.join(", ")));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: expected {}", path, expected));
+        }
+    }
+    if let (Some(minimum), Some(n)) = (schema.get("minimum").and_then(|m| m.as_f64()), value.as_f64()) {
+        if n < minimum {
+            errors.push(format!("{}: must be >= {}", path, minimum));
+        }
+    }
+    if let (Some(min_length), Some(s)) = (schema.get("minLength").and_then(|m| m.as_u64()), value.as_str()) {
+        if (s.chars().count() as u64) < min_length {
+            errors.push(format!("{}: must not be empty", path));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        for name in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten() {
+            if let Some(name) = name.as_str() {
+                if !object.contains_key(name) {
+                    errors.push(format!("{}: missing required property '{}'", path, name));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, item) in object {
+            let item_path = format!("{}.{}", path, key);
+            match properties.and_then(|p| p.get(key)) {
+                Some(property) => check(property, item, root, &item_path, errors),
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    errors.push(format!("{}: unknown property", item_path));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            check(items, item, root, &format!("{}[{}]", path, i), errors);
+        }
+    }
+
+    for sub in schema.get("allOf").and_then(|a| a.as_array()).into_iter().flatten() {
+        check(sub, value, root, path, errors);
+    }
+
+    if let Some(alternatives) = schema.get("anyOf").and_then(|a| a.as_array()) {
+        let results: Vec<Vec<String>> = alternatives
+            .iter()
+            .map(|alt| {
+                let mut alt_errors = Vec::new();
+                check(alt, value, root, path, &mut alt_errors);
+                alt_errors
+            })
+            .collect();
+        if !results.iter().any(|r| r.is_empty()) {
+            // Groups of {"required": [x]} read better as "one of"
+            let names: Vec<&str> = alternatives
+                .iter()
+                .filter_map(|alt| alt.get("required")?.as_array()?.first()?.as_str())
+                .collect();
+            // Alternatives of the wrong type are not interesting, e.g. null in nullable refs
+            let type_error = format!("{}: expected", path);
+            let same_type: Vec<&Vec<String>> = results.iter().filter(|r| !r[0].starts_with(&type_error)).collect();
+            if names.len() == alternatives.len() {
+                errors.push(format!("{}: missing required property '{}'", path, names.join("' or '")));
+            } else if same_type.len() == 1 {
+                errors.extend(same_type[0].iter().cloned());
+            } else {
+                errors.push(format!("{}: does not match any allowed form", path));
+            }
+        }
+    }
+
+    if let Some(condition) = schema.get("if") {
+        let mut condition_errors = Vec::new();
+        check(condition, value, root, path, &mut condition_errors);
+        let branch = if condition_errors.is_empty() { schema.get("then") } else { schema.get("else") };
+        if let Some(branch) = branch {
+            check(branch, value, root, path, errors);
+        }
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_workflow_matches_schema() {
+        let sample: Value = serde_json::from_str(include_str!("../workflows/kuaishou-auto-watch.json")).unwrap();
+        assert_eq!(validate_against_schema(&sample), Vec::<String>::new());
+        assert_eq!(workflow_schema()["version"], WORKFLOW_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let workflow = json!({
+            "id": "w", "name": "W", "inputs": [], "outputs": [],
+            "steps": [
+                {"id": "s1", "type": "action", "action": "swipe", "params": {"x1": 1, "y1": 2, "x2": 3}},
+                {"id": "s2", "type": "loop", "count": 5, "body": [{"id": "s2-1", "type": "wait", "duraton": "100"}]},
+                {"id": "s3", "type": "action", "action": "tap", "onError": {"strategy": "fallback"}}
+            ]
+        });
+        let errors = validate_against_schema(&workflow);
+        assert!(errors.contains(&"$.steps[0].params: missing required property 'y2' or 'end_y'".to_string()));
+        assert!(errors.contains(&"$.steps[1].count: expected string or null, got number".to_string()));
+        assert!(errors.contains(&"$.steps[1].body[0].duraton: unknown property".to_string()));
+        assert!(errors.contains(&"$.steps[2]: missing required property 'params'".to_string()));
+        assert!(errors.contains(&"$.steps[2].onError: missing required property 'fallback'".to_string()));
+    }
+
+    #[test]
+    fn test_snake_case_fields_follow_serde() {
+        let workflow = json!({
+            "id": "w", "name": "W", "inputs": [], "outputs": [],
+            "steps": [
+                {"id": "s1", "type": "extract", "selector": "text=Followers", "save_to": "followers"},
+                {"id": "s2", "type": "condition", "condition": "true", "then": [], "else_branch": []}
+            ]
+        });
+        // serde drops save_to (no alias), so the schema must not accept it
+        let parsed: crate::workflow::WorkflowDefinition = serde_json::from_value(workflow.clone()).unwrap();
+        assert_eq!(parsed.steps[0].save_to, None);
+        assert!(parsed.steps[1].else_branch.is_some());
+        let errors = validate_against_schema(&workflow);
+        assert!(errors.contains(&"$.steps[0].save_to: unknown property".to_string()), "{:?}", errors);
+        assert!(errors.contains(&"$.steps[0]: missing required property 'saveTo'".to_string()), "{:?}", errors);
+        assert!(!errors.iter().any(|e| e.starts_with("$.steps[1]")), "{:?}", errors);
+
+        let mut fixed = workflow;
+        fixed["steps"][0].as_object_mut().unwrap().remove("save_to");
+        fixed["steps"][0]["saveTo"] = json!("followers");
+        assert_eq!(validate_against_schema(&fixed), Vec::<String>::new());
+    }
+}
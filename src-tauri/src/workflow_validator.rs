@@ -0,0 +1,595 @@
+// Workflow Validator Module - Static checks before a workflow touches the device
+// Kiểm tra step type, field bắt buộc, action/params, id trùng và {{biến}} chưa được định nghĩa
+
+use crate::template::template_variables;
+use crate::workflow::{WorkflowDefinition, WorkflowStep};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tauri::command;
+
+// ============================================
+// Data Models
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowDiagnostic {
+    pub severity: String, // "error", "warning"
+    /// Machine readable code, e.g. "missing_field", "unknown_action", "undefined_variable"
+    pub code: String,
+    pub message: String,
+    pub step_id: Option<String>,
+    /// Location of the step, e.g. "steps[2].body[0].then[1]"
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    pub diagnostics: Vec<WorkflowDiagnostic>,
+}
+
+/// (action names, required params - each group is "any of", optional params)
+pub(crate) type ActionSpec = (&'static [&'static str], &'static [&'static [&'static str]], &'static [&'static str]);
+
+/// Actions handled by execute_action_step
+pub(crate) const ACTIONS: &[ActionSpec] = &[
+    (&["open_app", "start_app"], &[&["package"]], &["activity"]),
+    (&["tap"], &[&["x"], &["y"]], &[]),
+    (&["tap_index", "tap_by_index"], &[&["index"]], &[]),
+    (&["tap_element", "tap_text"], &[&["text", "selector"]], &["x", "y"]),
+    (&["swipe"], &[&["x1", "start_x"], &["y1", "start_y"], &["x2", "end_x"], &["y2", "end_y"]], &["duration"]),
+    (&["swipe_up", "swipe_down", "swipe_left", "swipe_right"], &[], &[]),
+    (&["type", "input_text"], &[&["text"]], &["clear"]),
+    (&["back", "home", "enter", "recent_apps", "wake", "dismiss_popup", "get_state"], &[], &[]),
+    (&["press_key"], &[&["keycode"]], &[]),
+    (&["screenshot"], &[], &["path"]),
+    (&["long_press"], &[&["x"], &["y"]], &["duration"]),
+    (&["double_tap"], &[&["x"], &["y"]], &[]),
+];
+
+// ============================================
+// Commands
+// ============================================
+
+/// Validate a workflow definition without running it
+#[command]
+pub async fn validate_workflow(workflow: WorkflowDefinition) -> Result<ValidationReport, String> {
+    Ok(validate_workflow_definition(&workflow, &|skill_id| crate::skill::load_skill(skill_id).map(|_| ())))
+}
+
+/// Run every static check and collect diagnostics - `find_skill` errors for skill ids that do not exist
+pub fn validate_workflow_definition(
+    workflow: &WorkflowDefinition,
+    find_skill: &dyn Fn(&str) -> Result<(), String>,
+) -> ValidationReport {
+    let mut validator = Validator {
+        find_skill,
+        workflow_id: workflow.id.clone(),
+        diagnostics: vec![],
+        seen_ids: HashMap::new(),
+        loop_depth: 0,
+    };
+
+    let mut defined: HashSet<String> = workflow.inputs.iter().map(|i| i.name.clone()).collect();
+    validator.check_steps(&workflow.steps, "steps", &mut defined);
+
+    for output in &workflow.outputs {
+        if !defined.contains(output) {
+            validator.push("warning", "undefined_output", None, "outputs",
+                format!("Output '{}' is never set by a step", output));
+        }
+    }
+
+    let errors = validator.diagnostics.iter().filter(|d| d.severity == "error").count();
+    let warnings = validator.diagnostics.len() - errors;
+    ValidationReport {
+        valid: errors == 0,
+        errors,
+        warnings,
+        diagnostics: validator.diagnostics,
+    }
+}
+
+// ============================================
+// Validator
+// ============================================
+
+struct Validator<'a> {
+    find_skill: &'a dyn Fn(&str) -> Result<(), String>,
+    workflow_id: String,
+    diagnostics: Vec<WorkflowDiagnostic>,
+    /// Step id -> path of its first occurrence
+    seen_ids: HashMap<String, String>,
+    /// Number of enclosing loops, `break`/`continue` need at least one
+    loop_depth: usize,
+}
+
+impl Validator<'_> {
+    fn push(&mut self, severity: &str, code: &str, step_id: Option<&str>, path: &str, message: String) {
+        self.diagnostics.push(WorkflowDiagnostic {
+            severity: severity.to_string(),
+            code: code.to_string(),
+            message,
+            step_id: step_id.map(|s| s.to_string()),
+            path: path.to_string(),
+        });
+    }
+
+    /// Steps run in order, so `defined` grows as save_to/loop variables appear
+    fn check_steps(&mut self, steps: &[WorkflowStep], path: &str, defined: &mut HashSet<String>) {
+        for (i, step) in steps.iter().enumerate() {
+            self.check_step(step, &format!("{}[{}]", path, i), defined);
+        }
+    }
+
+    fn check_step(&mut self, step: &WorkflowStep, path: &str, defined: &mut HashSet<String>) {
+        let id = Some(step.id.as_str());
+
+        if step.id.trim().is_empty() {
+            self.push("error", "missing_id", None, path, "Step has no id".to_string());
+        } else if let Some(first) = self.seen_ids.get(&step.id) {
+            let message = format!("Duplicate step id '{}' (first used at {})", step.id, first);
+            self.push("error", "duplicate_id", id, path, message);
+        } else {
+            self.seen_ids.insert(step.id.clone(), path.to_string());
+        }
+
+        // Variables read by this step must exist before it runs
+        self.check_templates(step, path, defined);
+
+        let missing = |field: &str| format!("{} step missing '{}' field", step.step_type, field);
+        match step.step_type.as_str() {
+            "action" => match &step.action {
+                Some(action) => self.check_action(step, action, path),
+                None => self.push("error", "missing_field", id, path, missing("action")),
+            },
+            "condition" => {
+                self.require_condition(step, path);
+                if step.then.is_none() && step.else_branch.is_none() {
+                    self.push("warning", "empty_branch", id, path, "Condition has no 'then' or 'else' steps".to_string());
+                }
+                // A variable counts as defined after the condition if either branch sets it
+                let mut then_defined = defined.clone();
+                let mut else_defined = defined.clone();
+                if let Some(then) = &step.then {
+                    self.check_steps(then, &format!("{}.then", path), &mut then_defined);
+                }
+                if let Some(else_branch) = &step.else_branch {
+                    self.check_steps(else_branch, &format!("{}.else", path), &mut else_defined);
+                }
+                defined.extend(then_defined);
+                defined.extend(else_defined);
+            }
+            "loop" => {
+                match &step.count {
+                    Some(count) if !count.contains("{{") && count.trim().parse::<i32>().is_err() => {
+                        self.push("error", "invalid_field", id, path, format!("Loop count '{}' is not a number", count));
+                    }
+                    Some(_) => {}
+                    None => self.push("error", "missing_field", id, path, missing("count")),
+                }
+                defined.insert(step.variable.clone().unwrap_or_else(|| "i".to_string()));
+                self.check_loop_body(step, path, defined);
+            }
+            "foreach" => {
+                if step.items.is_none() {
+                    self.push("error", "missing_field", id, path, missing("items"));
+                }
+                if step.concurrency == Some(0) {
+                    self.push("error", "invalid_field", id, path, "Foreach concurrency must be at least 1".to_string());
+                }
+                defined.insert(step.variable.clone().unwrap_or_else(|| "item".to_string()));
+                defined.insert(step.index_variable.clone().unwrap_or_else(|| "index".to_string()));
+                self.check_loop_body(step, path, defined);
+            }
+            "while" => {
+                self.require_condition(step, path);
+                self.check_loop_body(step, path, defined);
+            }
+            "parallel" => match &step.branches {
+                Some(branches) if !branches.is_empty() => {
+                    // Loop control cannot cross a branch boundary
+                    let loop_depth = std::mem::take(&mut self.loop_depth);
+                    let mut merged = defined.clone();
+                    for (b, branch) in branches.iter().enumerate() {
+                        let mut branch_defined = defined.clone();
+                        self.check_steps(branch, &format!("{}.branches[{}]", path, b), &mut branch_defined);
+                        merged.extend(branch_defined);
+                    }
+                    self.loop_depth = loop_depth;
+                    if step.merge_strategy.as_deref() == Some("namespace") {
+                        merged.insert(step.save_to.clone().unwrap_or_else(|| step.id.clone()));
+                    }
+                    *defined = merged;
+                }
+                _ => self.push("error", "missing_field", id, path, missing("branches")),
+            },
+            "python" => {
+                if step.script.is_none() && step.ai_prompt.is_none() {
+                    self.push("error", "missing_field", id, path, "Python step requires 'script' or 'aiPrompt'".to_string());
+                }
+            }
+            "scripter" => {
+                if step.prompt.is_none() && step.ai_prompt.is_none() {
+                    self.push("error", "missing_field", id, path, "Scripter step requires 'prompt' or 'aiPrompt'".to_string());
+                }
+            }
+            "prompt" => {
+                if step.prompt.is_none() {
+                    self.push("error", "missing_field", id, path, missing("prompt"));
+                }
+            }
+            "wait" => {
+                if let Some(condition) = &step.wait_condition {
+                    self.check_expression(step, condition, path);
+                }
+            }
+            "wait_for" => self.check_wait_for(step, path),
+            "extract" => {
+                match &step.selector {
+                    Some(selector) => self.check_selector(step, selector, path),
+                    None => self.push("error", "missing_field", id, path, missing("selector")),
+                }
+                if step.save_to.is_none() {
+                    self.push("error", "missing_field", id, path, missing("save_to"));
+                }
+                let attribute = step.params.as_ref().and_then(|p| p.get("attribute")).and_then(|v| v.as_str());
+                if let Some(attribute) = attribute.filter(|a| !a.contains("{{")) {
+                    if !crate::workflow::EXTRACT_ATTRIBUTES.contains(&attribute) {
+                        self.push("error", "invalid_field", id, path, format!("Unknown extract attribute '{}'", attribute));
+                    }
+                }
+            }
+            "skill" => match &step.skill_id {
+                None => self.push("error", "missing_field", id, path, missing("skill_id")),
+                Some(skill_id) if !skill_id.contains("{{") => {
+                    if let Err(e) = (self.find_skill)(skill_id) {
+                        self.push("warning", "unknown_skill", id, path, e);
+                    }
+                }
+                Some(_) => {}
+            },
+            "call" => self.check_call(step, path, defined),
+            "set" => match &step.set {
+                Some(operations) if !operations.is_empty() => {
+                    for operation in operations {
+                        let used = [
+                            operation.value.is_some(), operation.expression.is_some(), operation.increment.is_some(),
+                            operation.append.is_some(), operation.merge.is_some(),
+                        ].iter().filter(|b| **b).count();
+                        if operation.name.trim().is_empty() {
+                            self.push("error", "missing_field", id, path, "Set entry has no 'name'".to_string());
+                        } else if used != 1 {
+                            self.push("error", "invalid_field", id, path, format!(
+                                "Set '{}' must use exactly one of value, expression, increment, append or merge", operation.name));
+                        }
+                        if let Some(expression) = &operation.expression {
+                            self.check_expression(step, expression, path);
+                        }
+                        defined.insert(operation.name.clone());
+                    }
+                }
+                _ => self.push("error", "missing_field", id, path, missing("set")),
+            },
+            "break" | "continue" => {
+                if self.loop_depth == 0 {
+                    self.push("error", "outside_loop", id, path, format!("'{}' step is not inside a loop", step.step_type));
+                }
+                if let Some(condition) = &step.condition {
+                    self.check_expression(step, condition, path);
+                }
+            }
+            "try" => {
+                // The error variable exists in catch/finally; variables set only there may be missing later
+                let mut body_defined = defined.clone();
+                self.check_body(step, path, &mut body_defined);
+                defined.insert(step.variable.clone().unwrap_or_else(|| "error".to_string()));
+                if step.catch.is_none() && step.finally.is_none() {
+                    self.push("warning", "empty_branch", id, path, "Try has no 'catch' or 'finally' steps".to_string());
+                }
+                if let Some(catch) = &step.catch {
+                    let mut catch_defined = defined.clone();
+                    self.check_steps(catch, &format!("{}.catch", path), &mut catch_defined);
+                    body_defined.extend(catch_defined);
+                }
+                if let Some(finally) = &step.finally {
+                    self.check_steps(finally, &format!("{}.finally", path), defined);
+                }
+                defined.extend(body_defined);
+            }
+            other => self.push("error", "unknown_step_type", id, path, format!("Unknown step type: {}", other)),
+        }
+
+        if let Some(save_to) = &step.save_to {
+            defined.insert(save_to.clone());
+        }
+
+        if let Some(on_error) = &step.on_error {
+            let loop_depth = std::mem::take(&mut self.loop_depth);
+            let error_path = format!("{}.onError", path);
+            match on_error.strategy.as_str() {
+                "abort" | "skip" => {}
+                "retry" => {
+                    if let Some(Err(e)) = on_error.retry_on.as_deref().map(regex::Regex::new) {
+                        self.push("error", "invalid_field", id, &error_path, format!("Invalid retryOn pattern: {}", e));
+                    }
+                    if let Some(recovery) = &on_error.recovery {
+                        let mut recovery_defined = defined.clone();
+                        self.check_steps(recovery, &format!("{}.recovery", error_path), &mut recovery_defined);
+                    }
+                }
+                "fallback" => match &on_error.fallback {
+                    Some(fallback) => {
+                        let mut fallback_defined = defined.clone();
+                        self.check_steps(fallback, &format!("{}.fallback", error_path), &mut fallback_defined);
+                    }
+                    None => self.push("error", "missing_field", id, &error_path,
+                        "Fallback strategy requires 'fallback' steps".to_string()),
+                },
+                other => self.push("error", "invalid_field", id, &error_path,
+                    format!("Unknown error strategy: {}", other)),
+            }
+            self.loop_depth = loop_depth;
+        }
+    }
+
+    fn check_loop_body(&mut self, step: &WorkflowStep, path: &str, defined: &mut HashSet<String>) {
+        self.loop_depth += 1;
+        self.check_body(step, path, defined);
+        self.loop_depth -= 1;
+    }
+
+    fn check_body(&mut self, step: &WorkflowStep, path: &str, defined: &mut HashSet<String>) {
+        match &step.body {
+            Some(body) => self.check_steps(body, &format!("{}.body", path), defined),
+            None => self.push("error", "missing_field", Some(&step.id), path,
+                format!("{} step missing 'body' field", step.step_type)),
+        }
+    }
+
+    fn require_condition(&mut self, step: &WorkflowStep, path: &str) {
+        match &step.condition {
+            Some(condition) => self.check_expression(step, condition, path),
+            None => self.push("error", "missing_field", Some(&step.id), path,
+                format!("{} step missing 'condition' field", step.step_type)),
+        }
+    }
+
+    fn check_expression(&mut self, step: &WorkflowStep, expression: &str, path: &str) {
+        if let Err(e) = crate::expression::referenced_variables(expression) {
+            self.push("error", "invalid_condition", Some(&step.id), path,
+                format!("Invalid condition '{}': {}", expression, e));
+        }
+    }
+
+    fn check_selector(&mut self, step: &WorkflowStep, selector: &str, path: &str) {
+        // Templated selectors can only be checked at runtime
+        if selector.contains("{{") {
+            return;
+        }
+        if let Err(e) = crate::selector::select(&[], selector) {
+            self.push("error", "invalid_selector", Some(&step.id), path, e);
+        }
+    }
+
+    fn check_action(&mut self, step: &WorkflowStep, action: &str, path: &str) {
+        let id = Some(step.id.as_str());
+        let Some((_, required, optional)) = ACTIONS.iter().find(|(names, _, _)| names.contains(&action)) else {
+            self.push("error", "unknown_action", id, path, format!("Unknown action: {}", action));
+            return;
+        };
+
+        let params: Vec<&str> = step.params.iter().flatten().map(|(k, _)| k.as_str()).collect();
+        for group in required.iter() {
+            // tap_element may take its selector from the step itself
+            let satisfied = group.iter().any(|p| params.contains(p))
+                || (group.contains(&"selector") && step.selector.is_some());
+            if !satisfied {
+                self.push("error", "missing_param", id, path,
+                    format!("Action '{}' requires param '{}'", action, group.join("' or '")));
+            }
+        }
+        for param in params {
+            if !required.iter().any(|g| g.contains(&param)) && !optional.contains(&param) {
+                self.push("warning", "unknown_param", id, path,
+                    format!("Action '{}' does not use param '{}'", action, param));
+            }
+        }
+        if let Some(selector) = step.params.as_ref().and_then(|p| p.get("selector")).and_then(|v| v.as_str()) {
+            self.check_selector(step, selector, path);
+        }
+    }
+
+    fn check_wait_for(&mut self, step: &WorkflowStep, path: &str) {
+        let id = Some(step.id.as_str());
+        let until = step.until.as_deref().unwrap_or(if step.selector.is_some() {
+            "appear"
+        } else if step.activity.is_some() {
+            "activity"
+        } else {
+            ""
+        });
+        match until {
+            "appear" | "disappear" => match &step.selector {
+                Some(selector) => self.check_selector(step, selector, path),
+                None => self.push("error", "missing_field", id, path, format!("wait_for '{}' requires 'selector'", until)),
+            },
+            "activity" if step.activity.is_none() => {
+                self.push("error", "missing_field", id, path, "wait_for 'activity' requires 'activity'".to_string());
+            }
+            "activity" | "keyboard" | "keyboard_hidden" | "screen_change" => {}
+            "" => self.push("error", "missing_field", id, path, "wait_for step needs 'until', 'selector' or 'activity'".to_string()),
+            other => self.push("error", "invalid_field", id, path, format!("Unknown wait_for condition: {}", other)),
+        }
+    }
+
+    fn check_call(&mut self, step: &WorkflowStep, path: &str, defined: &mut HashSet<String>) {
+        let id = Some(step.id.as_str());
+        let Some(workflow_id) = &step.workflow_id else {
+            self.push("error", "missing_field", id, path, "call step missing 'workflow_id' field".to_string());
+            return;
+        };
+        if *workflow_id == self.workflow_id {
+            self.push("error", "call_cycle", id, path, format!("Workflow '{}' calls itself", workflow_id));
+            return;
+        }
+        if workflow_id.contains("{{") {
+            return;
+        }
+
+        match crate::workflow::load_workflow_definition(workflow_id) {
+            Ok(child) => {
+                let params: Vec<&String> = step.params.iter().flatten().map(|(k, _)| k).collect();
+                for input in &child.inputs {
+                    if input.default.is_none() && input.required != Some(false) && !params.contains(&&input.name) {
+                        self.push("warning", "missing_param", id, path,
+                            format!("Input '{}' of workflow '{}' is not mapped", input.name, workflow_id));
+                    }
+                }
+                if step.save_to.is_none() {
+                    defined.extend(child.outputs.iter().cloned());
+                }
+            }
+            Err(e) => self.push("warning", "unknown_workflow", id, path, e),
+        }
+    }
+
+    /// Flag {{variables}} that no input or earlier step defines
+    fn check_templates(&mut self, step: &WorkflowStep, path: &str, defined: &HashSet<String>) {
+        let texts = crate::workflow::step_template_texts(step);
+        let mut names: Vec<String> = texts.iter().flat_map(|t| template_variables(t)).collect();
+        for expression in [&step.condition, &step.wait_condition].into_iter().flatten() {
+            names.extend(crate::expression::referenced_variables(expression).unwrap_or_default());
+        }
+
+        let mut reported = HashSet::new();
+        for name in names {
+            if !defined.contains(&name) && reported.insert(name.clone()) {
+                self.push("warning", "undefined_variable", Some(&step.id), path,
+                    format!("Variable '{{{{{}}}}}' is not defined by an input or an earlier save_to", name));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(report: &ValidationReport) -> Vec<(String, String)> {
+        report.diagnostics.iter().map(|d| (d.code.clone(), d.path.clone())).collect()
+    }
+
+    fn no_skills(skill_id: &str) -> Result<(), String> {
+        Err(format!("Skill '{}' not found", skill_id))
+    }
+
+    #[test]
+    fn test_validate_nested_steps() {
+        let workflow: WorkflowDefinition = serde_json::from_value(serde_json::json!({
+            "id": "wf", "name": "Test", "outputs": ["title"],
+            "inputs": [{"name": "video_count", "type": "number"}],
+            "steps": [
+                {"id": "open", "type": "action", "action": "open_app", "params": {"package": "com.app"}},
+                {"id": "watch", "type": "loop", "count": "{{video_count}}", "body": [
+                    {"id": "check", "type": "condition", "condition": "{{i}} > 2 &&", "then": [
+                        {"id": "open", "type": "action", "action": "tap", "params": {"x": "1"}},
+                    ]},
+                    {"id": "swipe", "type": "action", "action": "fling"},
+                ]},
+                {"id": "read", "type": "extract", "selector": "//Button[@text=", "saveTo": "title", "params": {"attribute": "txt"}},
+                {"id": "type", "type": "action", "action": "input_text", "params": {"text": "{{missing}}", "speed": "1"}},
+                {"id": "bad", "type": "teleport"},
+            ],
+        })).unwrap();
+
+        let report = validate_workflow_definition(&workflow, &no_skills);
+        let found = codes(&report);
+        assert!(!report.valid);
+        assert!(found.contains(&("invalid_condition".to_string(), "steps[1].body[0]".to_string())));
+        assert!(found.contains(&("duplicate_id".to_string(), "steps[1].body[0].then[0]".to_string())));
+        assert!(found.contains(&("missing_param".to_string(), "steps[1].body[0].then[0]".to_string())));
+        assert!(found.contains(&("unknown_action".to_string(), "steps[1].body[1]".to_string())));
+        assert!(found.contains(&("invalid_selector".to_string(), "steps[2]".to_string())));
+        assert!(found.contains(&("invalid_field".to_string(), "steps[2]".to_string())));
+        assert!(found.contains(&("undefined_variable".to_string(), "steps[3]".to_string())));
+        assert!(found.contains(&("unknown_param".to_string(), "steps[3]".to_string())));
+        assert!(found.contains(&("unknown_step_type".to_string(), "steps[4]".to_string())));
+        assert!(!found.iter().any(|(code, _)| code == "undefined_output"));
+    }
+
+    #[test]
+    fn test_skill_lookup() {
+        let workflow: WorkflowDefinition = serde_json::from_value(serde_json::json!({
+            "id": "skill-wf", "name": "Skills", "inputs": [], "outputs": [],
+            "steps": [
+                {"id": "known", "type": "skill", "skillId": "follow_user"},
+                {"id": "unknown", "type": "skill", "skillId": "like_all"},
+                {"id": "templated", "type": "skill", "skillId": "{{skill}}"}
+            ]
+        })).unwrap();
+        let find_skill = |skill_id: &str| match skill_id {
+            "follow_user" => Ok(()),
+            _ => Err(format!("Skill '{}' not found", skill_id)),
+        };
+        let report = validate_workflow_definition(&workflow, &find_skill);
+        let found = codes(&report);
+        assert!(found.contains(&("unknown_skill".to_string(), "steps[1]".to_string())));
+        assert!(!found.iter().any(|(code, path)| code == "unknown_skill" && path != "steps[1]"));
+    }
+
+    #[test]
+    fn test_try_step_scopes() {
+        let workflow: WorkflowDefinition = serde_json::from_value(serde_json::json!({
+            "id": "try-wf", "name": "Try", "inputs": [], "outputs": [],
+            "steps": [{
+                "id": "guard", "type": "try", "variable": "err",
+                "body": [{"id": "open", "type": "action", "action": "open_app", "params": {"package": "com.app"}}],
+                "catch": [{"id": "log", "type": "prompt", "prompt": "Failed at {{err.step_id}}: {{err.message}}"}],
+                "finally": [{"id": "home", "type": "action", "action": "home"}]
+            }]
+        })).unwrap();
+        let report = validate_workflow_definition(&workflow, &no_skills);
+        assert!(report.diagnostics.is_empty(), "{:?}", report.diagnostics);
+    }
+
+    #[test]
+    fn test_loop_control_placement() {
+        let workflow: WorkflowDefinition = serde_json::from_value(serde_json::json!({
+            "id": "scroll", "name": "Scroll", "inputs": [], "outputs": [],
+            "steps": [
+                {"id": "scan", "type": "while", "condition": "true", "body": [
+                    {"id": "check", "type": "condition", "condition": "found", "then": [
+                        {"id": "stop", "type": "break"}
+                    ]},
+                    {"id": "skip", "type": "continue", "condition": "found == false"}
+                ]},
+                {"id": "stray", "type": "continue"}
+            ]
+        })).unwrap();
+        let report = validate_workflow_definition(&workflow, &no_skills);
+        let outside: Vec<&str> = report.diagnostics.iter()
+            .filter(|d| d.code == "outside_loop")
+            .filter_map(|d| d.step_id.as_deref())
+            .collect();
+        assert_eq!(outside, vec!["stray"]);
+    }
+
+    #[test]
+    fn test_template_variables() {
+        assert_eq!(template_variables("{{user.posts[0].id}} and {{ count }}"), vec!["user", "count"]);
+        assert_eq!(template_variables("{{name | upper}}{{broken"), vec!["name"]);
+    }
+
+    #[test]
+    fn test_sample_workflow_is_clean() {
+        let sample: WorkflowDefinition = serde_json::from_str(include_str!("../workflows/kuaishou-auto-watch.json")).unwrap();
+        let report = validate_workflow_definition(&sample, &no_skills);
+        assert!(report.valid, "{:?}", report.diagnostics);
+        assert!(!report.diagnostics.iter().any(|d| d.message.contains("random_wait")));
+    }
+}
@@ -200,7 +200,7 @@ const mockHandlers = {
         { id: 'step-2', type: 'wait', name: 'Chờ load', duration: '3000' },
         {
           id: 'step-3', type: 'loop', name: 'Lặp lại', count: '{{repeat_count}}', variable: 'i', body: [
-            { id: 'step-3-1', type: 'prompt', name: 'Thực hiện', prompt: 'Perform the action', save_to: 'result' },
+            { id: 'step-3-1', type: 'prompt', name: 'Thực hiện', prompt: 'Perform the action', saveTo: 'result' },
           ]
         },
       ],
@@ -213,6 +213,8 @@ const mockHandlers = {
     await new Promise(r => setTimeout(r, 3000)); // Simulate execution
     return {
       success: true,
+      runId: crypto.randomUUID(),
+      status: 'completed',
       stepsExecuted: workflow.steps?.length || 0,
       stepsFailed: 0,
       durationMs: 3000,
@@ -225,6 +227,49 @@ const mockHandlers = {
     };
   },
 
+  list_workflow_runs: async () => [],
+  cancel_workflow: async () => true,
+  pause_workflow: async () => true,
+  continue_workflow: async () => true,
+  step_workflow: async () => true,
+  skip_workflow_step: async () => true,
+  set_workflow_variable: async () => true,
+  set_workflow_breakpoints: async () => true,
+  get_workflow_debug_state: async () => [],
+  resume_workflow: async ({ runId }) => ({ success: true, runId, status: 'completed', outputs: {}, logs: [], error: null }),
+  list_workflow_checkpoints: async () => [],
+  delete_workflow_checkpoint: async () => null,
+  get_workflow_schema: async () => ({ title: 'WorkflowDefinition', version: 'mock', definitions: {} }),
+  check_workflow_schema: async () => [],
+  validate_workflow_inputs: async ({ inputs }) => ({ valid: true, inputs, errors: [] }),
+
+  // Workflow library commands
+  list_library_workflows: async () => [],
+  load_library_workflow: async ({ workflowId }) => { throw new Error(`Workflow '${workflowId}' not found in workflows directory`); },
+  save_library_workflow: async ({ workflow, message }) => ({ workflowId: workflow.id, version: 1, name: workflow.name, stepCount: workflow.steps.length, message: message ?? null, savedAt: new Date().toISOString() }),
+  duplicate_library_workflow: async ({ workflowId, name }) => ({ id: `${workflowId}-copy`, name: name || 'Copy', inputs: [], steps: [], outputs: [] }),
+  delete_library_workflow: async () => true,
+  list_workflow_versions: async () => [],
+  get_workflow_version: async ({ workflowId, version }) => { throw new Error(`Version ${version} of workflow '${workflowId}' not found`); },
+  diff_workflow_versions: async ({ workflowId, fromVersion, toVersion }) => ({ workflowId, fromVersion, toVersion, fields: [], steps: [] }),
+  rollback_workflow: async ({ workflowId, version }) => ({ workflowId, version: version + 1, name: '', stepCount: 0, message: `Rollback to version ${version}`, savedAt: new Date().toISOString() }),
+  export_workflow_python: async ({ workflow }) => `#!/usr/bin/env python3\n# ${workflow.name} - exported from workflow "${workflow.id}"\n`,
+
+  // Skill commands
+  list_skills: async () => [],
+  get_skill: async ({ skillId }) => { throw new Error(`Skill '${skillId}' not found`); },
+  save_skill: async ({ skill }) => ({ ...skill, id: skill.id || crypto.randomUUID(), updatedAt: new Date().toISOString() }),
+  delete_skill: async () => true,
+
+  // Run history commands
+  list_run_history: async () => [],
+  get_run_record: async ({ runId }) => { throw new Error(`Run ${runId} not found in history`); },
+  delete_run_record: async () => true,
+  clear_run_history: async () => 0,
+  get_history_retention: async () => ({ maxRuns: 500, maxAgeDays: 30 }),
+  set_history_retention: async ({ retention }) => retention,
+  rerun_from_history: async ({ runId }) => ({ success: true, runId, status: 'completed' }),
+
   // Task commands
   create_task: async (params) => ({
     id: crypto.randomUUID(),